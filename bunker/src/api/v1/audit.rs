@@ -0,0 +1,77 @@
+//! audit v1
+//!
+//! `GET /_api/v1/audit`: Requires "configure-cache" permission on the cache.
+//!
+//! Entries are returned in the order they were recorded. To retrieve
+//! the next page, pass the `next_cursor` of the response as `after`.
+//!
+//! Entries without a cache, such as tokens issued or revoked with
+//! `bunkeradm`, are only available through `bunkeradm audit-log`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::CacheName;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogRequest {
+    /// The cache to return entries of.
+    pub cache: CacheName,
+
+    /// Only return entries of tokens with this subject.
+    pub subject: Option<String>,
+
+    /// Only return entries of this action (e.g., "push").
+    pub action: Option<String>,
+
+    /// The cursor returned by the previous page.
+    pub after: Option<i64>,
+
+    /// The maximum number of entries to return.
+    ///
+    /// The server may return fewer entries.
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogResponse {
+    /// The entries in this page.
+    pub entries: Vec<AuditEntry>,
+
+    /// The cursor to retrieve the next page with.
+    ///
+    /// If unset, there are no more entries.
+    pub next_cursor: Option<i64>,
+}
+
+/// An entry in the audit log.
+///
+/// This is also the format of the lines in the export file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// The ID of the entry.
+    pub id: i64,
+
+    /// When the operation was performed, in RFC 3339 format.
+    pub timestamp: String,
+
+    /// The subject of the token used.
+    pub subject: Option<String>,
+
+    /// The ID of the token used.
+    pub token_id: Option<String>,
+
+    /// The operation performed.
+    pub action: String,
+
+    /// The name of the cache operated on.
+    pub cache: Option<String>,
+
+    /// The object of the operation, such as a store path.
+    pub target: Option<String>,
+
+    /// The hash of the previous entry.
+    pub prev_hash: String,
+
+    /// The hash of this entry.
+    pub hash: String,
+}
@@ -1,4 +1,4 @@
-use crate::signing::NixKeypair;
+use crate::signing::{NixKeypair, PqcKeypair};
 use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateCacheRequest {
@@ -22,6 +22,14 @@ pub struct CacheConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub public_key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_keys: Option<Vec<CachePublicKey>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keypair_rotation: Option<KeypairRotationConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pqc_keypair: Option<PqcKeypairConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pqc_public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub is_public: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub store_dir: Option<String>,
@@ -32,17 +40,121 @@ pub struct CacheConfig {
     pub upstream_cache_key_names: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub retention_period: Option<RetentionPeriodConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_proxy: Option<UpstreamProxyConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<QuotaConfig>,
+
+    /// Globs of store path names that require a token to pull.
+    ///
+    /// Matching paths can't be pulled anonymously even if the cache
+    /// is public.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restricted_paths: Option<Vec<String>>,
+
+    /// Current storage usage of the cache, in bytes.
+    ///
+    /// This is read-only, and only returned to clients with the
+    /// "configure-cache-retention" permission.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<u64>,
 }
 #[derive(Debug, Serialize, Deserialize)]
 pub enum KeypairConfig {
     Generate,
     Keypair(NixKeypair),
+
+    /// A key held by the server's external signer, referenced by
+    /// its public key.
+    External(String),
+}
+/// Rotation of the signing keypair.
+///
+/// During rotation, narinfos are signed with both the new keypair and
+/// the retiring ones, so clients can trust the new public key before
+/// the old one stops being used.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum KeypairRotationConfig {
+    /// Generates a new active keypair.
+    ///
+    /// The current keypair keeps signing for the given number of
+    /// seconds, or until it's retired manually if unset.
+    Rotate(Option<u32>),
+
+    /// Switches to a key held by the server's external signer,
+    /// referenced by its public key.
+    ///
+    /// The overlap works the same as with `Rotate`.
+    RotateToExternal(String, Option<u32>),
+
+    /// Retires all retiring keypairs immediately.
+    RetireAll,
+}
+/// A public key of a cache.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachePublicKey {
+    /// The public key in the canonical format.
+    pub public_key: String,
+
+    /// The state of the keypair.
+    pub state: KeypairState,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeypairState {
+    /// The keypair is used for signing.
+    Active,
+
+    /// The keypair is still used for signing but is being rotated out.
+    Retiring,
+}
+/// Configuration of the post-quantum signing keypair.
+///
+/// When enabled, narinfos carry an additional ML-DSA signature.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum PqcKeypairConfig {
+    Generate,
+    Keypair(PqcKeypair),
+
+    /// A key held by the server's external signer, referenced by
+    /// its public key.
+    External(String),
+
+    Disabled,
 }
 #[derive(Debug, Serialize, Deserialize)]
 pub enum RetentionPeriodConfig {
     Global,
     Period(u32),
 }
+/// A size limit of a cache.
+///
+/// When a cache exceeds its quota, garbage collection evicts the
+/// least-recently-accessed objects until it's under the quota again.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum QuotaConfig {
+    Unlimited,
+
+    /// The limit in bytes, compared against the sum of the NAR
+    /// sizes of the objects in the cache.
+    Limit(u64),
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub enum UpstreamProxyConfig {
+    Disabled,
+    Enabled(UpstreamProxy),
+}
+/// An upstream substituter that missing paths are fetched from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamProxy {
+    /// Base URL of the upstream substituter.
+    pub url: String,
+
+    /// Public keys that upstream signatures are verified against.
+    ///
+    /// Only keys whose names are also listed in
+    /// `upstream_cache_key_names` are trusted.
+    pub public_keys: Vec<String>,
+}
 impl CacheConfig {
     pub fn blank() -> Self {
         Self {
@@ -50,11 +162,19 @@ impl CacheConfig {
             substituter_endpoint: None,
             api_endpoint: None,
             public_key: None,
+            public_keys: None,
+            keypair_rotation: None,
+            pqc_keypair: None,
+            pqc_public_key: None,
             is_public: None,
             store_dir: None,
             priority: None,
             upstream_cache_key_names: None,
             retention_period: None,
+            upstream_proxy: None,
+            quota: None,
+            restricted_paths: None,
+            usage: None,
         }
     }
 }
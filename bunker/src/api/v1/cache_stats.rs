@@ -0,0 +1,48 @@
+//! cache-stats v1
+//!
+//! `GET /_api/v1/cache-stats/:cache`
+//!
+//! Requires "configure cache" permission.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Number of objects in the cache.
+    pub object_count: u64,
+
+    /// Sum of the NAR sizes of the objects.
+    ///
+    /// This is the logical size of the cache.
+    pub nar_bytes: u64,
+
+    /// Uncompressed size of the distinct chunks backing the objects.
+    ///
+    /// The ratio between `nar_bytes` and this is the deduplication
+    /// ratio within the cache.
+    pub chunk_bytes: u64,
+
+    /// Stored size of the distinct chunks backing the objects.
+    pub physical_bytes: u64,
+
+    /// Stored size of the chunks that are also used by other caches.
+    pub shared_bytes: u64,
+
+    /// Stored size of the chunks that are only used by this cache.
+    pub unique_bytes: u64,
+
+    /// The users that uploaded the most data, largest first.
+    pub top_uploaders: Vec<UploaderStats>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploaderStats {
+    /// The user, or `None` for objects uploaded anonymously.
+    pub username: Option<String>,
+
+    /// Number of objects uploaded by the user.
+    pub object_count: u64,
+
+    /// Sum of the NAR sizes of the objects uploaded by the user.
+    pub nar_bytes: u64,
+}
@@ -0,0 +1,47 @@
+//! copy-paths v1
+//!
+//! `POST /_api/v1/copy-paths`
+//!
+//! Requires "pull" permission on the source cache and "push"
+//! permission on the destination cache.
+//!
+//! The copies share the NARs of the source objects, so no data
+//! is uploaded again.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::CacheName;
+use crate::nix_store::StorePathHash;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CopyPathsRequest {
+    /// The name of the cache to copy from.
+    pub source: CacheName,
+
+    /// The name of the cache to copy to.
+    pub destination: CacheName,
+
+    /// The list of store paths to copy.
+    pub store_path_hashes: Vec<StorePathHash>,
+
+    /// Whether to copy the closures of the paths as well.
+    ///
+    /// The closure is computed from the references of the objects
+    /// in the source cache.
+    #[serde(default)]
+    pub closure: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CopyPathsResponse {
+    /// The store paths that were copied.
+    ///
+    /// Paths already in the destination cache are omitted.
+    pub copied_paths: Vec<String>,
+
+    /// The requested paths that are not in the source cache.
+    ///
+    /// When copying closures, this includes the members of the
+    /// closures that are not in the source cache.
+    pub missing_paths: Vec<StorePathHash>,
+}
@@ -0,0 +1,53 @@
+//! delete-paths v1
+//!
+//! `POST /_api/v1/delete-paths`
+//!
+//! Requires "delete" permission.
+//!
+//! Only the objects are removed from the cache. The underlying
+//! NARs and chunks are reclaimed by garbage collection once no
+//! other object uses them.
+//!
+//! Objects in the closures of pinned paths are never deleted. When
+//! deleting closures, members that other objects in the cache still
+//! reference are kept.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::CacheName;
+use crate::nix_store::StorePathHash;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletePathsRequest {
+    /// The name of the cache.
+    pub cache: CacheName,
+
+    /// The list of store paths to delete.
+    pub store_path_hashes: Vec<StorePathHash>,
+
+    /// Whether to delete the closures of the paths as well.
+    ///
+    /// The closure is computed from the references of the objects
+    /// in the cache. Referenced paths that aren't in the cache are
+    /// ignored.
+    #[serde(default)]
+    pub closure: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletePathsResponse {
+    /// The store paths that were deleted.
+    ///
+    /// Requested paths that aren't in the cache are omitted.
+    pub deleted_paths: Vec<String>,
+
+    /// The store paths that weren't deleted because they are in the
+    /// closure of a pinned path.
+    #[serde(default)]
+    pub pinned_paths: Vec<String>,
+
+    /// The closure members that weren't deleted because other objects
+    /// in the cache still reference them.
+    #[serde(default)]
+    pub retained_paths: Vec<String>,
+}
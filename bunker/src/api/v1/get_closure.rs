@@ -0,0 +1,34 @@
+//! get-closure v1
+//!
+//! `POST /_api/v1/get-closure`
+//!
+//! Requires "pull" permission.
+//!
+//! The closure is computed from the references of the objects
+//! in the cache, so the paths don't need to exist on the client.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::CacheName;
+use crate::nix_store::StorePathHash;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetClosureRequest {
+    /// The name of the cache.
+    pub cache: CacheName,
+
+    /// The list of store paths to compute the closure of.
+    pub store_path_hashes: Vec<StorePathHash>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetClosureResponse {
+    /// The store paths in the closure that are in the cache.
+    pub paths: Vec<String>,
+
+    /// The members of the closure that are not in the cache.
+    ///
+    /// The references of missing paths are unknown, so the
+    /// closure is only complete if this is empty.
+    pub missing_paths: Vec<StorePathHash>,
+}
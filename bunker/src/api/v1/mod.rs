@@ -1,3 +1,12 @@
+pub mod audit;
 pub mod cache_config;
+pub mod cache_stats;
+pub mod copy_paths;
+pub mod delete_paths;
+pub mod get_closure;
 pub mod get_missing_paths;
+pub mod objects;
+pub mod pin;
+pub mod token_info;
+pub mod tokens;
 pub mod upload_path;
@@ -0,0 +1,90 @@
+//! objects v1
+//!
+//! - `GET /_api/v1/objects/:cache`: Requires "pull" permission.
+//! - `DELETE /_api/v1/objects/:cache/:store_path_hash`: Requires "delete" permission.
+//!
+//! When listing, objects are returned in the order they were first
+//! inserted. To retrieve the next page, pass the `next_cursor` of
+//! the response as `after`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::nix_store::StorePathHash;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ListObjectsRequest {
+    /// Only return objects whose store path contains this substring.
+    pub name: Option<String>,
+
+    /// Only return objects built for this system.
+    pub system: Option<String>,
+
+    /// Only return objects uploaded by this user.
+    pub created_by: Option<String>,
+
+    /// Only return objects uploaded at or after this time, in RFC 3339 format.
+    pub created_after: Option<String>,
+
+    /// Only return objects uploaded before this time, in RFC 3339 format.
+    pub created_before: Option<String>,
+
+    /// Only return objects last accessed at or after this time, in RFC 3339 format.
+    pub accessed_after: Option<String>,
+
+    /// Only return objects last accessed before this time, in RFC 3339 format.
+    ///
+    /// Objects that have never been accessed are included.
+    pub accessed_before: Option<String>,
+
+    /// The cursor returned by the previous page.
+    pub after: Option<i64>,
+
+    /// The maximum number of objects to return.
+    ///
+    /// The server may return fewer objects.
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListObjectsResponse {
+    /// The objects in this page.
+    pub objects: Vec<ObjectInfo>,
+
+    /// The cursor to retrieve the next page with.
+    ///
+    /// If unset, there are no more objects.
+    pub next_cursor: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ObjectInfo {
+    /// The hash portion of the store path.
+    pub store_path_hash: StorePathHash,
+
+    /// The full store path.
+    pub store_path: String,
+
+    /// The system the path was built for.
+    pub system: Option<String>,
+
+    /// The deriver of the path.
+    pub deriver: Option<String>,
+
+    /// The references of the path.
+    pub references: Vec<String>,
+
+    /// The hash of the NAR.
+    pub nar_hash: String,
+
+    /// The size of the NAR.
+    pub nar_size: u64,
+
+    /// When the object was uploaded, in RFC 3339 format.
+    pub created_at: String,
+
+    /// When the object was last accessed, in RFC 3339 format.
+    pub last_accessed_at: Option<String>,
+
+    /// The user that uploaded the object.
+    pub created_by: Option<String>,
+}
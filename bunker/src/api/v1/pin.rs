@@ -0,0 +1,58 @@
+//! pins v1
+//!
+//! - `GET /_api/v1/pins/:cache`
+//! - `POST /_api/v1/pins/:cache`
+//! - `DELETE /_api/v1/pins/:cache/:store_path_hash`
+//!
+//! Requires "pin" permission.
+//!
+//! Pinned store paths and their closures are never deleted by
+//! time-based garbage collection.
+
+use serde::{Deserialize, Serialize};
+
+use crate::nix_store::StorePathHash;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatePinRequest {
+    /// The hash portion of the store path to pin.
+    ///
+    /// The path doesn't need to be in the cache yet.
+    pub store_path_hash: StorePathHash,
+
+    /// An optional human-readable label.
+    pub label: Option<String>,
+
+    /// The number of seconds after which the pin expires.
+    ///
+    /// If unset, the pin never expires. An existing pin of
+    /// the same path is replaced.
+    pub expires_in: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListPinsResponse {
+    /// The pins in the cache that haven't expired.
+    pub pins: Vec<PinInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PinInfo {
+    /// The hash portion of the pinned store path.
+    pub store_path_hash: StorePathHash,
+
+    /// The full store path, if the object is in the cache.
+    pub store_path: Option<String>,
+
+    /// The label of the pin.
+    pub label: Option<String>,
+
+    /// When the pin was created, in RFC 3339 format.
+    pub created_at: String,
+
+    /// When the pin expires, in RFC 3339 format.
+    pub expires_at: Option<String>,
+
+    /// The user that created the pin.
+    pub created_by: Option<String>,
+}
@@ -0,0 +1,37 @@
+//! token-info v1
+//!
+//! `GET /_api/v1/token-info`
+//!
+//! Returns what the token the request is authenticated with grants.
+//! Anonymous requests receive an empty response.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TokenInfo {
+    /// The subject of the token.
+    pub subject: Option<String>,
+
+    /// The ID of the token.
+    ///
+    /// Tokens without an ID cannot be revoked.
+    pub token_id: Option<String>,
+
+    /// When the token expires, in RFC 3339 format.
+    pub expires_at: Option<String>,
+
+    /// Permissions granted to caches.
+    ///
+    /// Patterns are listed in the order they are matched. Public
+    /// caches can be pulled from regardless.
+    pub caches: Vec<CacheGrant>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheGrant {
+    /// The cache name pattern, which may include wildcards.
+    pub pattern: String,
+
+    /// The names of the permissions granted.
+    pub permissions: Vec<String>,
+}
@@ -0,0 +1,35 @@
+//! tokens v1
+//!
+//! `POST /_api/v1/tokens`
+//!
+//! Issues a token on behalf of the token the request is authenticated
+//! with. The new token can only grant permissions the current token
+//! has, and cannot outlive it.
+
+use serde::{Deserialize, Serialize};
+
+use super::token_info::CacheGrant;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTokenRequest {
+    /// The number of seconds after which the token expires.
+    pub validity: u64,
+
+    /// Permissions to grant to caches.
+    ///
+    /// Patterns are matched in the order they are listed, with
+    /// exact cache names taking precedence.
+    pub caches: Vec<CacheGrant>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTokenResponse {
+    /// The encoded token.
+    pub token: String,
+
+    /// The ID of the token.
+    pub token_id: Option<String>,
+
+    /// When the token expires, in RFC 3339 format.
+    pub expires_at: String,
+}
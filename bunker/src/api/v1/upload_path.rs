@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use serde_with::{DefaultOnError, serde_as};
+use serde_with::{serde_as, DefaultOnError};
 
 use crate::cache::CacheName;
 use crate::hash::Hash;
@@ -19,6 +19,11 @@ pub struct UploadPathNarInfo {
     pub ca: Option<String>,
     pub nar_hash: Hash,
     pub nar_size: usize,
+
+    /// Whether a token is required to pull the path, even if the
+    /// cache is public.
+    #[serde(default)]
+    pub restricted: bool,
 }
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
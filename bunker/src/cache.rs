@@ -125,13 +125,55 @@ impl CacheNamePattern {
         })
     }
 
+    /// Returns the pattern as a string.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
     /// Tests if the pattern matches a name.
     pub fn matches(&self, name: &CacheName) -> bool {
+        self.matches_str(name.as_str())
+    }
+
+    /// Tests if the pattern matches all names another pattern matches.
+    pub fn contains(&self, other: &CacheNamePattern) -> bool {
+        // Wildcards in the other pattern can only be matched by our
+        // own wildcards, since names can't contain '*'
+        self.matches_str(&other.pattern)
+    }
+
+    /// Tests if the pattern may match some name another pattern matches.
+    ///
+    /// This is conservative and may return true for some patterns that
+    /// never match the same name (e.g., `a*b` and `ab*c`).
+    pub fn may_overlap(&self, other: &CacheNamePattern) -> bool {
+        if !self.pattern.contains('*') {
+            return other.matches_str(&self.pattern);
+        }
+        if !other.pattern.contains('*') {
+            return self.matches_str(&other.pattern);
+        }
+
+        let (prefix, suffix) = self.literal_affixes();
+        let (other_prefix, other_suffix) = other.literal_affixes();
+
+        (prefix.starts_with(other_prefix) || other_prefix.starts_with(prefix))
+            && (suffix.ends_with(other_suffix) || other_suffix.ends_with(suffix))
+    }
+
+    fn matches_str(&self, name: &str) -> bool {
         match &self.matcher {
-            Some(matcher) => matcher.matches(name.as_str()),
-            None => self.pattern == name.as_str(),
+            Some(matcher) => matcher.matches(name),
+            None => self.pattern == name,
         }
     }
+
+    /// Returns the parts before the first and after the last wildcard.
+    fn literal_affixes(&self) -> (&str, &str) {
+        let start = self.pattern.find('*').unwrap_or(self.pattern.len());
+        let end = self.pattern.rfind('*').map_or(0, |i| i + 1);
+        (&self.pattern[..start], &self.pattern[end..])
+    }
 }
 
 impl FromStr for CacheNamePattern {
@@ -263,4 +305,33 @@ pub mod tests {
         assert_eq!(pattern1, pattern2);
         assert_ne!(pattern, pattern1);
     }
+
+    #[test]
+    fn test_cache_name_pattern_relations() {
+        macro_rules! pattern {
+            ($p:expr) => {
+                CacheNamePattern::new($p.to_string()).unwrap()
+            };
+        }
+
+        assert!(pattern! { "*" }.contains(&pattern! { "team-*" }));
+        assert!(pattern! { "team-*" }.contains(&pattern! { "team-*" }));
+        assert!(pattern! { "team-*" }.contains(&pattern! { "team-a*" }));
+        assert!(pattern! { "team-*" }.contains(&pattern! { "team-abc" }));
+        assert!(cache! { "team-abc" }
+            .to_pattern()
+            .contains(&pattern! { "team-abc" }));
+        assert!(!pattern! { "team-a*" }.contains(&pattern! { "team-*" }));
+        assert!(!pattern! { "team-abc" }.contains(&pattern! { "team-*" }));
+        assert!(!pattern! { "*-prod" }.contains(&pattern! { "team-*" }));
+
+        assert!(pattern! { "team-*" }.may_overlap(&pattern! { "*-prod" }));
+        assert!(pattern! { "team-*" }.may_overlap(&pattern! { "team-abc" }));
+        assert!(pattern! { "team-abc" }.may_overlap(&pattern! { "team-*" }));
+        assert!(pattern! { "team-a*" }.may_overlap(&pattern! { "team*" }));
+        assert!(!pattern! { "team-*" }.may_overlap(&pattern! { "other-*" }));
+        assert!(!pattern! { "*-prod" }.may_overlap(&pattern! { "*-dev" }));
+        assert!(!pattern! { "team-abc" }.may_overlap(&pattern! { "team-xyz" }));
+        assert!(!pattern! { "team-*" }.may_overlap(&pattern! { "abc" }));
+    }
 }
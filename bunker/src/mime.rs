@@ -8,3 +8,9 @@ pub const NARINFO: &str = "text/x-nix-narinfo";
 
 /// .nar
 pub const NAR: &str = "application/x-nix-nar";
+
+/// .ls
+pub const NAR_LISTING: &str = "application/json";
+
+/// /log
+pub const BUILD_LOG: &str = "text/plain; charset=utf-8";
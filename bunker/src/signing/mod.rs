@@ -4,6 +4,14 @@
 //! we can either generate signatures on the fly per request, or cache them
 //! in the data store.
 //!
+//! ## Hybrid signatures
+//!
+//! A keypair can optionally carry an ML-DSA-65 keypair in addition to the
+//! Ed25519 one. In this mode, two signatures are produced for each object.
+//! The ML-DSA key has a distinct name (`{name}-mldsa65` by default), so
+//! stock Nix skips the post-quantum signature as one from an untrusted key
+//! and keeps verifying the Ed25519 one.
+//!
 //! ## String format
 //!
 //! All signing-related strings in Nix follow the same format (henceforth
@@ -28,12 +36,19 @@ use serde::{de, ser, Deserialize, Serialize};
 use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, DecodeError, Engine};
 use displaydoc::Display;
 use ed25519_compact::{Error as SignatureError, KeyPair, PublicKey, Signature};
+use oqs::sig::{Algorithm, Sig};
 
 use crate::error::BunkerResult;
 
 #[cfg(test)]
 mod tests;
 
+/// The post-quantum signature algorithm.
+const PQC_ALGORITHM: Algorithm = Algorithm::MlDsa65;
+
+/// The suffix of post-quantum key names derived from Ed25519 key names.
+const PQC_KEY_NAME_SUFFIX: &str = "-mldsa65";
+
 /// An ed25519 keypair for signing.
 #[derive(Debug)]
 pub struct NixKeypair {
@@ -42,6 +57,9 @@ pub struct NixKeypair {
 
     /// The keypair.
     keypair: KeyPair,
+
+    /// The post-quantum keypair in hybrid mode.
+    pqc: Option<PqcKeypair>,
 }
 
 /// An ed25519 public key for verification.
@@ -52,6 +70,32 @@ pub struct NixPublicKey {
 
     /// The public key.
     public: PublicKey,
+
+    /// The post-quantum public key in hybrid mode.
+    pqc: Option<PqcPublicKey>,
+}
+
+/// An ML-DSA keypair for post-quantum signing.
+#[derive(Clone)]
+pub struct PqcKeypair {
+    /// Name of this key.
+    name: String,
+
+    /// The secret key.
+    secret: Vec<u8>,
+
+    /// The public key.
+    public: Vec<u8>,
+}
+
+/// An ML-DSA public key for post-quantum verification.
+#[derive(Debug, Clone)]
+pub struct PqcPublicKey {
+    /// Name of this key.
+    name: String,
+
+    /// The public key.
+    public: Vec<u8>,
 }
 
 /// A signing error.
@@ -90,6 +134,15 @@ pub enum Error {
     ///
     /// A valid name cannot be empty and must be contain colons (:).
     InvalidSigningKeyName(String),
+
+    /// Post-quantum signature error: {0}
+    PqcError(oqs::Error),
+
+    /// The post-quantum key cannot have the same name as the Ed25519 key ("{0}").
+    ConflictingKeyName(String),
+
+    /// No signature from key "{0}" was found.
+    MissingSignature(String),
 }
 
 impl NixKeypair {
@@ -103,9 +156,20 @@ impl NixKeypair {
         Ok(Self {
             name: name.to_string(),
             keypair,
+            pqc: None,
         })
     }
 
+    /// Generates a new hybrid keypair.
+    ///
+    /// The post-quantum key is named `{name}-mldsa65`.
+    pub fn generate_hybrid(name: &str) -> BunkerResult<Self> {
+        let keypair = Self::generate(name)?;
+        let pqc = PqcKeypair::generate(&pqc_key_name(name))?;
+
+        keypair.with_pqc_keypair(pqc)
+    }
+
     /// Imports an existing keypair from its canonical representation.
     pub fn from_str(keypair: &str) -> BunkerResult<Self> {
         let (name, bytes) = decode_string(keypair, "keypair", KeyPair::BYTES, None)?;
@@ -115,9 +179,30 @@ impl NixKeypair {
         Ok(Self {
             name: name.to_string(),
             keypair,
+            pqc: None,
         })
     }
 
+    /// Attaches a post-quantum keypair, enabling hybrid signing.
+    pub fn with_pqc_keypair(mut self, pqc: PqcKeypair) -> BunkerResult<Self> {
+        if pqc.name == self.name {
+            return Err(Error::ConflictingKeyName(pqc.name).into());
+        }
+
+        self.pqc = Some(pqc);
+        Ok(self)
+    }
+
+    /// Returns the name of the key.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the post-quantum keypair, if hybrid signing is enabled.
+    pub fn pqc_keypair(&self) -> Option<&PqcKeypair> {
+        self.pqc.as_ref()
+    }
+
     /// Returns the canonical representation of the keypair.
     ///
     /// This results in a 64-byte base64 payload that contains both the private
@@ -142,6 +227,7 @@ impl NixKeypair {
         NixPublicKey {
             name: self.name.clone(),
             public: self.keypair.pk,
+            pqc: self.pqc.as_ref().map(PqcKeypair::to_public_key),
         }
     }
 
@@ -151,6 +237,13 @@ impl NixKeypair {
         format!("{}:{}", self.name, BASE64_STANDARD.encode(bytes))
     }
 
+    /// Signs a message with the post-quantum key, returning its canonical representation.
+    ///
+    /// Returns `None` if hybrid signing is not enabled.
+    pub fn sign_pqc(&self, message: &[u8]) -> BunkerResult<Option<String>> {
+        self.pqc.as_ref().map(|pqc| pqc.sign(message)).transpose()
+    }
+
     /// Verifies a message.
     pub fn verify(&self, message: &[u8], signature: &str) -> BunkerResult<()> {
         let (_, bytes) = decode_string(signature, "signature", Signature::BYTES, Some(&self.name))?;
@@ -197,9 +290,30 @@ impl NixPublicKey {
         Ok(Self {
             name: name.to_string(),
             public,
+            pqc: None,
         })
     }
 
+    /// Attaches a post-quantum public key, enabling hybrid verification.
+    pub fn with_pqc_public_key(mut self, pqc: PqcPublicKey) -> BunkerResult<Self> {
+        if pqc.name == self.name {
+            return Err(Error::ConflictingKeyName(pqc.name).into());
+        }
+
+        self.pqc = Some(pqc);
+        Ok(self)
+    }
+
+    /// Returns the name of the key.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the post-quantum public key, if hybrid verification is enabled.
+    pub fn pqc_public_key(&self) -> Option<&PqcPublicKey> {
+        self.pqc.as_ref()
+    }
+
     /// Returns the Nix-compatible textual representation of the public key.
     ///
     /// For example, it can look like:
@@ -219,6 +333,193 @@ impl NixPublicKey {
             .verify(message, &signature)
             .map_err(|e| Error::SignatureError(e).into())
     }
+
+    /// Verifies a message against a list of signatures.
+    ///
+    /// A valid Ed25519 signature is required. In hybrid mode, a valid
+    /// post-quantum signature is required as well.
+    pub fn verify_hybrid<S: AsRef<str>>(
+        &self,
+        message: &[u8],
+        signatures: &[S],
+    ) -> BunkerResult<()> {
+        let find = |name: &str| {
+            signatures
+                .iter()
+                .map(AsRef::as_ref)
+                .find(|s| s.split_once(':').map(|(n, _)| n) == Some(name))
+                .ok_or_else(|| Error::MissingSignature(name.to_string()))
+        };
+
+        self.verify(message, find(&self.name)?)?;
+
+        if let Some(pqc) = &self.pqc {
+            pqc.verify(message, find(&pqc.name)?)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PqcKeypair {
+    /// Generates a new keypair.
+    pub fn generate(name: &str) -> BunkerResult<Self> {
+        validate_name(name)?;
+
+        let (public, secret) = pqc_sig()?.keypair().map_err(Error::PqcError)?;
+
+        Ok(Self {
+            name: name.to_string(),
+            secret: secret.into_vec(),
+            public: public.into_vec(),
+        })
+    }
+
+    /// Imports an existing keypair from its canonical representation.
+    pub fn from_str(keypair: &str) -> BunkerResult<Self> {
+        let sig = pqc_sig()?;
+        let secret_len = sig.length_secret_key();
+        let expected_len = secret_len + sig.length_public_key();
+
+        let (name, mut secret) = decode_string(keypair, "PQC keypair", expected_len, None)?;
+        let public = secret.split_off(secret_len);
+
+        Ok(Self {
+            name: name.to_string(),
+            secret,
+            public,
+        })
+    }
+
+    /// Returns the name of the key.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the canonical representation of the keypair.
+    ///
+    /// Like Ed25519 keypairs, the payload contains both the private key
+    /// and the public key, in that order.
+    pub fn export_keypair(&self) -> String {
+        let mut bytes = self.secret.clone();
+        bytes.extend_from_slice(&self.public);
+
+        format!("{}:{}", self.name, BASE64_STANDARD.encode(bytes))
+    }
+
+    /// Returns the canonical representation of the public key.
+    pub fn export_public_key(&self) -> String {
+        format!("{}:{}", self.name, BASE64_STANDARD.encode(&self.public))
+    }
+
+    /// Returns the public key portion of the keypair.
+    pub fn to_public_key(&self) -> PqcPublicKey {
+        PqcPublicKey {
+            name: self.name.clone(),
+            public: self.public.clone(),
+        }
+    }
+
+    /// Signs a message, returning its canonical representation.
+    pub fn sign(&self, message: &[u8]) -> BunkerResult<String> {
+        let sig = pqc_sig()?;
+        let secret = sig
+            .secret_key_from_bytes(&self.secret)
+            .ok_or(Error::PqcError(oqs::Error::InvalidLength))?;
+
+        let signature = sig.sign(message, secret).map_err(Error::PqcError)?;
+
+        Ok(format!(
+            "{}:{}",
+            self.name,
+            BASE64_STANDARD.encode(signature.into_vec())
+        ))
+    }
+}
+
+impl std::fmt::Debug for PqcKeypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PqcKeypair")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'de> Deserialize<'de> for PqcKeypair {
+    /// Deserializes a potentially-invalid post-quantum keypair from its canonical representation.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        use de::Error;
+        String::deserialize(deserializer)
+            .and_then(|s| Self::from_str(&s).map_err(|e| Error::custom(e.to_string())))
+    }
+}
+
+impl Serialize for PqcKeypair {
+    /// Serializes a post-quantum keypair to its canonical representation.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_str(&self.export_keypair())
+    }
+}
+
+impl PqcPublicKey {
+    /// Imports an existing public key from its canonical representation.
+    pub fn from_str(public_key: &str) -> BunkerResult<Self> {
+        let expected_len = pqc_sig()?.length_public_key();
+        let (name, public) = decode_string(public_key, "PQC public key", expected_len, None)?;
+
+        Ok(Self {
+            name: name.to_string(),
+            public,
+        })
+    }
+
+    /// Returns the name of the key.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the textual representation of the public key.
+    pub fn export(&self) -> String {
+        format!("{}:{}", self.name, BASE64_STANDARD.encode(&self.public))
+    }
+
+    /// Verifies a message.
+    pub fn verify(&self, message: &[u8], signature: &str) -> BunkerResult<()> {
+        let sig = pqc_sig()?;
+        let (_, bytes) = decode_string(
+            signature,
+            "PQC signature",
+            sig.length_signature(),
+            Some(&self.name),
+        )?;
+
+        let signature = sig
+            .signature_from_bytes(&bytes)
+            .ok_or(Error::PqcError(oqs::Error::InvalidLength))?;
+        let public = sig
+            .public_key_from_bytes(&self.public)
+            .ok_or(Error::PqcError(oqs::Error::InvalidLength))?;
+
+        sig.verify(message, signature, public)
+            .map_err(|e| Error::PqcError(e).into())
+    }
+}
+
+/// Returns the default name of the post-quantum key paired with an Ed25519 key.
+pub fn pqc_key_name(name: &str) -> String {
+    format!("{}{}", name, PQC_KEY_NAME_SUFFIX)
+}
+
+/// Returns an instance of the post-quantum signature algorithm.
+fn pqc_sig() -> BunkerResult<Sig> {
+    oqs::init();
+    Sig::new(PQC_ALGORITHM).map_err(|e| Error::PqcError(e).into())
 }
 
 /// Validates the name/label of a signing key.
@@ -66,3 +66,65 @@ fn test_signing() {
 
     keypair.verify(message, "bunker-test:lo9EfNIL4eGRuNh7DTbAAffWPpI2SlYC/8uP7JnhgmfRIUNGhSbFe8qEaKN0mFS02TuhPpXFPNtRkFcCp0hGAQ==").unwrap_err();
 }
+
+#[test]
+fn test_pqc_keypair_roundtrip() {
+    let keypair = PqcKeypair::generate("bunker-test-mldsa65").expect("Could not generate key");
+
+    let import =
+        PqcKeypair::from_str(&keypair.export_keypair()).expect("Could not re-import generated key");
+
+    assert_eq!(keypair.name, import.name);
+    assert_eq!(keypair.secret, import.secret);
+    assert_eq!(keypair.public, import.public);
+
+    let import_pub = PqcPublicKey::from_str(&keypair.export_public_key())
+        .expect("Could not re-import public key");
+
+    assert_eq!(keypair.public, import_pub.public);
+    assert_eq!(keypair.export_public_key(), import_pub.export());
+}
+
+#[test]
+fn test_hybrid_signing() {
+    let keypair = NixKeypair::generate_hybrid("bunker-test").expect("Could not generate key");
+
+    let public = keypair.to_public_key();
+    assert_eq!(
+        "bunker-test-mldsa65",
+        public.pqc_public_key().unwrap().name()
+    );
+
+    let message = b"hello world";
+
+    let signature = keypair.sign(message);
+    let pqc_signature = keypair
+        .sign_pqc(message)
+        .expect("Could not sign message")
+        .expect("Keypair has no PQC key");
+
+    assert!(pqc_signature.starts_with("bunker-test-mldsa65:"));
+
+    public
+        .verify_hybrid(message, &[&signature, &pqc_signature])
+        .unwrap();
+    public
+        .pqc_public_key()
+        .unwrap()
+        .verify(b"goodbye world", &pqc_signature)
+        .unwrap_err();
+
+    // the PQC signature is required in hybrid mode
+    public.verify_hybrid(message, &[&signature]).unwrap_err();
+
+    // the Ed25519 signature is still verifiable on its own
+    public.verify(message, &signature).unwrap();
+}
+
+#[test]
+fn test_pqc_conflicting_name() {
+    let keypair = NixKeypair::generate("bunker-test").expect("Could not generate key");
+    let pqc = PqcKeypair::generate("bunker-test").expect("Could not generate key");
+
+    keypair.with_pqc_keypair(pqc).unwrap_err();
+}
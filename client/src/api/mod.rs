@@ -1,5 +1,6 @@
 use std::error::Error as StdError;
 use std::fmt;
+
 use anyhow::Result;
 use bytes::Bytes;
 use const_format::concatcp;
@@ -13,10 +14,19 @@ use reqwest::{
     Body, Client as HttpClient, Response, StatusCode, Url,
 };
 use serde::Deserialize;
+
 use crate::config::ServerConfig;
 use crate::version::BUNKER_DISTRIBUTOR;
 use bunker::api::v1::cache_config::{CacheConfig, CreateCacheRequest};
+use bunker::api::v1::cache_stats::CacheStats;
+use bunker::api::v1::copy_paths::{CopyPathsRequest, CopyPathsResponse};
+use bunker::api::v1::delete_paths::{DeletePathsRequest, DeletePathsResponse};
+use bunker::api::v1::get_closure::{GetClosureRequest, GetClosureResponse};
 use bunker::api::v1::get_missing_paths::{GetMissingPathsRequest, GetMissingPathsResponse};
+use bunker::api::v1::objects::{ListObjectsRequest, ListObjectsResponse};
+use bunker::api::v1::pin::{CreatePinRequest, ListPinsResponse};
+use bunker::api::v1::token_info::TokenInfo;
+use bunker::api::v1::tokens::{CreateTokenRequest, CreateTokenResponse};
 use bunker::api::v1::upload_path::{
     UploadPathNarInfo, UploadPathResult, BUNKER_NAR_INFO, BUNKER_NAR_INFO_PREAMBLE_SIZE,
 };
@@ -119,6 +129,43 @@ impl ApiClient {
             Err(api_error.into())
         }
     }
+    pub async fn get_cache_stats(&self, cache: &CacheName) -> Result<CacheStats> {
+        let endpoint = self
+            .endpoint
+            .join("_api/v1/cache-stats/")?
+            .join(cache.as_str())?;
+
+        let res = self.client.get(endpoint).send().await?;
+
+        if res.status().is_success() {
+            let cache_stats = res.json().await?;
+            Ok(cache_stats)
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
+    pub async fn get_closure(
+        &self,
+        cache: &CacheName,
+        store_path_hashes: Vec<StorePathHash>,
+    ) -> Result<GetClosureResponse> {
+        let endpoint = self.endpoint.join("_api/v1/get-closure")?;
+        let payload = GetClosureRequest {
+            cache: cache.to_owned(),
+            store_path_hashes,
+        };
+
+        let res = self.client.post(endpoint).json(&payload).send().await?;
+
+        if res.status().is_success() {
+            let closure = res.json().await?;
+            Ok(closure)
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
     pub async fn get_missing_paths(
         &self,
         cache: &CacheName,
@@ -140,6 +187,54 @@ impl ApiClient {
             Err(api_error.into())
         }
     }
+    pub async fn copy_paths(
+        &self,
+        source: &CacheName,
+        destination: &CacheName,
+        store_path_hashes: Vec<StorePathHash>,
+        closure: bool,
+    ) -> Result<CopyPathsResponse> {
+        let endpoint = self.endpoint.join("_api/v1/copy-paths")?;
+        let payload = CopyPathsRequest {
+            source: source.to_owned(),
+            destination: destination.to_owned(),
+            store_path_hashes,
+            closure,
+        };
+
+        let res = self.client.post(endpoint).json(&payload).send().await?;
+
+        if res.status().is_success() {
+            let response = res.json().await?;
+            Ok(response)
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
+    pub async fn delete_paths(
+        &self,
+        cache: &CacheName,
+        store_path_hashes: Vec<StorePathHash>,
+        closure: bool,
+    ) -> Result<DeletePathsResponse> {
+        let endpoint = self.endpoint.join("_api/v1/delete-paths")?;
+        let payload = DeletePathsRequest {
+            cache: cache.to_owned(),
+            store_path_hashes,
+            closure,
+        };
+
+        let res = self.client.post(endpoint).json(&payload).send().await?;
+
+        if res.status().is_success() {
+            let response = res.json().await?;
+            Ok(response)
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
     pub async fn upload_path<S>(
         &self,
         nar_info: UploadPathNarInfo,
@@ -181,6 +276,124 @@ impl ApiClient {
             Err(api_error.into())
         }
     }
+    pub async fn upload_build_log(
+        &self,
+        cache: &CacheName,
+        drv_name: &str,
+        log: Vec<u8>,
+    ) -> Result<()> {
+        let endpoint = self
+            .endpoint
+            .join("_api/v1/upload-build-log/")?
+            .join(&format!("{}/", cache.as_str()))?
+            .join(drv_name)?;
+
+        let res = self
+            .client
+            .put(endpoint)
+            .header(USER_AGENT, HeaderValue::from_str(BUNKER_USER_AGENT)?)
+            .body(log)
+            .send()
+            .await?;
+
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
+    pub async fn list_objects(
+        &self,
+        cache: &CacheName,
+        request: &ListObjectsRequest,
+    ) -> Result<ListObjectsResponse> {
+        let endpoint = self
+            .endpoint
+            .join("_api/v1/objects/")?
+            .join(cache.as_str())?;
+
+        let res = self.client.get(endpoint).query(request).send().await?;
+
+        if res.status().is_success() {
+            let objects = res.json().await?;
+            Ok(objects)
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
+    pub async fn list_pins(&self, cache: &CacheName) -> Result<ListPinsResponse> {
+        let endpoint = self.endpoint.join("_api/v1/pins/")?.join(cache.as_str())?;
+
+        let res = self.client.get(endpoint).send().await?;
+
+        if res.status().is_success() {
+            let pins = res.json().await?;
+            Ok(pins)
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
+    pub async fn create_pin(&self, cache: &CacheName, request: &CreatePinRequest) -> Result<()> {
+        let endpoint = self.endpoint.join("_api/v1/pins/")?.join(cache.as_str())?;
+
+        let res = self.client.post(endpoint).json(request).send().await?;
+
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
+    pub async fn delete_pin(
+        &self,
+        cache: &CacheName,
+        store_path_hash: &StorePathHash,
+    ) -> Result<()> {
+        let endpoint = self
+            .endpoint
+            .join("_api/v1/pins/")?
+            .join(&format!("{}/", cache.as_str()))?
+            .join(store_path_hash.as_str())?;
+
+        let res = self.client.delete(endpoint).send().await?;
+
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
+    pub async fn get_token_info(&self) -> Result<TokenInfo> {
+        let endpoint = self.endpoint.join("_api/v1/token-info")?;
+
+        let res = self.client.get(endpoint).send().await?;
+
+        if res.status().is_success() {
+            let token_info = res.json().await?;
+            Ok(token_info)
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
+    pub async fn create_token(&self, request: &CreateTokenRequest) -> Result<CreateTokenResponse> {
+        let endpoint = self.endpoint.join("_api/v1/tokens")?;
+
+        let res = self.client.post(endpoint).json(request).send().await?;
+
+        if res.status().is_success() {
+            let response = res.json().await?;
+            Ok(response)
+        } else {
+            let api_error = ApiError::try_from_response(res).await?;
+            Err(api_error.into())
+        }
+    }
 }
 impl StdError for ApiError {}
 impl ApiError {
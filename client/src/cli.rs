@@ -8,11 +8,16 @@ use clap_complete::Shell;
 use enum_as_inner::EnumAsInner;
 
 use crate::command::cache::{self, Cache};
+use crate::command::delete::{self, Delete};
 use crate::command::get_closure::{self, GetClosure};
 use crate::command::login::{self, Login};
+use crate::command::pin::{self, Pin};
 use crate::command::push::{self, Push};
 use crate::command::r#use::{self, Use};
+use crate::command::token::{self, Token};
+use crate::command::unpin::{self, Unpin};
 use crate::command::watch_store::{self, WatchStore};
+use crate::command::whoami::{self, Whoami};
 
 /// Bunker binary cache client.
 #[derive(Debug, Parser)]
@@ -29,7 +34,12 @@ pub enum Command {
     Use(Use),
     Push(Push),
     Cache(Cache),
+    Pin(Pin),
+    Unpin(Unpin),
+    Delete(Delete),
     WatchStore(WatchStore),
+    Whoami(Whoami),
+    Token(Token),
 
     #[clap(hide = true)]
     GetClosure(GetClosure),
@@ -55,7 +65,12 @@ pub async fn run() -> Result<()> {
         Command::Use(_) => r#use::run(opts).await,
         Command::Push(_) => push::run(opts).await,
         Command::Cache(_) => cache::run(opts).await,
+        Command::Pin(_) => pin::run(opts).await,
+        Command::Unpin(_) => unpin::run(opts).await,
+        Command::Delete(_) => delete::run(opts).await,
         Command::WatchStore(_) => watch_store::run(opts).await,
+        Command::Whoami(_) => whoami::run(opts).await,
+        Command::Token(_) => token::run(opts).await,
         Command::GetClosure(_) => get_closure::run(opts).await,
     }
 }
@@ -66,7 +81,12 @@ async fn gen_completions(shell: Option<String>) -> Result<()> {
         .parse()
         .unwrap();
 
-    clap_complete::generate(shell, &mut Opts::command(), "bunker", &mut std::io::stdout());
+    clap_complete::generate(
+        shell,
+        &mut Opts::command(),
+        "bunker",
+        &mut std::io::stdout(),
+    );
 
     Ok(())
 }
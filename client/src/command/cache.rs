@@ -1,15 +1,22 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
 use anyhow::{anyhow, Result};
 use clap::{Parser, Subcommand};
 use dialoguer::Input;
 use humantime::Duration;
+use indicatif::HumanBytes;
 
 use crate::api::ApiClient;
 use crate::cache::CacheRef;
 use crate::cli::Opts;
 use crate::config::Config;
 use bunker::api::v1::cache_config::{
-    CacheConfig, CreateCacheRequest, KeypairConfig, RetentionPeriodConfig,
+    CacheConfig, CreateCacheRequest, KeypairConfig, KeypairRotationConfig, KeypairState,
+    PqcKeypairConfig, QuotaConfig, RetentionPeriodConfig, UpstreamProxy, UpstreamProxyConfig,
 };
+use bunker::api::v1::objects::ListObjectsRequest;
+use bunker::nix_store::NixStore;
 
 /// Manage caches on an Bunker server.
 #[derive(Debug, Parser)]
@@ -24,6 +31,10 @@ enum Command {
     Configure(Configure),
     Destroy(Destroy),
     Info(Info),
+    Stats(Stats),
+    Ls(Ls),
+    Copy(CopyPaths),
+    Closure(Closure),
 }
 
 /// Create a cache.
@@ -61,6 +72,13 @@ struct Create {
     #[clap(long, default_value = "41")]
     priority: i32,
 
+    /// Sign with a key held by the server's external signer.
+    ///
+    /// The key is referenced by its public key. By default, a
+    /// new keypair is generated and stored on the server.
+    #[clap(long, value_name = "PUBLIC_KEY")]
+    external_key: Option<String>,
+
     /// The signing key name of an upstream cache.
     ///
     /// When pushing to the cache, paths signed with this key
@@ -87,10 +105,54 @@ struct Configure {
     ///
     /// The server-side signing key will be regenerated and
     /// all users will need to configure the new signing key
-    /// in `nix.conf`.
+    /// in `nix.conf`. Use `--rotate-keypair` to keep the old
+    /// key working in the meantime.
     #[clap(long)]
     regenerate_keypair: bool,
 
+    /// Rotate the signing keypair.
+    ///
+    /// A new signing keypair is generated, and the current one
+    /// keeps signing until it's retired. Run `bunker use` again
+    /// on clients to trust the new key before retiring the old one.
+    #[clap(long)]
+    rotate_keypair: bool,
+
+    /// How long the current keypair keeps signing after rotation.
+    ///
+    /// You can use expressions like "2 weeks" and "30d". If unset,
+    /// the keypair keeps signing until `--retire-keypairs` is used.
+    #[clap(long, value_name = "PERIOD", requires = "rotate_keypair")]
+    rotation_overlap: Option<Duration>,
+
+    /// Stop signing with keypairs that are being rotated out.
+    #[clap(long)]
+    retire_keypairs: bool,
+
+    /// Sign with a key held by the server's external signer.
+    ///
+    /// The key is referenced by its public key. Combine with
+    /// `--rotate-keypair` to keep the current key working in
+    /// the meantime.
+    #[clap(long, value_name = "PUBLIC_KEY")]
+    external_key: Option<String>,
+
+    /// Use a post-quantum key held by the server's external signer.
+    #[clap(long, value_name = "PUBLIC_KEY")]
+    external_pqc_key: Option<String>,
+
+    /// Generate a post-quantum signing keypair.
+    ///
+    /// Narinfos will carry an additional ML-DSA signature
+    /// alongside the Ed25519 one. Stock Nix ignores the extra
+    /// signature.
+    #[clap(long)]
+    generate_pqc_keypair: bool,
+
+    /// Remove the post-quantum signing keypair.
+    #[clap(long)]
+    disable_pqc_keypair: bool,
+
     /// Make the cache public.
     ///
     /// Use `--private` to make it private.
@@ -135,6 +197,49 @@ struct Configure {
     /// Reset the retention period of the cache to global default.
     #[clap(long)]
     reset_retention_period: bool,
+
+    /// Set the storage quota of the cache.
+    ///
+    /// When the cache is over its quota, garbage collection evicts
+    /// the least-recently-accessed paths. You can use sizes like
+    /// "200G" and "1.5TiB". Units are powers of 1024.
+    #[clap(long, value_name = "SIZE", value_parser = parse_size)]
+    quota: Option<u64>,
+
+    /// Remove the storage quota of the cache.
+    #[clap(long)]
+    remove_quota: bool,
+
+    /// Fetch missing paths from an upstream substituter.
+    ///
+    /// Paths are only accepted if they are signed by one of the
+    /// `--upstream-proxy-key`s whose name is also configured
+    /// with `--upstream-cache-key-name`.
+    #[clap(long, value_name = "URL")]
+    upstream_proxy: Option<String>,
+
+    /// A public key that paths from the upstream proxy are verified against.
+    ///
+    /// Specify this flag multiple times to add multiple keys.
+    #[clap(long = "upstream-proxy-key", value_name = "KEY")]
+    upstream_proxy_keys: Vec<String>,
+
+    /// Stop fetching missing paths from the upstream proxy.
+    #[clap(long)]
+    disable_upstream_proxy: bool,
+
+    /// Require a token to pull paths whose names match a glob.
+    ///
+    /// Matching paths can't be pulled anonymously even if the
+    /// cache is public. Specify this flag multiple times to add
+    /// multiple globs (e.g., "secret-*"). This replaces the
+    /// existing globs.
+    #[clap(long = "restricted-path", value_name = "GLOB")]
+    restricted_paths: Option<Vec<String>>,
+
+    /// Remove all restricted path globs.
+    #[clap(long, conflicts_with = "restricted_paths")]
+    clear_restricted_paths: bool,
 }
 
 /// Destroy a cache.
@@ -163,6 +268,109 @@ struct Info {
     cache: CacheRef,
 }
 
+/// Show usage statistics of a cache.
+///
+/// You need the `configure_cache` permission on the cache.
+#[derive(Debug, Clone, Parser)]
+struct Stats {
+    /// Name of the cache to query.
+    cache: CacheRef,
+}
+
+/// List the objects in a cache.
+///
+/// You need the `pull` permission on the cache.
+#[derive(Debug, Clone, Parser)]
+struct Ls {
+    /// Name of the cache to query.
+    cache: CacheRef,
+
+    /// Only list store paths containing this substring.
+    #[clap(long)]
+    name: Option<String>,
+
+    /// Only list paths built for this system.
+    #[clap(long)]
+    system: Option<String>,
+
+    /// Only list paths uploaded by this user.
+    #[clap(long, value_name = "USER")]
+    created_by: Option<String>,
+
+    /// Only list paths uploaded at or after this time.
+    ///
+    /// You can use RFC 3339 timestamps like "2024-01-31T12:00:00Z"
+    /// or durations like "2 weeks", which count back from now.
+    #[clap(long, value_name = "TIME", value_parser = parse_time)]
+    created_after: Option<String>,
+
+    /// Only list paths uploaded before this time.
+    #[clap(long, value_name = "TIME", value_parser = parse_time)]
+    created_before: Option<String>,
+
+    /// Only list paths last accessed at or after this time.
+    #[clap(long, value_name = "TIME", value_parser = parse_time)]
+    accessed_after: Option<String>,
+
+    /// Only list paths last accessed before this time.
+    ///
+    /// Paths that have never been accessed are included.
+    #[clap(long, value_name = "TIME", value_parser = parse_time)]
+    accessed_before: Option<String>,
+
+    /// The maximum number of paths to list.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    limit: Option<u64>,
+
+    /// Output the paths as JSON.
+    #[clap(long)]
+    json: bool,
+}
+
+/// Copy store paths from one cache to another.
+///
+/// The paths are copied on the server without being uploaded
+/// again. You need the `pull` permission on the source cache
+/// and the `push` permission on the destination cache.
+#[derive(Debug, Clone, Parser)]
+struct CopyPaths {
+    /// Name of the cache to copy from.
+    source: CacheRef,
+
+    /// Name of the cache to copy to.
+    ///
+    /// It must be on the same server as the source cache.
+    destination: CacheRef,
+
+    /// The store paths to copy.
+    ///
+    /// The paths don't need to exist locally.
+    paths: Vec<PathBuf>,
+
+    /// Copy the closures of the paths as well.
+    ///
+    /// The closures are computed from the references of
+    /// the paths in the source cache.
+    #[clap(long)]
+    closure: bool,
+}
+
+/// Show the closure of store paths in a cache.
+///
+/// The closure is computed from the references of the paths
+/// in the cache, so the paths don't need to exist locally.
+/// Fails if any member of the closure is missing.
+///
+/// You need the `pull` permission on the cache.
+#[derive(Debug, Clone, Parser)]
+struct Closure {
+    /// Name of the cache to query.
+    cache: CacheRef,
+
+    /// The store paths to compute the closure of.
+    paths: Vec<PathBuf>,
+}
+
 pub async fn run(opts: Opts) -> Result<()> {
     let sub = opts.command.as_cache().unwrap();
     match &sub.command {
@@ -170,6 +378,10 @@ pub async fn run(opts: Opts) -> Result<()> {
         Command::Configure(sub) => configure_cache(sub.to_owned()).await,
         Command::Destroy(sub) => destroy_cache(sub.to_owned()).await,
         Command::Info(sub) => show_cache_config(sub.to_owned()).await,
+        Command::Stats(sub) => show_cache_stats(sub.to_owned()).await,
+        Command::Ls(sub) => list_objects(sub.to_owned()).await,
+        Command::Copy(sub) => copy_paths(sub.to_owned()).await,
+        Command::Closure(sub) => show_closure(sub.to_owned()).await,
     }
 }
 
@@ -180,8 +392,10 @@ async fn create_cache(sub: Create) -> Result<()> {
     let api = ApiClient::from_server_config(server.clone())?;
 
     let request = CreateCacheRequest {
-        // TODO: Make this configurable?
-        keypair: KeypairConfig::Generate,
+        keypair: match sub.external_key {
+            Some(public_key) => KeypairConfig::External(public_key),
+            None => KeypairConfig::Generate,
+        },
         is_public: sub.public,
         priority: sub.priority,
         store_dir: sub.store_dir,
@@ -216,6 +430,46 @@ async fn configure_cache(sub: Configure) -> Result<()> {
         ));
     }
 
+    if sub.quota.is_some() && sub.remove_quota {
+        return Err(anyhow!(
+            "`--quota` and `--remove-quota` cannot be set at the same time."
+        ));
+    }
+
+    if sub.upstream_proxy.is_some() && sub.disable_upstream_proxy {
+        return Err(anyhow!(
+            "`--upstream-proxy` and `--disable-upstream-proxy` cannot be set at the same time."
+        ));
+    }
+
+    let keypair_flags = [
+        sub.regenerate_keypair,
+        sub.rotate_keypair,
+        sub.retire_keypairs,
+    ];
+    if keypair_flags.into_iter().filter(|flag| *flag).count() > 1 {
+        return Err(anyhow!(
+            "Only one of `--regenerate-keypair`, `--rotate-keypair` and `--retire-keypairs` can be set."
+        ));
+    }
+
+    if sub.external_key.is_some() && (sub.regenerate_keypair || sub.retire_keypairs) {
+        return Err(anyhow!(
+            "`--external-key` cannot be set with `--regenerate-keypair` or `--retire-keypairs`."
+        ));
+    }
+
+    let pqc_flags = [
+        sub.generate_pqc_keypair,
+        sub.disable_pqc_keypair,
+        sub.external_pqc_key.is_some(),
+    ];
+    if pqc_flags.into_iter().filter(|flag| *flag).count() > 1 {
+        return Err(anyhow!(
+            "Only one of `--generate-pqc-keypair`, `--disable-pqc-keypair` and `--external-pqc-key` can be set."
+        ));
+    }
+
     if sub.public {
         patch.is_public = Some(true);
     } else if sub.private {
@@ -228,14 +482,55 @@ async fn configure_cache(sub: Configure) -> Result<()> {
         patch.retention_period = Some(RetentionPeriodConfig::Global);
     }
 
+    if let Some(quota) = sub.quota {
+        patch.quota = Some(QuotaConfig::Limit(quota));
+    } else if sub.remove_quota {
+        patch.quota = Some(QuotaConfig::Unlimited);
+    }
+
     if sub.regenerate_keypair {
         patch.keypair = Some(KeypairConfig::Generate);
     }
 
+    if sub.rotate_keypair {
+        let overlap = sub.rotation_overlap.map(|period| period.as_secs() as u32);
+        patch.keypair_rotation = Some(match sub.external_key {
+            Some(public_key) => KeypairRotationConfig::RotateToExternal(public_key, overlap),
+            None => KeypairRotationConfig::Rotate(overlap),
+        });
+    } else if let Some(public_key) = sub.external_key {
+        patch.keypair = Some(KeypairConfig::External(public_key));
+    } else if sub.retire_keypairs {
+        patch.keypair_rotation = Some(KeypairRotationConfig::RetireAll);
+    }
+
+    if sub.generate_pqc_keypair {
+        patch.pqc_keypair = Some(PqcKeypairConfig::Generate);
+    } else if sub.disable_pqc_keypair {
+        patch.pqc_keypair = Some(PqcKeypairConfig::Disabled);
+    } else if let Some(public_key) = sub.external_pqc_key {
+        patch.pqc_keypair = Some(PqcKeypairConfig::External(public_key));
+    }
+
+    if let Some(url) = sub.upstream_proxy {
+        patch.upstream_proxy = Some(UpstreamProxyConfig::Enabled(UpstreamProxy {
+            url,
+            public_keys: sub.upstream_proxy_keys,
+        }));
+    } else if sub.disable_upstream_proxy {
+        patch.upstream_proxy = Some(UpstreamProxyConfig::Disabled);
+    }
+
     patch.store_dir = sub.store_dir;
     patch.priority = sub.priority;
     patch.upstream_cache_key_names = sub.upstream_cache_key_names;
 
+    if sub.clear_restricted_paths {
+        patch.restricted_paths = Some(Vec::new());
+    } else {
+        patch.restricted_paths = sub.restricted_paths;
+    }
+
     let api = ApiClient::from_server_config(server.clone())?;
     api.configure_cache(cache, &patch).await?;
 
@@ -298,6 +593,22 @@ async fn show_cache_config(sub: Info) -> Result<()> {
         eprintln!("           Public Key: {}", public_key);
     }
 
+    if let Some(public_keys) = cache_config.public_keys {
+        let retiring: Vec<_> = public_keys
+            .into_iter()
+            .filter(|k| k.state == KeypairState::Retiring)
+            .map(|k| k.public_key)
+            .collect();
+
+        if !retiring.is_empty() {
+            eprintln!("  Retiring Public Keys: {:?}", retiring);
+        }
+    }
+
+    if let Some(pqc_public_key) = cache_config.pqc_public_key {
+        eprintln!("       PQC Public Key: {}", pqc_public_key);
+    }
+
     if let Some(substituter_endpoint) = cache_config.substituter_endpoint {
         eprintln!("Binary Cache Endpoint: {}", substituter_endpoint);
     }
@@ -329,5 +640,252 @@ async fn show_cache_config(sub: Info) -> Result<()> {
         }
     }
 
+    if let Some(restricted_paths) = cache_config.restricted_paths {
+        if !restricted_paths.is_empty() {
+            eprintln!("     Restricted Paths: {:?}", restricted_paths);
+        }
+    }
+
+    if let Some(UpstreamProxyConfig::Enabled(proxy)) = cache_config.upstream_proxy {
+        eprintln!("       Upstream Proxy: {}", proxy.url);
+        eprintln!("  Upstream Proxy Keys: {:?}", proxy.public_keys);
+    }
+
+    if let Some(quota) = cache_config.quota {
+        match quota {
+            QuotaConfig::Limit(quota) => {
+                eprintln!("                Quota: {}", HumanBytes(quota));
+            }
+            QuotaConfig::Unlimited => {
+                eprintln!("                Quota: Unlimited");
+            }
+        }
+    }
+
+    if let Some(usage) = cache_config.usage {
+        eprintln!("                Usage: {}", HumanBytes(usage));
+    }
+
+    Ok(())
+}
+
+async fn show_cache_stats(sub: Stats) -> Result<()> {
+    let config = Config::load()?;
+
+    let (_, server, cache) = config.resolve_cache(&sub.cache)?;
+    let api = ApiClient::from_server_config(server.clone())?;
+    let stats = api.get_cache_stats(cache).await?;
+
+    eprintln!("              Objects: {}", stats.object_count);
+    eprintln!("            NAR Bytes: {}", HumanBytes(stats.nar_bytes));
+    eprintln!("          Chunk Bytes: {}", HumanBytes(stats.chunk_bytes));
+    eprintln!(
+        "       Physical Bytes: {}",
+        HumanBytes(stats.physical_bytes)
+    );
+    eprintln!("         Shared Bytes: {}", HumanBytes(stats.shared_bytes));
+    eprintln!("         Unique Bytes: {}", HumanBytes(stats.unique_bytes));
+
+    if stats.chunk_bytes != 0 {
+        eprintln!(
+            "          Dedup Ratio: {:.2}",
+            stats.nar_bytes as f64 / stats.chunk_bytes as f64
+        );
+    }
+
+    if !stats.top_uploaders.is_empty() {
+        eprintln!("        Top Uploaders:");
+
+        for uploader in stats.top_uploaders {
+            eprintln!(
+                "  {}: {} objects, {}",
+                uploader.username.as_deref().unwrap_or("(anonymous)"),
+                uploader.object_count,
+                HumanBytes(uploader.nar_bytes)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn list_objects(sub: Ls) -> Result<()> {
+    let config = Config::load()?;
+
+    let (_, server, cache) = config.resolve_cache(&sub.cache)?;
+    let api = ApiClient::from_server_config(server.clone())?;
+
+    let mut request = ListObjectsRequest {
+        name: sub.name,
+        system: sub.system,
+        created_by: sub.created_by,
+        created_after: sub.created_after,
+        created_before: sub.created_before,
+        accessed_after: sub.accessed_after,
+        accessed_before: sub.accessed_before,
+        after: None,
+        limit: None,
+    };
+
+    let mut objects = Vec::new();
+    loop {
+        if let Some(limit) = sub.limit {
+            request.limit = Some(limit - objects.len() as u64);
+        }
+
+        let page = api.list_objects(cache, &request).await?;
+        objects.extend(page.objects);
+
+        match page.next_cursor {
+            Some(cursor) if sub.limit.is_none_or(|limit| (objects.len() as u64) < limit) => {
+                request.after = Some(cursor);
+            }
+            _ => break,
+        }
+    }
+
+    if sub.json {
+        println!("{}", serde_json::to_string_pretty(&objects)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<20} {:>12} {:<20} {:<16} STORE PATH",
+        "CREATED", "NAR SIZE", "SYSTEM", "CREATED BY"
+    );
+    for object in objects {
+        println!(
+            "{:<20} {:>12} {:<20} {:<16} {}",
+            object.created_at,
+            HumanBytes(object.nar_size).to_string(),
+            object.system.as_deref().unwrap_or("-"),
+            object.created_by.as_deref().unwrap_or("-"),
+            object.store_path,
+        );
+    }
+
+    Ok(())
+}
+
+async fn copy_paths(sub: CopyPaths) -> Result<()> {
+    let config = Config::load()?;
+
+    let (source_server_name, server, source) = config.resolve_cache(&sub.source)?;
+    let (destination_server_name, _, destination) = config.resolve_cache(&sub.destination)?;
+
+    if source_server_name != destination_server_name {
+        return Err(anyhow!(
+            "Paths can only be copied between caches on the same server"
+        ));
+    }
+
+    if sub.paths.is_empty() {
+        eprintln!("🤷 Nothing specified.");
+        return Ok(());
+    }
+
+    let api = ApiClient::from_server_config(server.clone())?;
+
+    let store = NixStore::connect()?;
+    let store_path_hashes = sub
+        .paths
+        .iter()
+        .map(|path| Ok(store.parse_store_path(path)?.to_hash()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let response = api
+        .copy_paths(source, destination, store_path_hashes, sub.closure)
+        .await?;
+
+    for path in &response.copied_paths {
+        eprintln!("Copied {}", path);
+    }
+
+    for hash in &response.missing_paths {
+        eprintln!("⚠️ {} is not in {}", hash.as_str(), source.as_str());
+    }
+
+    eprintln!(
+        "✅ Copied {} paths to {}",
+        response.copied_paths.len(),
+        destination.as_str()
+    );
+
+    Ok(())
+}
+
+async fn show_closure(sub: Closure) -> Result<()> {
+    let config = Config::load()?;
+
+    let (_, server, cache) = config.resolve_cache(&sub.cache)?;
+    let api = ApiClient::from_server_config(server.clone())?;
+
+    let store = NixStore::connect()?;
+    let store_path_hashes = sub
+        .paths
+        .iter()
+        .map(|path| Ok(store.parse_store_path(path)?.to_hash()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let closure = api.get_closure(cache, store_path_hashes).await?;
+
+    for path in &closure.paths {
+        println!("{}", path);
+    }
+
+    if !closure.missing_paths.is_empty() {
+        for hash in &closure.missing_paths {
+            eprintln!("❌ {} is missing", hash.as_str());
+        }
+
+        return Err(anyhow!(
+            "{} paths in the closure are not in {}",
+            closure.missing_paths.len(),
+            cache.as_str()
+        ));
+    }
+
     Ok(())
 }
+
+/// Parses a timestamp or a duration before now into RFC 3339 format.
+fn parse_time(time: &str) -> Result<String> {
+    let time = match humantime::parse_rfc3339_weak(time) {
+        Ok(time) => time,
+        Err(_) => {
+            let ago = humantime::parse_duration(time)
+                .map_err(|_| anyhow!("Invalid time \"{}\"", time))?;
+            SystemTime::now()
+                .checked_sub(ago)
+                .ok_or_else(|| anyhow!("Time \"{}\" is too far in the past", time))?
+        }
+    };
+
+    Ok(humantime::format_rfc3339_seconds(time).to_string())
+}
+
+/// Parses a size like "200G" into bytes.
+fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid size \"{}\"", size))?;
+
+    let unit = unit.trim().to_ascii_uppercase();
+    let exponent = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        _ => return Err(anyhow!("Invalid size unit \"{}\"", unit)),
+    };
+
+    Ok((number * 1024f64.powi(exponent)) as u64)
+}
@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::api::ApiClient;
+use crate::cache::CacheRef;
+use crate::cli::Opts;
+use crate::config::Config;
+use bunker::nix_store::NixStore;
+
+/// Delete store paths from a binary cache.
+///
+/// You need the `delete` permission on the cache. The storage
+/// is reclaimed by garbage collection on the server.
+#[derive(Debug, Parser)]
+pub struct Delete {
+    /// The cache to delete the paths from.
+    ///
+    /// This can be either `servername:cachename` or `cachename`
+    /// when using the default server.
+    cache: CacheRef,
+
+    /// The store paths to delete.
+    ///
+    /// The paths don't need to exist locally.
+    paths: Vec<PathBuf>,
+
+    /// Delete the closures of the paths as well.
+    ///
+    /// The closures are computed from the references of
+    /// the paths in the cache. Members that other paths in
+    /// the cache still reference are kept.
+    #[clap(long)]
+    closure: bool,
+}
+
+pub async fn run(opts: Opts) -> Result<()> {
+    let sub = opts.command.as_delete().unwrap();
+    let config = Config::load()?;
+
+    let (_, server, cache) = config.resolve_cache(&sub.cache)?;
+    let api = ApiClient::from_server_config(server.clone())?;
+
+    if sub.paths.is_empty() {
+        eprintln!("🤷 Nothing specified.");
+        return Ok(());
+    }
+
+    let store = NixStore::connect()?;
+    let store_path_hashes = sub
+        .paths
+        .iter()
+        .map(|path| Ok(store.parse_store_path(path)?.to_hash()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let response = api
+        .delete_paths(cache, store_path_hashes, sub.closure)
+        .await?;
+
+    for path in &response.deleted_paths {
+        eprintln!("Deleted {}", path);
+    }
+
+    for path in &response.pinned_paths {
+        eprintln!("📌 Kept {} (pinned)", path);
+    }
+
+    for path in &response.retained_paths {
+        eprintln!("Kept {} (still referenced)", path);
+    }
+
+    if response.deleted_paths.is_empty() {
+        if response.pinned_paths.is_empty() && response.retained_paths.is_empty() {
+            eprintln!("🤷 None of the paths are in the cache.");
+        } else {
+            eprintln!("🤷 None of the paths could be deleted.");
+        }
+    } else {
+        eprintln!("✅ Deleted {} paths", response.deleted_paths.len());
+    }
+
+    Ok(())
+}
@@ -1,6 +1,11 @@
 pub mod cache;
+pub mod delete;
 pub mod get_closure;
 pub mod login;
+pub mod pin;
 pub mod push;
+pub mod token;
+pub mod unpin;
 pub mod r#use;
 pub mod watch_store;
+pub mod whoami;
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+use humantime::Duration;
+
+use crate::api::ApiClient;
+use crate::cache::CacheRef;
+use crate::cli::Opts;
+use crate::config::Config;
+use bunker::api::v1::pin::CreatePinRequest;
+use bunker::nix_store::NixStore;
+
+/// Pin store paths in a binary cache.
+///
+/// Pinned paths and their closures are never deleted by
+/// garbage collection.
+#[derive(Debug, Parser)]
+pub struct Pin {
+    /// The cache to pin the paths in.
+    ///
+    /// This can be either `servername:cachename` or `cachename`
+    /// when using the default server.
+    cache: CacheRef,
+
+    /// The store paths to pin.
+    paths: Vec<PathBuf>,
+
+    /// A label for the pins, such as a release name.
+    #[clap(long)]
+    label: Option<String>,
+
+    /// Make the pins expire after some time.
+    ///
+    /// You can use expressions like "2 years", "3 months"
+    /// and "1y".
+    #[clap(long, value_name = "PERIOD")]
+    expires_in: Option<Duration>,
+
+    /// List the pins in the cache instead.
+    #[clap(long, conflicts_with_all = ["paths", "label", "expires_in"])]
+    list: bool,
+}
+
+pub async fn run(opts: Opts) -> Result<()> {
+    let sub = opts.command.as_pin().unwrap();
+    let config = Config::load()?;
+
+    let (_, server, cache) = config.resolve_cache(&sub.cache)?;
+    let api = ApiClient::from_server_config(server.clone())?;
+
+    if sub.list {
+        let pins = api.list_pins(cache).await?.pins;
+
+        if pins.is_empty() {
+            eprintln!("🤷 No pins in \"{}\".", cache.as_str());
+        }
+
+        for pin in pins {
+            let path = pin
+                .store_path
+                .unwrap_or_else(|| format!("{} (not in cache)", pin.store_path_hash.as_str()));
+            let label = pin
+                .label
+                .map(|label| format!(" [{}]", label))
+                .unwrap_or_default();
+            let expiry = pin
+                .expires_at
+                .map(|expires_at| format!(", expires {}", expires_at))
+                .unwrap_or_default();
+
+            println!("{}{} (pinned {}{})", path, label, pin.created_at, expiry);
+        }
+
+        return Ok(());
+    }
+
+    if sub.paths.is_empty() {
+        eprintln!("🤷 Nothing specified.");
+        return Ok(());
+    }
+
+    let store = NixStore::connect()?;
+    for path in &sub.paths {
+        let store_path = store.follow_store_path(path)?;
+
+        api.create_pin(
+            cache,
+            &CreatePinRequest {
+                store_path_hash: store_path.to_hash(),
+                label: sub.label.clone(),
+                expires_in: sub.expires_in.map(|period| period.as_secs()),
+            },
+        )
+        .await?;
+
+        eprintln!(
+            "📌 Pinned {}",
+            store.get_full_path(&store_path).to_str().unwrap()
+        );
+    }
+
+    Ok(())
+}
@@ -1,18 +1,19 @@
 use std::io::IsTerminal;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
 use clap::Parser;
 use indicatif::MultiProgress;
 use tokio::io::{self, AsyncBufReadExt, BufReader};
+use tokio::process::Command;
 
 use crate::api::ApiClient;
 use crate::cache::{CacheName, CacheRef, ServerName};
 use crate::cli::Opts;
 use crate::config::Config;
 use crate::push::{PushConfig, PushSessionConfig, Pusher};
-use bunker::nix_store::NixStore;
+use bunker::nix_store::{NixStore, StorePath};
 
 /// Push closures to a binary cache.
 #[derive(Debug, Parser)]
@@ -42,6 +43,20 @@ pub struct Push {
     #[clap(short = 'j', long, default_value = "5")]
     jobs: usize,
 
+    /// Also push the build logs of the pushed paths.
+    ///
+    /// Logs are read from the local Nix store. Paths without
+    /// a log (e.g., substituted paths) are skipped.
+    #[clap(long)]
+    logs: bool,
+
+    /// Require a token to pull the pushed paths.
+    ///
+    /// The paths can't be pulled anonymously even if the cache
+    /// is public.
+    #[clap(long)]
+    restricted: bool,
+
     /// Always send the upload info as part of the payload.
     #[clap(long, hide = true)]
     force_preamble: bool,
@@ -49,11 +64,13 @@ pub struct Push {
 
 struct PushContext {
     store: Arc<NixStore>,
+    api: ApiClient,
     cache_name: CacheName,
     server_name: ServerName,
     pusher: Pusher,
     no_closure: bool,
     ignore_upstream_cache_filter: bool,
+    logs: bool,
 }
 
 impl PushContext {
@@ -100,13 +117,37 @@ impl PushContext {
             );
         }
 
+        let mut pushed_paths = Vec::new();
         for (_, path_info) in plan.store_path_map {
+            pushed_paths.push(path_info.path.clone());
             self.pusher.queue(path_info).await?;
         }
 
         let results = self.pusher.wait().await;
         results.into_values().collect::<Result<Vec<()>>>()?;
 
+        if self.logs {
+            self.push_logs(pushed_paths).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn push_logs(&self, paths: Vec<StorePath>) -> Result<()> {
+        let mut num_logs = 0;
+
+        for path in paths {
+            let full_path = self.store.get_full_path(&path);
+            if let Some((drv_name, log)) = read_build_log(&full_path).await? {
+                self.api
+                    .upload_build_log(&self.cache_name, &drv_name, log)
+                    .await?;
+                num_logs += 1;
+            }
+        }
+
+        eprintln!("📜 Pushed {num_logs} build logs");
+
         Ok(())
     }
 
@@ -159,13 +200,14 @@ pub async fn run(opts: Opts) -> Result<()> {
     let push_config = PushConfig {
         num_workers: sub.jobs,
         force_preamble: sub.force_preamble,
+        restricted: sub.restricted,
     };
 
     let mp = MultiProgress::new();
 
     let pusher = Pusher::new(
         store.clone(),
-        api,
+        api.clone(),
         cache_name.to_owned(),
         cache_config,
         mp,
@@ -174,11 +216,13 @@ pub async fn run(opts: Opts) -> Result<()> {
 
     let push_ctx = PushContext {
         store,
+        api,
         cache_name: cache_name.clone(),
         server_name: server_name.clone(),
         pusher,
         no_closure: sub.no_closure,
         ignore_upstream_cache_filter: sub.ignore_upstream_cache_filter,
+        logs: sub.logs,
     };
 
     if sub.stdin {
@@ -188,6 +232,10 @@ pub async fn run(opts: Opts) -> Result<()> {
             ));
         }
 
+        if sub.logs {
+            return Err(anyhow!("--logs cannot be used with --stdin"));
+        }
+
         push_ctx.push_stdin().await?;
     } else {
         push_ctx.push_static(sub.paths.clone()).await?;
@@ -195,3 +243,41 @@ pub async fn run(opts: Opts) -> Result<()> {
 
     Ok(())
 }
+
+/// Reads the build log of a store path from the local Nix store.
+///
+/// Returns the base name of the deriver and the log, or `None` if
+/// the deriver or the log is unavailable.
+async fn read_build_log(path: &Path) -> Result<Option<(String, Vec<u8>)>> {
+    let output = Command::new("nix-store")
+        .arg("--query")
+        .arg("--deriver")
+        .arg(path)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    let deriver = String::from_utf8(output.stdout)?;
+    let deriver = Path::new(deriver.trim());
+
+    let drv_name = match deriver.file_name().and_then(|n| n.to_str()) {
+        Some(name) if name.ends_with(".drv") => name.to_string(),
+        _ => return Ok(None), // unknown-deriver
+    };
+
+    let output = Command::new("nix-store")
+        .arg("--read-log")
+        .arg(deriver)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        tracing::debug!("No build log for {}", drv_name);
+        return Ok(None);
+    }
+
+    Ok(Some((drv_name, output.stdout)))
+}
@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use humantime::Duration;
+
+use crate::api::ApiClient;
+use crate::cache::ServerName;
+use crate::cli::Opts;
+use crate::config::Config;
+use bunker::api::v1::token_info::CacheGrant;
+use bunker::api::v1::tokens::CreateTokenRequest;
+use bunker::cache::CacheNamePattern;
+
+/// Manage tokens on an Bunker server.
+#[derive(Debug, Parser)]
+pub struct Token {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    Create(Create),
+}
+
+/// Create a token on behalf of the current token.
+///
+/// The new token can only grant permissions that the current
+/// token has, and cannot outlive it. This is useful for handing
+/// out short-lived tokens to CI jobs.
+///
+/// $ bunker token create --validity "1 day" --pull "dev-*" --push "dev-ci"
+#[derive(Debug, Clone, Parser)]
+struct Create {
+    /// The server to create the token on.
+    ///
+    /// If unspecified, the default server is used.
+    server: Option<ServerName>,
+
+    /// How long the token is valid for.
+    ///
+    /// You can use expressions like "2 weeks" and "30d".
+    #[clap(long, value_name = "PERIOD")]
+    validity: Duration,
+
+    #[clap(long = "pull", value_name = "PATTERN")]
+    pull_patterns: Vec<CacheNamePattern>,
+
+    #[clap(long = "push", value_name = "PATTERN")]
+    push_patterns: Vec<CacheNamePattern>,
+
+    #[clap(long = "delete", value_name = "PATTERN")]
+    delete_patterns: Vec<CacheNamePattern>,
+
+    #[clap(long = "create-cache", value_name = "PATTERN")]
+    create_cache_patterns: Vec<CacheNamePattern>,
+
+    #[clap(long = "configure-cache", value_name = "PATTERN")]
+    configure_cache_patterns: Vec<CacheNamePattern>,
+
+    #[clap(long = "configure-cache-retention", value_name = "PATTERN")]
+    configure_cache_retention_patterns: Vec<CacheNamePattern>,
+
+    #[clap(long = "destroy-cache", value_name = "PATTERN")]
+    destroy_cache_patterns: Vec<CacheNamePattern>,
+
+    #[clap(long = "pin", value_name = "PATTERN")]
+    pin_patterns: Vec<CacheNamePattern>,
+}
+
+pub async fn run(opts: Opts) -> Result<()> {
+    let sub = opts.command.as_token().unwrap();
+    match &sub.command {
+        Command::Create(sub) => create_token(sub.to_owned()).await,
+    }
+}
+
+async fn create_token(sub: Create) -> Result<()> {
+    let config = Config::load()?;
+
+    let (server_name, server) = match &sub.server {
+        Some(name) => {
+            let server = config
+                .servers
+                .get(name)
+                .ok_or_else(|| anyhow!("Server \"{}\" does not exist", name.as_str()))?;
+            (name, server)
+        }
+        None => config.default_server()?,
+    };
+
+    let mut caches: Vec<CacheGrant> = Vec::new();
+    for (patterns, permission) in [
+        (&sub.pull_patterns, "pull"),
+        (&sub.push_patterns, "push"),
+        (&sub.delete_patterns, "delete"),
+        (&sub.create_cache_patterns, "create-cache"),
+        (&sub.configure_cache_patterns, "configure-cache"),
+        (
+            &sub.configure_cache_retention_patterns,
+            "configure-cache-retention",
+        ),
+        (&sub.destroy_cache_patterns, "destroy-cache"),
+        (&sub.pin_patterns, "pin"),
+    ] {
+        for pattern in patterns {
+            let index = match caches
+                .iter()
+                .position(|grant| grant.pattern == pattern.as_str())
+            {
+                Some(index) => index,
+                None => {
+                    caches.push(CacheGrant {
+                        pattern: pattern.as_str().to_owned(),
+                        permissions: Vec::new(),
+                    });
+                    caches.len() - 1
+                }
+            };
+            caches[index].permissions.push(permission.to_owned());
+        }
+    }
+
+    let api = ApiClient::from_server_config(server.clone())?;
+    let request = CreateTokenRequest {
+        validity: sub.validity.as_secs(),
+        caches,
+    };
+    let response = api.create_token(&request).await?;
+
+    eprintln!(
+        "✨ Created token {} on \"{}\", expiring at {}",
+        response.token_id.as_deref().unwrap_or("(no id)"),
+        server_name.as_str(),
+        response.expires_at
+    );
+    println!("{}", response.token);
+
+    Ok(())
+}
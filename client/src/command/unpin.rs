@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::api::ApiClient;
+use crate::cache::CacheRef;
+use crate::cli::Opts;
+use crate::config::Config;
+use bunker::nix_store::NixStore;
+
+/// Unpin store paths in a binary cache.
+///
+/// The paths become subject to garbage collection again.
+#[derive(Debug, Parser)]
+pub struct Unpin {
+    /// The cache to unpin the paths in.
+    ///
+    /// This can be either `servername:cachename` or `cachename`
+    /// when using the default server.
+    cache: CacheRef,
+
+    /// The store paths to unpin.
+    paths: Vec<PathBuf>,
+}
+
+pub async fn run(opts: Opts) -> Result<()> {
+    let sub = opts.command.as_unpin().unwrap();
+    let config = Config::load()?;
+
+    let (_, server, cache) = config.resolve_cache(&sub.cache)?;
+    let api = ApiClient::from_server_config(server.clone())?;
+
+    if sub.paths.is_empty() {
+        eprintln!("🤷 Nothing specified.");
+        return Ok(());
+    }
+
+    let store = NixStore::connect()?;
+    for path in &sub.paths {
+        let store_path = store.parse_store_path(path)?;
+        api.delete_pin(cache, &store_path.to_hash()).await?;
+
+        eprintln!(
+            "Unpinned {}",
+            store.get_full_path(&store_path).to_str().unwrap()
+        );
+    }
+
+    Ok(())
+}
@@ -34,6 +34,13 @@ pub async fn run(opts: Opts) -> Result<()> {
     let public_key = cache_config.public_key
         .ok_or_else(|| anyhow!("The server did not tell us which public key it uses. Is signing managed by the client?"))?;
 
+    // During key rotation, the retiring keys are still trusted
+    let public_keys = if let Some(public_keys) = cache_config.public_keys {
+        public_keys.into_iter().map(|k| k.public_key).collect()
+    } else {
+        vec![public_key]
+    };
+
     eprintln!(
         "Configuring Nix to use \"{cache}\" on \"{server_name}\":",
         cache = cache.as_str(),
@@ -42,11 +49,15 @@ pub async fn run(opts: Opts) -> Result<()> {
 
     // Modify nix.conf
     eprintln!("+ Substituter: {}", substituter);
-    eprintln!("+ Trusted Public Key: {}", public_key);
+    for public_key in &public_keys {
+        eprintln!("+ Trusted Public Key: {}", public_key);
+    }
 
     let mut nix_config = NixConfig::load().await?;
     nix_config.add_substituter(&substituter);
-    nix_config.add_trusted_public_key(&public_key);
+    for public_key in public_keys.iter().rev() {
+        nix_config.add_trusted_public_key(public_key);
+    }
 
     // Modify netrc
     if let Some(token) = server.token()? {
@@ -35,6 +35,13 @@ pub struct WatchStore {
     #[clap(short = 'j', long, default_value = "5")]
     jobs: usize,
 
+    /// Require a token to pull the pushed paths.
+    ///
+    /// The paths can't be pulled anonymously even if the cache
+    /// is public.
+    #[clap(long)]
+    restricted: bool,
+
     /// Always send the upload info as part of the payload.
     #[clap(long, hide = true)]
     force_preamble: bool,
@@ -65,6 +72,7 @@ pub async fn run(opts: Opts) -> Result<()> {
     let push_config = PushConfig {
         num_workers: sub.jobs,
         force_preamble: sub.force_preamble,
+        restricted: sub.restricted,
     };
 
     let push_session_config = PushSessionConfig {
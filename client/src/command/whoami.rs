@@ -0,0 +1,67 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+
+use crate::api::ApiClient;
+use crate::cache::ServerName;
+use crate::cli::Opts;
+use crate::config::Config;
+
+/// Show what the token for a server grants.
+///
+/// Revoked and invalid tokens are reported as anonymous access.
+#[derive(Debug, Parser)]
+pub struct Whoami {
+    /// The server to query.
+    ///
+    /// If unspecified, the default server is used.
+    server: Option<ServerName>,
+}
+
+pub async fn run(opts: Opts) -> Result<()> {
+    let sub = opts.command.as_whoami().unwrap();
+    let config = Config::load()?;
+
+    let (server_name, server) = match &sub.server {
+        Some(name) => {
+            let server = config
+                .servers
+                .get(name)
+                .ok_or_else(|| anyhow!("Server \"{}\" does not exist", name.as_str()))?;
+            (name, server)
+        }
+        None => config.default_server()?,
+    };
+
+    let api = ApiClient::from_server_config(server.clone())?;
+    let info = api.get_token_info().await?;
+
+    if info.token_id.is_none() && info.subject.is_none() && info.caches.is_empty() {
+        eprintln!(
+            "👤 Accessing \"{}\" anonymously, only public caches are available",
+            server_name.as_str()
+        );
+        return Ok(());
+    }
+
+    eprintln!(
+        "🔑 Authenticated to \"{}\" as \"{}\"",
+        server_name.as_str(),
+        info.subject.as_deref().unwrap_or("(no subject)")
+    );
+    eprintln!(
+        "         Token ID: {}",
+        info.token_id
+            .as_deref()
+            .unwrap_or("(none, cannot be revoked)")
+    );
+    eprintln!(
+        "       Expires at: {}",
+        info.expires_at.as_deref().unwrap_or("(never)")
+    );
+
+    for grant in &info.caches {
+        println!("{}: {}", grant.pattern, grant.permissions.join(", "));
+    }
+
+    Ok(())
+}
@@ -50,6 +50,9 @@ pub struct PushConfig {
 
     /// Whether to always include the upload info in the PUT payload.
     pub force_preamble: bool,
+
+    /// Whether to require a token to pull the paths.
+    pub restricted: bool,
 }
 
 /// Configuration for a push session.
@@ -253,6 +256,7 @@ impl Pusher {
                 &cache,
                 mp.clone(),
                 config.force_preamble,
+                config.restricted,
             )
             .await;
 
@@ -503,6 +507,7 @@ pub async fn upload_path(
     cache: &CacheName,
     mp: MultiProgress,
     force_preamble: bool,
+    restricted: bool,
 ) -> Result<()> {
     let path = &path_info.path;
     let upload_info = {
@@ -533,6 +538,7 @@ pub async fn upload_path(
             ca: path_info.ca,
             nar_hash: path_info.nar_hash.to_owned(),
             nar_size: path_info.nar_size as usize,
+            restricted,
         }
     };
 
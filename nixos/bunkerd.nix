@@ -18,6 +18,7 @@ let
       ''
         cat $configFile
         export BUNKER_SERVER_TOKEN_HS256_SECRET_BASE64="dGVzdCBzZWNyZXQ="
+        export BUNKER_SERVER_AUDIT_HMAC_KEY_BASE64="AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="
         export BUNKER_SERVER_DATABASE_URL="sqlite://:memory:"
         ${cfg.package}/bin/bunkerd --mode check-config -f $configFile
         cat <$configFile >$out
@@ -128,6 +129,8 @@ in
 
           'garbage-collector' only runs the garbage collector periodically.
 
+          'verifier' only runs storage verification periodically.
+
           A simple NixOS-based Bunker deployment will typically have one 'monolithic' and any number of 'api-server' nodes.
 
           There are several other supported modes that perform one-off operations, but these are the only ones that make sense to run via the NixOS module.
@@ -136,6 +139,7 @@ in
           "monolithic"
           "api-server"
           "garbage-collector"
+          "verifier"
         ];
         default = "monolithic";
       };
@@ -160,6 +164,7 @@ in
           Run `openssl genrsa -traditional -out private_key.pem 4096 | base64 -w0` and create a file with the following contents:
 
           BUNKER_SERVER_TOKEN_RS256_SECRET="output from command"
+          BUNKER_SERVER_AUDIT_HMAC_KEY_BASE64="output from `openssl rand -base64 32`"
 
           Then, set `services.bunkerd.environmentFile` to the quoted absolute path of the file.
         '';
@@ -1,13 +1,16 @@
 //! HTTP middlewares for access control.
 
 use axum::{extract::Request, middleware::Next, response::Response};
+
 use bunker::cache::CacheName;
+
 use bunker_token::util::parse_authorization_header;
 use sea_orm::DatabaseConnection;
 use tokio::sync::OnceCell;
 
 use crate::access::{CachePermission, Token};
-use crate::database::{BunkerDatabase, entity::cache::CacheModel};
+use crate::database::entity::object::ObjectModel;
+use crate::database::{entity::cache::CacheModel, BunkerDatabase};
 use crate::error::ServerResult;
 use crate::{RequestState, State};
 
@@ -88,38 +91,98 @@ impl AuthState {
 
         permission
     }
+
+    /// Returns permission granted for an object in a cache.
+    ///
+    /// Public permissions are not granted for restricted objects,
+    /// which can only be pulled with an explicit permission.
+    pub fn get_permission_for_object(
+        &self,
+        cache_name: &CacheName,
+        cache: &CacheModel,
+        object: &ObjectModel,
+    ) -> CachePermission {
+        let grant_public_permissions = cache.is_public && !cache.is_object_restricted(object);
+        self.get_permission_for_cache(cache_name, grant_public_permissions)
+    }
 }
 
 /// Performs auth.
 pub async fn apply_auth(req: Request, next: Next) -> Response {
-    let token: Option<Token> = req
+    let jwt = req
         .headers()
         .get("Authorization")
         .and_then(|bytes| bytes.to_str().ok())
-        .and_then(parse_authorization_header)
-        .and_then(|jwt| {
-            let state = req.extensions().get::<State>().unwrap();
-            let signature_type = state.config.jwt.signing_config.clone().into();
-
-            let res_token = Token::from_jwt(
-                &jwt,
-                &signature_type,
-                &state.config.jwt.token_bound_issuer,
-                &state.config.jwt.token_bound_audiences,
-            );
-
-            if let Err(e) = &res_token {
-                tracing::debug!("Ignoring bad JWT token: {}", e);
-            }
+        .and_then(parse_authorization_header);
 
-            res_token.ok()
-        });
+    let token = if let Some(jwt) = jwt {
+        let state = req.extensions().get::<State>().unwrap();
+        verify_token(state, &jwt).await
+    } else {
+        None
+    };
 
     if let Some(token) = token {
-        let req_state = req.extensions().get::<RequestState>().unwrap();
-        req_state.auth.token.set(token).unwrap();
-        tracing::trace!("Added valid token");
+        let state = req.extensions().get::<State>().unwrap();
+
+        if is_token_revoked(state, &token).await {
+            tracing::debug!("Ignoring revoked JWT token");
+        } else {
+            let req_state = req.extensions().get::<RequestState>().unwrap();
+            req_state.auth.token.set(token).unwrap();
+            tracing::trace!("Added valid token");
+        }
     }
 
     next.run(req).await
 }
+
+/// Verifies a JWT.
+///
+/// Tokens signed by the server itself are tried first, followed by
+/// tokens from the configured OIDC issuers.
+async fn verify_token(state: &State, jwt: &str) -> Option<Token> {
+    let signature_type = state.config.jwt.signing_config.clone().into();
+
+    match Token::from_jwt(
+        jwt,
+        &signature_type,
+        &state.config.jwt.token_bound_issuer,
+        &state.config.jwt.token_bound_audiences,
+    ) {
+        Ok(token) => return Some(token),
+        Err(e) => {
+            if state.oidc_issuers.is_empty() {
+                tracing::debug!("Ignoring bad JWT token: {}", e);
+                return None;
+            }
+        }
+    }
+
+    for issuer in &state.oidc_issuers {
+        match issuer.verify(jwt, &state.http_client).await {
+            Ok(token) => return Some(token),
+            Err(e) => tracing::trace!("Token not accepted by OIDC issuer: {}", e),
+        }
+    }
+
+    tracing::debug!("Ignoring bad JWT token");
+    None
+}
+
+/// Returns whether a token has been revoked.
+///
+/// Tokens without a `jti` claim cannot be revoked. If the revocation
+/// list cannot be checked, the token is treated as revoked.
+async fn is_token_revoked(state: &State, token: &Token) -> bool {
+    let Some(jti) = token.jti() else {
+        return false;
+    };
+
+    let revoked = async { state.database().await?.is_token_revoked(jti).await }.await;
+
+    revoked.unwrap_or_else(|e| {
+        tracing::warn!("Failed to check token revocation: {}", e);
+        true
+    })
+}
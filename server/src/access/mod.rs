@@ -1,2 +1,4 @@
 pub mod http;
+pub mod oidc;
+pub mod tokens;
 pub use bunker_token::*;
@@ -0,0 +1,200 @@
+//! Tokens from external OIDC issuers.
+//!
+//! CI systems like GitHub Actions and GitLab CI can issue OIDC tokens
+//! to jobs. We accept them if they are signed by a key in the JSON Web
+//! Key Set of a configured issuer. Such tokens don't carry any Bunker
+//! permissions themselves. Instead, their claims (e.g., `repository`
+//! and `ref`) are mapped to cache permissions by the configured rules.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use tokio::fs;
+use tokio::sync::{Mutex, RwLock};
+use wildmatch::WildMatch;
+
+use super::jwks::Jwks;
+use super::{SignatureType, Token};
+use crate::config::{ClaimMappingRule, JwksSource, OidcConfig};
+
+/// Timeout for fetching a JSON Web Key Set.
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An external OIDC issuer.
+#[derive(Debug)]
+pub struct OidcIssuer {
+    config: OidcConfig,
+
+    /// The cached key set.
+    jwks: RwLock<Option<CachedJwks>>,
+
+    /// Held by the request reloading the key set.
+    reloading: Mutex<()>,
+}
+
+#[derive(Debug)]
+struct CachedJwks {
+    jwks: Arc<Jwks>,
+    loaded_at: Instant,
+}
+
+macro_rules! grant_permissions {
+    ($token:ident, $list:expr, $perm:ident) => {
+        for pattern in $list {
+            let perm = $token.get_or_insert_permission_mut(pattern.to_owned());
+            perm.$perm = true;
+        }
+    };
+}
+
+impl OidcIssuer {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            jwks: RwLock::new(None),
+            reloading: Mutex::new(()),
+        }
+    }
+
+    /// Verifies and decodes a token from this issuer.
+    ///
+    /// The permissions of the returned token are derived from
+    /// its claims.
+    pub async fn verify(&self, jwt: &str, http_client: &reqwest::Client) -> Result<Token> {
+        let jwks = self.jwks(http_client).await?;
+
+        let mut token = Token::from_jwt(
+            jwt,
+            &SignatureType::Jwks(jwks),
+            &Some(self.config.issuer.clone()),
+            &Some(self.config.audiences.clone()),
+        )?;
+
+        token.clear_permissions();
+        for rule in &self.config.rules {
+            if rule_matches(rule, &token) {
+                grant_permissions!(token, &rule.pull, pull);
+                grant_permissions!(token, &rule.push, push);
+                grant_permissions!(token, &rule.delete, delete);
+                grant_permissions!(token, &rule.create_cache, create_cache);
+                grant_permissions!(token, &rule.configure_cache, configure_cache);
+                grant_permissions!(
+                    token,
+                    &rule.configure_cache_retention,
+                    configure_cache_retention
+                );
+                grant_permissions!(token, &rule.destroy_cache, destroy_cache);
+                grant_permissions!(token, &rule.pin, pin);
+            }
+        }
+
+        Ok(token)
+    }
+
+    /// Returns the key set, reloading it if it's stale.
+    ///
+    /// Only one request reloads the key set at a time. Others continue
+    /// to use the stale key set in the meantime, and if reloading fails,
+    /// it continues to be used until the next refresh.
+    async fn jwks(&self, http_client: &reqwest::Client) -> Result<Arc<Jwks>> {
+        if let Some(jwks) = self.fresh_jwks().await {
+            return Ok(jwks);
+        }
+
+        let _reloading = match self.reloading.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => {
+                if let Some(cached) = &*self.jwks.read().await {
+                    return Ok(cached.jwks.clone());
+                }
+
+                // Nothing to fall back to
+                self.reloading.lock().await
+            }
+        };
+
+        // Another request may have reloaded it while we were waiting
+        if let Some(jwks) = self.fresh_jwks().await {
+            return Ok(jwks);
+        }
+
+        // The cached key set isn't locked while fetching
+        let result = self.load_jwks(http_client).await;
+        let mut cached = self.jwks.write().await;
+
+        match result {
+            Ok(jwks) => {
+                tracing::debug!(
+                    "Loaded {} keys for OIDC issuer {}",
+                    jwks.len(),
+                    self.config.issuer
+                );
+
+                let jwks = Arc::new(jwks);
+                *cached = Some(CachedJwks {
+                    jwks: jwks.clone(),
+                    loaded_at: Instant::now(),
+                });
+
+                Ok(jwks)
+            }
+            Err(e) => {
+                let Some(cached) = &mut *cached else {
+                    return Err(e);
+                };
+
+                tracing::warn!(
+                    "Failed to reload keys for OIDC issuer {}: {}",
+                    self.config.issuer,
+                    e
+                );
+                cached.loaded_at = Instant::now();
+
+                Ok(cached.jwks.clone())
+            }
+        }
+    }
+
+    /// Returns the cached key set if it's still fresh.
+    async fn fresh_jwks(&self) -> Option<Arc<Jwks>> {
+        self.jwks
+            .read()
+            .await
+            .as_ref()
+            .filter(|cached| cached.loaded_at.elapsed() < self.config.refresh_interval)
+            .map(|cached| cached.jwks.clone())
+    }
+
+    async fn load_jwks(&self, http_client: &reqwest::Client) -> Result<Jwks> {
+        let json = match &self.config.jwks {
+            JwksSource::Url(url) => {
+                http_client
+                    .get(url)
+                    .timeout(JWKS_FETCH_TIMEOUT)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?
+            }
+            JwksSource::File(path) => fs::read_to_string(path).await?,
+        };
+
+        let jwks = Jwks::from_json(&json)?;
+        if jwks.is_empty() {
+            return Err(anyhow!("The key set has no usable keys"));
+        }
+
+        Ok(jwks)
+    }
+}
+
+/// Returns whether all claims required by a rule match.
+fn rule_matches(rule: &ClaimMappingRule, token: &Token) -> bool {
+    rule.claims.iter().all(|(name, pattern)| {
+        token
+            .claim(name)
+            .is_some_and(|value| WildMatch::new(pattern).matches(&value))
+    })
+}
@@ -0,0 +1,166 @@
+//! Records of issued tokens.
+//!
+//! Tokens issued with `bunkeradm make-token` or on behalf of other
+//! tokens are recorded so they can be listed and revoked later.
+//! Revoked tokens, along with the tokens issued on their behalf, are
+//! rejected by [`apply_auth`](super::http::apply_auth).
+
+use std::collections::{BTreeSet, HashSet};
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use sea_orm::entity::prelude::*;
+use sea_orm::query::{Condition, QueryOrder, QuerySelect};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue::Set;
+use sea_orm::TransactionTrait;
+
+use super::Token;
+use crate::audit;
+use crate::config::Config;
+use crate::database::entity::audit_log::AuditAction;
+use crate::database::entity::token::{self, Entity as TokenEntity, TokenModel};
+use crate::error::{ErrorKind, ServerError, ServerResult};
+use crate::StateInner;
+
+/// Records a token issued with `bunkeradm make-token`.
+pub async fn record_token(config: Config, token: &Token) -> Result<()> {
+    let state = StateInner::new(config).await;
+    let db = state.database().await?;
+
+    insert_token(db, token, None).await?;
+
+    let jti = token.jti().unwrap_or_default().to_owned();
+    audit::record_admin(&state, AuditAction::CreateToken, &[jti]).await?;
+
+    Ok(())
+}
+
+/// Records an issued token in the database.
+///
+/// Tokens issued on behalf of other tokens record the ID of their
+/// parent, so revoking the parent revokes them as well.
+pub(crate) async fn insert_token(
+    db: &DatabaseConnection,
+    token: &Token,
+    parent_jti: Option<&str>,
+) -> ServerResult<()> {
+    let jti = token
+        .jti()
+        .ok_or_else(|| ErrorKind::RequestError(anyhow!("The token has no ID")))?;
+    let claims =
+        serde_json::to_string(token.opaque_claims()).map_err(ServerError::request_error)?;
+
+    TokenEntity::insert(token::ActiveModel {
+        jti: Set(jti.to_owned()),
+        subject: Set(token.sub().map(str::to_owned)),
+        parent_jti: Set(parent_jti.map(str::to_owned)),
+        claims: Set(Some(claims)),
+        expires_at: Set(token.expires_at()),
+        created_at: Set(Utc::now()),
+        revoked_at: Set(None),
+        ..Default::default()
+    })
+    .exec(db)
+    .await
+    .map_err(ServerError::database_error)?;
+
+    Ok(())
+}
+
+/// Lists recorded tokens, optionally only those of a subject.
+pub async fn list_tokens(config: Config, subject: Option<&str>) -> Result<Vec<TokenModel>> {
+    let state = StateInner::new(config).await;
+    let db = state.database().await?;
+
+    let mut query = TokenEntity::find().order_by_asc(token::Column::Id);
+    if let Some(subject) = subject {
+        query = query.filter(token::Column::Subject.eq(subject));
+    }
+
+    Ok(query.all(db).await?)
+}
+
+/// Revokes tokens by their IDs and/or all tokens of a subject.
+///
+/// IDs of tokens that were never recorded are added to the
+/// revocation list as well. Tokens issued on behalf of revoked
+/// tokens are rejected too. Returns the IDs of the tokens newly
+/// revoked, which don't include such descendants.
+pub async fn revoke_tokens(
+    config: Config,
+    jtis: &[String],
+    subject: Option<&str>,
+) -> Result<Vec<String>> {
+    let state = StateInner::new(config).await;
+    let db = state.database().await?;
+    let now = Utc::now();
+
+    let mut condition = Condition::any();
+    if !jtis.is_empty() {
+        condition = condition.add(token::Column::Jti.is_in(jtis.iter().cloned()));
+    }
+    if let Some(subject) = subject {
+        condition = condition.add(token::Column::Subject.eq(subject));
+    }
+
+    if condition.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let txn = db.begin().await?;
+
+    let known: Vec<(String, Option<ChronoDateTimeUtc>)> = TokenEntity::find()
+        .select_only()
+        .column(token::Column::Jti)
+        .column(token::Column::RevokedAt)
+        .filter(condition)
+        .into_tuple()
+        .all(&txn)
+        .await?;
+
+    let known_jtis: HashSet<&str> = known.iter().map(|(jti, _)| jti.as_str()).collect();
+    let unknown: BTreeSet<String> = jtis
+        .iter()
+        .filter(|jti| !known_jtis.contains(jti.as_str()))
+        .cloned()
+        .collect();
+
+    let mut revoked: Vec<String> = known
+        .iter()
+        .filter(|(_, revoked_at)| revoked_at.is_none())
+        .map(|(jti, _)| jti.to_owned())
+        .collect();
+
+    if !revoked.is_empty() {
+        TokenEntity::update_many()
+            .col_expr(token::Column::RevokedAt, Expr::value(now))
+            .filter(token::Column::Jti.is_in(revoked.iter().cloned()))
+            .filter(token::Column::RevokedAt.is_null())
+            .exec(&txn)
+            .await?;
+    }
+
+    if !unknown.is_empty() {
+        TokenEntity::insert_many(unknown.iter().map(|jti| token::ActiveModel {
+            jti: Set(jti.to_owned()),
+            created_at: Set(now),
+            revoked_at: Set(Some(now)),
+            ..Default::default()
+        }))
+        .on_conflict(
+            OnConflict::column(token::Column::Jti)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&txn)
+        .await?;
+    }
+
+    txn.commit().await?;
+
+    revoked.extend(unknown);
+    audit::record_admin(&state, AuditAction::RevokeToken, &revoked).await?;
+
+    Ok(revoked)
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::Opts;
+use bunker_server::audit::{self, AuditLogFilter};
+use bunker_server::config::Config;
+
+/// Query the audit log.
+///
+/// Unlike the API, this covers entries without a cache as well,
+/// such as tokens issued and revoked with `bunkeradm`. Entries are
+/// printed as JSON lines.
+///
+/// $ bunkeradm audit-log --action revoke-token
+#[derive(Debug, Parser)]
+pub struct AuditLog {
+    /// Only show entries of this cache.
+    #[clap(long)]
+    cache: Option<String>,
+
+    /// Only show entries of tokens with this subject.
+    #[clap(long)]
+    sub: Option<String>,
+
+    /// Only show entries of this action.
+    #[clap(long)]
+    action: Option<String>,
+
+    /// Only show entries after this ID.
+    #[clap(long)]
+    after: Option<i64>,
+
+    /// The maximum number of entries to show.
+    #[clap(long)]
+    limit: Option<u64>,
+}
+
+pub async fn run(config: Config, opts: Opts) -> Result<()> {
+    let sub = opts.command.as_audit_log().unwrap();
+
+    let filter = AuditLogFilter {
+        cache: sub.cache.clone(),
+        subject: sub.sub.clone(),
+        action: sub.action.as_deref().map(audit::parse_action).transpose()?,
+        after: sub.after,
+    };
+
+    let entries = audit::query_audit_log(config, filter, sub.limit).await?;
+
+    for entry in entries {
+        println!("{}", serde_json::to_string(&entry)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use tracing_subscriber::EnvFilter;
+
+use crate::Opts;
+
+use bunker_server::config::Config;
+use bunker_server::gc;
+
+/// Run garbage collection.
+///
+/// $ bunkeradm gc --retry-deleted
+#[derive(Debug, Parser)]
+pub struct Gc {
+    /// Only retry deleting chunks stuck in Deleted state.
+    ///
+    /// Chunks whose deletion from storage failed are retried
+    /// immediately, regardless of their backoff.
+    #[clap(long)]
+    retry_deleted: bool,
+}
+
+pub async fn run(config: Config, opts: Opts) -> Result<()> {
+    let sub = opts.command.as_gc().unwrap();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+
+    if !sub.retry_deleted {
+        return gc::run_garbage_collection_once(config).await;
+    }
+
+    let report = gc::run_retry_deleted_chunks(config).await?;
+
+    eprintln!(" Deleted chunks: {}", report.deleted);
+    eprintln!("  Failed chunks: {}", report.failed);
+
+    if report.failed != 0 {
+        return Err(anyhow!("Some chunks failed to be deleted"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use clap::Parser;
+
+use crate::Opts;
+use bunker_server::access::tokens;
+use bunker_server::config::Config;
+
+/// List tokens issued with `bunkeradm make-token`.
+///
+/// $ bunkeradm list-tokens --sub "alice"
+#[derive(Debug, Parser)]
+pub struct ListTokens {
+    /// Only list tokens of this subject.
+    #[clap(long)]
+    sub: Option<String>,
+
+    /// Include expired and revoked tokens.
+    #[clap(long)]
+    all: bool,
+}
+
+pub async fn run(config: Config, opts: Opts) -> Result<()> {
+    let sub = opts.command.as_list_tokens().unwrap();
+    let now = Utc::now();
+
+    let records = tokens::list_tokens(config, sub.sub.as_deref()).await?;
+
+    println!(
+        "{:<36}  {:<20}  {:<20}  {:<20}  STATUS",
+        "ID", "SUBJECT", "ISSUED", "EXPIRES"
+    );
+
+    for record in records {
+        let status = if record.revoked_at.is_some() {
+            "revoked"
+        } else if record.is_expired(now) {
+            "expired"
+        } else {
+            "active"
+        };
+
+        if !sub.all && status != "active" {
+            continue;
+        }
+
+        let expires_at = record
+            .expires_at
+            .map(|expires_at| expires_at.to_rfc3339_opts(SecondsFormat::Secs, true))
+            .unwrap_or_else(|| "-".to_string());
+
+        println!(
+            "{:<36}  {:<20}  {:<20}  {:<20}  {}",
+            record.jti,
+            record.subject.as_deref().unwrap_or("-"),
+            record.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            expires_at,
+            status,
+        );
+    }
+
+    Ok(())
+}
@@ -1,13 +1,17 @@
-use anyhow::{Result, anyhow};
+use anyhow::{anyhow, Result};
 use chrono::{Duration as ChronoDuration, Utc};
 use clap::Parser;
 use humantime::Duration;
 
 use crate::Opts;
 use bunker::cache::CacheNamePattern;
-use bunker_server::access::Token;
+
+use bunker_server::access::{tokens, Token};
 use bunker_server::config::Config;
 
+/// Issued tokens are recorded so they can be revoked with
+/// `bunkeradm revoke-token`.
+///
 /// $ bunkeradm make-token --sub "alice" --validity "2y" --pull "dev-*" --push "dev-*" --pull "prod"
 #[derive(Debug, Parser)]
 pub struct MakeToken {
@@ -40,6 +44,9 @@ pub struct MakeToken {
 
     #[clap(long = "destroy-cache", value_name = "PATTERN")]
     destroy_cache_patterns: Vec<CacheNamePattern>,
+
+    #[clap(long = "pin", value_name = "PATTERN")]
+    pin_patterns: Vec<CacheNamePattern>,
 }
 
 macro_rules! grant_permissions {
@@ -71,17 +78,20 @@ pub async fn run(config: Config, opts: Opts) -> Result<()> {
         configure_cache_retention
     );
     grant_permissions!(token, &sub.destroy_cache_patterns, destroy_cache);
+    grant_permissions!(token, &sub.pin_patterns, pin);
 
     if sub.dump_claims {
         println!("{}", serde_json::to_string(token.opaque_claims())?);
     } else {
-        let signature_type = config.jwt.signing_config.into();
+        let signature_type = config.jwt.signing_config.clone().into();
 
         let encoded_token = token.encode(
             &signature_type,
             &config.jwt.token_bound_issuer,
             &config.jwt.token_bound_audiences,
         )?;
+        tokens::record_token(config, &token).await?;
+
         println!("{}", encoded_token);
     }
 
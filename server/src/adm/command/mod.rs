@@ -1 +1,7 @@
+pub mod audit_log;
+pub mod gc;
+pub mod list_tokens;
 pub mod make_token;
+pub mod revoke_token;
+pub mod verify;
+pub mod verify_audit_log;
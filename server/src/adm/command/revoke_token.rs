@@ -0,0 +1,38 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+
+use crate::Opts;
+
+use bunker_server::access::tokens;
+use bunker_server::config::Config;
+
+/// Revoke tokens.
+///
+/// Revoked tokens are rejected by the server even if they haven't
+/// expired. Tokens that weren't recorded can be revoked by their ID
+/// as well.
+///
+/// $ bunkeradm revoke-token 0d1c3a6e-3e5f-4b0f-8d0a-6f1f2b5c9e47
+#[derive(Debug, Parser)]
+pub struct RevokeToken {
+    /// The IDs (`jti` claims) of the tokens to revoke.
+    ids: Vec<String>,
+
+    /// Revoke all recorded tokens of this subject.
+    #[clap(long)]
+    sub: Option<String>,
+}
+
+pub async fn run(config: Config, opts: Opts) -> Result<()> {
+    let sub = opts.command.as_revoke_token().unwrap();
+
+    if sub.ids.is_empty() && sub.sub.is_none() {
+        return Err(anyhow!("No tokens to revoke were specified"));
+    }
+
+    let revoked = tokens::revoke_tokens(config, &sub.ids, sub.sub.as_deref()).await?;
+
+    eprintln!("Revoked {} tokens", revoked.len());
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use tracing_subscriber::EnvFilter;
+
+use crate::Opts;
+
+use bunker_server::config::Config;
+use bunker_server::verify::{self, VerifyOptions};
+
+/// Verify the integrity of the stored chunks.
+///
+/// Chunks are downloaded and rehashed. Corrupted chunks are marked
+/// so clients pushing the affected paths upload them again.
+///
+/// $ bunkeradm verify --nars
+#[derive(Debug, Parser)]
+pub struct Verify {
+    /// Reassemble NARs and verify their NAR hashes as well.
+    #[clap(long)]
+    nars: bool,
+
+    /// Only report corruption without marking anything.
+    #[clap(long)]
+    dry_run: bool,
+}
+
+pub async fn run(config: Config, opts: Opts) -> Result<()> {
+    let sub = opts.command.as_verify().unwrap();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+
+    let options = VerifyOptions {
+        verify_nars: sub.nars,
+        dry_run: sub.dry_run,
+    };
+    let report = verify::run_verification_once(config, options).await?;
+
+    eprintln!("    Chunks verified: {}", report.chunks_verified);
+    eprintln!("  Corrupted chunks: {}", report.corrupted_chunks.len());
+    eprintln!(" Unreadable chunks: {}", report.unreadable_chunks.len());
+    if sub.nars {
+        eprintln!("      NARs verified: {}", report.nars_verified);
+        eprintln!("    Corrupted NARs: {}", report.corrupted_nars.len());
+    }
+
+    if !report.corrupted_chunks.is_empty() || !report.corrupted_nars.is_empty() {
+        return Err(anyhow!("Corruption was found in the storage"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use tracing_subscriber::EnvFilter;
+
+use crate::Opts;
+
+use bunker_server::audit;
+use bunker_server::config::Config;
+
+/// Verify the hash chain of the audit log.
+///
+/// Entries that were modified, reordered or removed break the chain.
+///
+/// $ bunkeradm verify-audit-log
+#[derive(Debug, Parser)]
+pub struct VerifyAuditLog {}
+
+pub async fn run(config: Config, _opts: Opts) -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+
+    let report = audit::verify_audit_log(config).await?;
+
+    eprintln!("Entries verified: {}", report.entries_verified);
+
+    if let Some(id) = report.first_invalid_entry {
+        return Err(anyhow!("The chain is broken at entry {}", id));
+    }
+
+    Ok(())
+}
@@ -7,7 +7,13 @@ use clap::{Parser, Subcommand};
 use enum_as_inner::EnumAsInner;
 
 use bunker_server::config;
+use command::audit_log::{self, AuditLog};
+use command::gc::{self, Gc};
+use command::list_tokens::{self, ListTokens};
 use command::make_token::{self, MakeToken};
+use command::revoke_token::{self, RevokeToken};
+use command::verify::{self, Verify};
+use command::verify_audit_log::{self, VerifyAuditLog};
 
 /// Bunker server administration utilities.
 #[derive(Debug, Parser)]
@@ -25,7 +31,13 @@ pub struct Opts {
 
 #[derive(Debug, Subcommand, EnumAsInner)]
 pub enum Command {
+    Gc(Gc),
     MakeToken(MakeToken),
+    RevokeToken(RevokeToken),
+    ListTokens(ListTokens),
+    Verify(Verify),
+    VerifyAuditLog(VerifyAuditLog),
+    AuditLog(AuditLog),
 }
 
 #[tokio::main]
@@ -34,7 +46,13 @@ async fn main() -> Result<()> {
     let config = config::load_config(opts.config.as_deref(), false).await?;
 
     match opts.command {
+        Command::Gc(_) => gc::run(config, opts).await?,
         Command::MakeToken(_) => make_token::run(config, opts).await?,
+        Command::RevokeToken(_) => revoke_token::run(config, opts).await?,
+        Command::ListTokens(_) => list_tokens::run(config, opts).await?,
+        Command::Verify(_) => verify::run(config, opts).await?,
+        Command::VerifyAuditLog(_) => verify_audit_log::run(config, opts).await?,
+        Command::AuditLog(_) => audit_log::run(config, opts).await?,
     }
 
     Ok(())
@@ -7,27 +7,42 @@
 use std::collections::VecDeque;
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use axum::http;
 use axum::{
-    Router,
     body::Body,
     extract::{Extension, Path},
     http::StatusCode,
     response::{IntoResponse, Redirect, Response},
     routing::get,
+    Router,
 };
-use futures::TryStreamExt as _;
+use bytes::Bytes;
+use chrono::Utc;
 use futures::stream::BoxStream;
+use futures::TryStreamExt as _;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use serde::Serialize;
-use tokio_util::io::ReaderStream;
+use tokio_util::io::{ReaderStream, StreamReader};
 use tracing::instrument;
 
-use crate::database::BunkerDatabase;
+use super::upstream;
+use crate::database::entity::build_log::{self, Entity as BuildLog};
+use crate::database::entity::cache::CacheModel;
 use crate::database::entity::chunk::ChunkModel;
-use crate::error::{ErrorKind, ServerResult};
-use crate::narinfo::NarInfo;
+use crate::database::entity::nar::NarModel;
+use crate::database::entity::nar_listing::{self, Entity as NarListing};
+use crate::database::entity::object::ObjectModel;
+use crate::database::BunkerDatabase;
+use crate::encryption::{open_chunk, MasterKey};
+use crate::error::{ErrorKind, ServerError, ServerResult};
+use crate::metrics;
+use crate::narinfo::{Compression, NarInfo};
 use crate::nix_manifest;
 use crate::storage::{Download, StorageBackend};
 use crate::{RequestState, State};
@@ -36,6 +51,12 @@ use bunker::mime;
 use bunker::nix_store::StorePathHash;
 use bunker::stream::merge_chunks;
 
+/// The storage backend and master key that chunks are streamed with.
+type ChunkStreamerArg = (
+    Arc<Box<dyn StorageBackend + 'static>>,
+    Option<Arc<MasterKey>>,
+);
+
 #[derive(Debug, Clone, Serialize)]
 struct NixCacheInfo {
     #[serde(rename = "WantMassQuery")]
@@ -93,56 +114,157 @@ async fn get_nix_cache_info(
 /// `/:cache/:path`, which may be one of
 /// - GET `/:cache/{storePathHash}.narinfo`
 /// - HEAD `/:cache/{storePathHash}.narinfo`
-/// - GET `/:cache/{storePathHash}.ls` (not implemented)
+/// - GET `/:cache/{storePathHash}.ls`
 #[instrument(skip_all, fields(cache_name, path))]
 #[axum_macros::debug_handler]
 async fn get_store_path_info(
     Extension(state): Extension<State>,
     Extension(req_state): Extension<RequestState>,
     Path((cache_name, path)): Path<(CacheName, String)>,
-) -> ServerResult<NarInfo> {
+) -> ServerResult<Response> {
     let components: Vec<&str> = path.splitn(2, '.').collect();
 
     if components.len() != 2 {
         return Err(ErrorKind::NotFound.into());
     }
 
-    // TODO: Other endpoints
-    if components[1] != "narinfo" {
-        return Err(ErrorKind::NotFound.into());
-    }
-
     let store_path_hash = StorePathHash::new(components[0].to_string())?;
 
+    match components[1] {
+        "narinfo" => Ok(get_nar_info(state, req_state, cache_name, store_path_hash)
+            .await?
+            .into_response()),
+        "ls" => get_nar_listing(state, req_state, cache_name, store_path_hash).await,
+        _ => Err(ErrorKind::NotFound.into()),
+    }
+}
+
+/// Gets the narinfo of a store path.
+///
+/// - GET `/:cache/{storePathHash}.narinfo`
+async fn get_nar_info(
+    state: State,
+    req_state: RequestState,
+    cache_name: CacheName,
+    store_path_hash: StorePathHash,
+) -> ServerResult<NarInfo> {
     tracing::debug!(
         "Received request for {}.narinfo in {:?}",
         store_path_hash.as_str(),
         cache_name
     );
 
-    let (object, cache, nar, _) = state
-        .database()
-        .await?
-        .find_object_and_chunks_by_store_path_hash(&cache_name, &store_path_hash, false)
-        .await?;
+    let (object, cache, nar, _) =
+        find_object_or_fetch_upstream(&state, &req_state, &cache_name, &store_path_hash, false)
+            .await?;
 
     let permission = req_state
         .auth
-        .get_permission_for_cache(&cache_name, cache.is_public);
+        .get_permission_for_object(&cache_name, &cache, &object);
     permission.require_pull()?;
 
-    req_state.set_public_cache(cache.is_public);
+    req_state.set_public_cache(cache.is_public && !cache.is_object_restricted(&object));
 
     let mut narinfo = object.to_nar_info(&nar)?;
 
-    if narinfo.signature().is_none() {
-        let keypair = cache.keypair()?;
-        narinfo.sign(&keypair);
+    // Signatures from the uploader are kept alongside our own. During
+    // key rotation, retiring keypairs keep signing as well.
+    for signing_key in cache.signing_keys()? {
+        let signer = signing_key.into_signer(state.signer.as_ref())?;
+        narinfo.sign(signer.as_ref()).await?;
     }
 
     Ok(narinfo)
 }
 
+/// Gets the file listing of a store path.
+///
+/// - GET `/:cache/{storePathHash}.ls`
+///
+/// The listing is generated from the NAR on the first request
+/// and cached in the database afterwards.
+async fn get_nar_listing(
+    state: State,
+    req_state: RequestState,
+    cache_name: CacheName,
+    store_path_hash: StorePathHash,
+) -> ServerResult<Response> {
+    tracing::debug!(
+        "Received request for {}.ls in {:?}",
+        store_path_hash.as_str(),
+        cache_name
+    );
+
+    let database = state.database().await?;
+
+    let (object, cache, nar, _) =
+        find_object_or_fetch_upstream(&state, &req_state, &cache_name, &store_path_hash, false)
+            .await?;
+
+    let permission = req_state
+        .auth
+        .get_permission_for_object(&cache_name, &cache, &object);
+    permission.require_pull()?;
+
+    req_state.set_public_cache(cache.is_public && !cache.is_object_restricted(&object));
+
+    let cached = NarListing::find_by_id(nar.id)
+        .one(database)
+        .await
+        .map_err(ServerError::database_error)?;
+
+    let listing = if let Some(cached) = cached {
+        cached.listing
+    } else {
+        let (_, _, nar, chunks) = database
+            .find_object_and_chunks_by_store_path_hash(&cache_name, &store_path_hash, true)
+            .await?;
+
+        if chunks.iter().any(Option::is_none) {
+            return Err(ErrorKind::IncompleteNar.into());
+        }
+
+        let chunks: VecDeque<_> = chunks.into_iter().map(Option::unwrap).collect();
+        let storage = state.storage().await?.clone();
+
+        let stream = StreamReader::new(stream_nar_uncompressed(
+            chunks,
+            storage,
+            state.master_key.clone(),
+        ));
+        let listing = crate::nar_listing::NarListing::from_nar(stream)
+            .await
+            .map_err(ServerError::storage_error)?;
+        let listing = serde_json::to_string(&listing).map_err(ServerError::storage_error)?;
+
+        // Another request may have generated the listing in the meantime
+        NarListing::insert(nar_listing::ActiveModel {
+            nar_id: Set(nar.id),
+            listing: Set(listing.clone()),
+            created_at: Set(Utc::now()),
+        })
+        .on_conflict(
+            OnConflict::column(nar_listing::Column::NarId)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(database)
+        .await
+        .map_err(ServerError::database_error)?;
+
+        listing
+    };
+
+    Ok((
+        [(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static(mime::NAR_LISTING),
+        )],
+        listing,
+    )
+        .into_response())
+}
+
 /// Gets a NAR.
 ///
 /// - GET `:cache/nar/{storePathHash}.nar`
@@ -176,16 +298,16 @@ async fn get_nar(
 
     let database = state.database().await?;
 
-    let (object, cache, _nar, chunks) = database
-        .find_object_and_chunks_by_store_path_hash(&cache_name, &store_path_hash, true)
-        .await?;
+    let (object, cache, _nar, chunks) =
+        find_object_or_fetch_upstream(&state, &req_state, &cache_name, &store_path_hash, true)
+            .await?;
 
     let permission = req_state
         .auth
-        .get_permission_for_cache(&cache_name, cache.is_public);
+        .get_permission_for_object(&cache_name, &cache, &object);
     permission.require_pull()?;
 
-    req_state.set_public_cache(cache.is_public);
+    req_state.set_public_cache(cache.is_public && !cache.is_object_restricted(&object));
 
     if chunks.iter().any(Option::is_none) {
         // at least one of the chunks is missing :(
@@ -199,9 +321,19 @@ async fn get_nar(
         let chunk = chunks[0].as_ref().unwrap();
         let remote_file = &chunk.remote_file.0;
         let storage = state.storage().await?;
-        match storage.download_file_db(remote_file, false).await? {
+
+        // Encrypted chunks are decrypted by us, so clients can't be
+        // redirected to the storage backend
+        let encrypted = chunk.encryption_key.is_some();
+
+        match storage.download_file_db(remote_file, encrypted).await? {
+            Download::Url(_) if encrypted => Err(ErrorKind::StorageError(anyhow!(
+                "Storage returned a URL for an encrypted chunk"
+            ))
+            .into()),
             Download::Url(url) => Ok(Redirect::temporary(&url).into_response()),
             Download::AsyncRead(stream) => {
+                let stream = open_chunk(state.master_key.as_deref(), chunk, stream)?;
                 let stream = ReaderStream::new(stream).map_err(|e| {
                     tracing::error!(%e, "Stream error");
                     e
@@ -224,7 +356,7 @@ async fn get_nar(
             IoError::new(IoErrorKind::Other, e)
         }
 
-        let streamer = |chunk: ChunkModel, storage: Arc<Box<dyn StorageBackend + 'static>>| async move {
+        let streamer = |chunk: ChunkModel, (storage, master_key): ChunkStreamerArg| async move {
             match storage
                 .download_file_db(&chunk.remote_file.0, true)
                 .await
@@ -235,6 +367,8 @@ async fn get_nar(
                     "URLs not supported for NAR reassembly",
                 )),
                 Download::AsyncRead(stream) => {
+                    let stream =
+                        open_chunk(master_key.as_deref(), &chunk, stream).map_err(io_error)?;
                     let stream: BoxStream<_> = Box::pin(ReaderStream::new(stream));
                     Ok(stream)
                 }
@@ -243,11 +377,13 @@ async fn get_nar(
 
         let chunks: VecDeque<_> = chunks.into_iter().map(Option::unwrap).collect();
         let storage = state.storage().await?.clone();
+        let master_key = state.master_key.clone();
 
         // TODO: Make num_prefetch configurable
         // The ideal size depends on the average chunk size
-        let merged = merge_chunks(chunks, streamer, storage, 2).map_err(|e| {
+        let merged = merge_chunks(chunks, streamer, (storage, master_key), 2).map_err(|e| {
             tracing::error!(%e, "Stream error");
+            metrics::record_chunk_download_error();
             e
         });
         let body = Body::from_stream(merged);
@@ -263,9 +399,160 @@ async fn get_nar(
     }
 }
 
+/// Gets a build log.
+///
+/// - GET `:cache/log/{drvName}`
+///
+/// Logs are stored compressed and always streamed through
+/// the server uncompressed.
+#[instrument(skip_all, fields(cache_name, drv_name))]
+async fn get_build_log(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Path((cache_name, drv_name)): Path<(CacheName, String)>,
+) -> ServerResult<Response> {
+    tracing::debug!("Received request for log {} in {:?}", drv_name, cache_name);
+
+    let database = state.database().await?;
+    let cache = req_state
+        .auth
+        .auth_cache(database, &cache_name, |cache, permission| {
+            permission.require_pull()?;
+            Ok(cache)
+        })
+        .await?;
+
+    req_state.set_public_cache(cache.is_public);
+
+    let log = BuildLog::find()
+        .filter(build_log::Column::CacheId.eq(cache.id))
+        .filter(build_log::Column::DrvName.eq(drv_name))
+        .one(database)
+        .await
+        .map_err(ServerError::database_error)?
+        .ok_or(ErrorKind::NotFound)?;
+
+    let compression = Compression::from_str(&log.compression)?;
+
+    let storage = state.storage().await?;
+    let stream = match storage.download_file_db(&log.remote_file.0, true).await? {
+        Download::Url(_) => {
+            return Err(
+                ErrorKind::StorageError(anyhow!("Storage returned a URL for a log")).into(),
+            );
+        }
+        Download::AsyncRead(stream) => stream,
+    };
+
+    let stream = compression
+        .decompress(stream)
+        .map_err(ServerError::storage_error)?;
+    let stream = ReaderStream::new(stream).map_err(|e| {
+        tracing::error!(%e, "Stream error");
+        e
+    });
+    let body = Body::from_stream(stream);
+
+    Ok((
+        [(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static(mime::BUILD_LOG),
+        )],
+        body,
+    )
+        .into_response())
+}
+
+/// Finds an object, fetching it from the upstream proxy if it's missing.
+async fn find_object_or_fetch_upstream(
+    state: &State,
+    req_state: &RequestState,
+    cache_name: &CacheName,
+    store_path_hash: &StorePathHash,
+    include_chunks: bool,
+) -> ServerResult<(ObjectModel, CacheModel, NarModel, Vec<Option<ChunkModel>>)> {
+    let database = state.database().await?;
+
+    let result = database
+        .find_object_and_chunks_by_store_path_hash(cache_name, store_path_hash, include_chunks)
+        .await;
+
+    match result {
+        Err(e) if matches!(e.kind(), ErrorKind::NoSuchObject) => {
+            let cache = database.find_cache(cache_name).await?;
+            if cache.upstream_proxy.is_none() {
+                return Err(e);
+            }
+
+            // Only clients that can pull are allowed to trigger upstream fetches
+            let permission = req_state
+                .auth
+                .get_permission_for_cache(cache_name, cache.is_public);
+            permission.require_pull()?;
+
+            if upstream::fetch_path(state, cache_name, cache, store_path_hash).await? {
+                database
+                    .find_object_and_chunks_by_store_path_hash(
+                        cache_name,
+                        store_path_hash,
+                        include_chunks,
+                    )
+                    .await
+            } else {
+                Err(e)
+            }
+        }
+        result => result,
+    }
+}
+
+/// Returns a stream of the uncompressed NAR reassembled from its chunks.
+///
+/// Unlike the compressed chunks served by `get_nar`, each chunk
+/// is decompressed individually before being concatenated.
+fn stream_nar_uncompressed(
+    chunks: VecDeque<ChunkModel>,
+    storage: Arc<Box<dyn StorageBackend + 'static>>,
+    master_key: Option<Arc<MasterKey>>,
+) -> BoxStream<'static, Result<Bytes, IoError>> {
+    fn io_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> IoError {
+        IoError::new(IoErrorKind::Other, e)
+    }
+
+    let streamer = |chunk: ChunkModel, (storage, master_key): ChunkStreamerArg| async move {
+        let compression = Compression::from_str(&chunk.compression).map_err(io_error)?;
+
+        let stream = match storage
+            .download_file_db(&chunk.remote_file.0, true)
+            .await
+            .map_err(io_error)?
+        {
+            Download::Url(_) => {
+                return Err(IoError::new(
+                    IoErrorKind::Other,
+                    "URLs not supported for NAR reassembly",
+                ));
+            }
+            Download::AsyncRead(stream) => stream,
+        };
+
+        let stream = open_chunk(master_key.as_deref(), &chunk, stream).map_err(io_error)?;
+        let stream: BoxStream<_> = Box::pin(ReaderStream::new(compression.decompress(stream)?));
+        Ok(stream)
+    };
+
+    Box::pin(
+        merge_chunks(chunks, streamer, (storage, master_key), 2).map_err(|e| {
+            metrics::record_chunk_download_error();
+            e
+        }),
+    )
+}
+
 pub fn get_router() -> Router {
     Router::new()
         .route("/:cache/nix-cache-info", get(get_nix_cache_info))
         .route("/:cache/:path", get(get_store_path_info))
         .route("/:cache/nar/:path", get(get_nar))
+        .route("/:cache/log/:drv", get(get_build_log))
 }
@@ -1,7 +1,8 @@
 //! HTTP API.
 
 mod binary_cache;
-mod v1;
+mod upstream;
+pub(crate) mod v1;
 
 use axum::{response::Html, routing::get, Router};
 
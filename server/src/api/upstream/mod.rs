@@ -0,0 +1,227 @@
+//! Upstream pull-through proxy.
+//!
+//! A cache with an upstream proxy configured fetches paths it
+//! doesn't have from the upstream substituter on demand. This
+//! turns Bunker into a caching proxy for environments without
+//! direct access to the upstream.
+//!
+//! The upstream narinfo must be signed by a trusted key, and the
+//! NAR is ingested through the normal upload pipeline which verifies
+//! its hash and size. The `URL` field isn't covered by the signature,
+//! so NARs are only fetched from the origin of the upstream.
+
+#[cfg(test)]
+mod tests;
+
+use std::io;
+use std::path::Path;
+
+use anyhow::anyhow;
+use futures::TryStreamExt;
+use reqwest::{StatusCode, Url};
+use tokio_util::io::StreamReader;
+
+use super::v1::upload_path::ingest_path;
+use crate::database::entity::cache::CacheModel;
+use crate::error::{ErrorKind, ServerError, ServerResult};
+use crate::narinfo::NarInfo;
+use crate::State;
+use bunker::api::v1::upload_path::UploadPathNarInfo;
+use bunker::cache::CacheName;
+use bunker::nix_store::StorePathHash;
+use bunker::signing::NixPublicKey;
+
+/// Fetches a missing path from the upstream proxy of a cache.
+///
+/// Returns whether the path was added to the cache. If the cache
+/// has no upstream proxy or the path doesn't exist upstream,
+/// `false` is returned.
+pub(crate) async fn fetch_path(
+    state: &State,
+    cache_name: &CacheName,
+    cache: CacheModel,
+    store_path_hash: &StorePathHash,
+) -> ServerResult<bool> {
+    let proxy = match &cache.upstream_proxy {
+        Some(proxy) => proxy.0.clone(),
+        None => return Ok(false),
+    };
+
+    tracing::debug!(
+        "Fetching {} from upstream {}",
+        store_path_hash.as_str(),
+        proxy.url
+    );
+
+    let base = base_url(&proxy.url)?;
+
+    // Fetch and verify the narinfo
+    let narinfo_url = base
+        .join(&format!("{}.narinfo", store_path_hash.as_str()))
+        .map_err(ServerError::upstream_error)?;
+
+    let res = state
+        .http_client
+        .get(narinfo_url)
+        .send()
+        .await
+        .map_err(ServerError::upstream_error)?;
+
+    match res.status() {
+        StatusCode::NOT_FOUND | StatusCode::FORBIDDEN => return Ok(false),
+        status if !status.is_success() => {
+            return Err(ErrorKind::UpstreamError(anyhow!(
+                "Upstream returned {} for the narinfo",
+                status
+            ))
+            .into());
+        }
+        _ => {}
+    }
+
+    let narinfo = res.text().await.map_err(ServerError::upstream_error)?;
+    let narinfo = NarInfo::from_str(&narinfo).map_err(|e| {
+        ErrorKind::UpstreamError(anyhow!("Upstream returned an invalid narinfo: {}", e))
+    })?;
+
+    validate_narinfo(&narinfo, &cache, store_path_hash)?;
+    verify_signature(
+        &narinfo,
+        &proxy.public_keys,
+        &cache.upstream_cache_key_names.0,
+    )?;
+
+    // Fetch the NAR
+    let nar_url = nar_url(&base, &narinfo.url)?;
+
+    let res = state
+        .http_client
+        .get(nar_url)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(ServerError::upstream_error)?;
+
+    let stream = StreamReader::new(
+        res.bytes_stream()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+    );
+    let stream = narinfo
+        .compression
+        .decompress(Box::new(stream))
+        .map_err(ServerError::upstream_error)?;
+
+    let upload_info = UploadPathNarInfo {
+        cache: cache_name.clone(),
+        store_path_hash: store_path_hash.clone(),
+        store_path: narinfo.store_path.to_string_lossy().to_string(),
+        references: narinfo.references,
+        system: narinfo.system,
+        deriver: narinfo.deriver,
+        sigs: narinfo.signatures.clone(),
+        ca: narinfo.ca,
+        nar_hash: narinfo.nar_hash,
+        nar_size: narinfo.nar_size,
+        restricted: false,
+    };
+
+    let database = state.database().await?;
+    let _ = ingest_path(None, cache, upload_info, stream, database, state).await?;
+
+    Ok(true)
+}
+
+/// Returns the base URL of an upstream substituter.
+fn base_url(url: &str) -> ServerResult<Url> {
+    let url = if url.ends_with('/') {
+        url.to_string()
+    } else {
+        format!("{}/", url)
+    };
+
+    Url::parse(&url).map_err(ServerError::upstream_error)
+}
+
+/// Returns the URL of the NAR referenced by an upstream narinfo.
+///
+/// The URL must have the same origin as the upstream.
+fn nar_url(base: &Url, url: &str) -> ServerResult<Url> {
+    let nar_url = base.join(url).map_err(ServerError::upstream_error)?;
+
+    if nar_url.origin() != base.origin() {
+        return Err(ErrorKind::UpstreamError(anyhow!(
+            "Upstream narinfo points to a different origin: {}",
+            nar_url
+        ))
+        .into());
+    }
+
+    Ok(nar_url)
+}
+
+/// Validates that an upstream narinfo describes the requested path.
+fn validate_narinfo(
+    narinfo: &NarInfo,
+    cache: &CacheModel,
+    store_path_hash: &StorePathHash,
+) -> ServerResult<()> {
+    if narinfo.store_dir() != Path::new(&cache.store_dir) {
+        return Err(ErrorKind::UpstreamError(anyhow!(
+            "Upstream store directory {:?} doesn't match the cache",
+            narinfo.store_dir()
+        ))
+        .into());
+    }
+
+    let base_name = narinfo
+        .store_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+
+    if base_name.split_once('-').map(|(hash, _)| hash) != Some(store_path_hash.as_str()) {
+        return Err(
+            ErrorKind::UpstreamError(anyhow!("Upstream returned a different store path")).into(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Verifies that an upstream narinfo is signed by a trusted key.
+///
+/// One of the signatures must be made by one of the proxy's public
+/// keys whose name is also listed in `upstream_cache_key_names`.
+fn verify_signature(
+    narinfo: &NarInfo,
+    public_keys: &[String],
+    key_names: &[String],
+) -> ServerResult<()> {
+    let untrusted =
+        || ErrorKind::UpstreamError(anyhow!("Upstream narinfo isn't signed by a trusted key"));
+
+    let public_keys = public_keys
+        .iter()
+        .map(|public_key| NixPublicKey::from_str(public_key))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let fingerprint = narinfo.fingerprint();
+    for signature in narinfo.signatures() {
+        let Some((key_name, _)) = signature.split_once(':') else {
+            continue;
+        };
+
+        if !key_names.iter().any(|name| name == key_name) {
+            continue;
+        }
+
+        if public_keys
+            .iter()
+            .any(|public_key| public_key.verify(&fingerprint, signature).is_ok())
+        {
+            return Ok(());
+        }
+    }
+
+    Err(untrusted().into())
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter};
+
+use super::*;
+use crate::database::entity::cache;
+use crate::database::entity::object::{self, Entity as Object};
+use crate::database::entity::Json as DbJson;
+use crate::narinfo::Compression;
+use crate::testing::{self, TestState};
+use bunker::api::v1::cache_config::UpstreamProxy;
+use bunker::hash::Hash;
+use bunker::signing::NixKeypair;
+
+const NARINFO: &str = r#"
+StorePath: /nix/store/xcp9cav49dmsjbwdjlmkjxj10gkpx553-hello-2.10
+URL: nar/0nqgf15qfiacfxrgm2wkw0gwwncjqqzzalj8rs14w9srkydkjsk9.nar.xz
+Compression: xz
+FileHash: sha256:0nqgf15qfiacfxrgm2wkw0gwwncjqqzzalj8rs14w9srkydkjsk9
+FileSize: 41104
+NarHash: sha256:91e129ac1959d062ad093d2b1f8b65afae0f712056fe3eac78ec530ff6a1bb9a
+NarSize: 206104
+References: 563528481rvhc5kxwipjmg6rqrl95mdx-glibc-2.33-56 xcp9cav49dmsjbwdjlmkjxj10gkpx553-hello-2.10
+Deriver: vvb4wxmnjixmrkhmj2xb75z62hrr41i7-hello-2.10.drv
+Sig: cache.nixos.org-1:lo9EfNIL4eGRuNh7DTbAAffWPpI2SlYC/8uP7JnhgmfRIUNGhSbFe8qEaKN0mFS02TuhPpXFPNtRkFcCp0hGAQ==
+"#;
+
+const NIXOS_PUBLIC_KEY: &str = "cache.nixos.org-1:6NCHdD59X431o0gWypbMrAURkbJ16ZPMQFGspcDShjY=";
+const OTHER_PUBLIC_KEY: &str = "bunker-test:C929acssgtJoINkUtLbc81GFJPUW9maR77TxEu9ZpRw=";
+
+#[test]
+fn test_verify_signature() {
+    let narinfo = NarInfo::from_str(NARINFO).unwrap();
+    let public_keys = vec![OTHER_PUBLIC_KEY.to_string(), NIXOS_PUBLIC_KEY.to_string()];
+    let key_names = vec!["cache.nixos.org-1".to_string()];
+
+    verify_signature(&narinfo, &public_keys, &key_names).expect("Signature should be trusted");
+
+    // Key name not listed in upstream_cache_key_names
+    assert!(verify_signature(&narinfo, &public_keys, &[]).is_err());
+
+    // No matching public key
+    assert!(verify_signature(&narinfo, &[OTHER_PUBLIC_KEY.to_string()], &key_names).is_err());
+
+    // Tampered narinfo
+    let mut tampered = NarInfo::from_str(NARINFO).unwrap();
+    tampered.nar_size += 1;
+    assert!(verify_signature(&tampered, &public_keys, &key_names).is_err());
+
+    // Unsigned narinfo
+    let mut unsigned = NarInfo::from_str(NARINFO).unwrap();
+    unsigned.signatures.clear();
+    assert!(verify_signature(&unsigned, &public_keys, &key_names).is_err());
+
+    // Additional signatures from unknown keys are skipped
+    let mut multiple = NarInfo::from_str(NARINFO).unwrap();
+    multiple.signatures.insert(0, "other-1:AAAA".to_string());
+    verify_signature(&multiple, &public_keys, &key_names).expect("Signature should be trusted");
+}
+
+#[test]
+fn test_base_url() {
+    let with_slash = base_url("http://localhost:8080/mirror/").unwrap();
+    let without_slash = base_url("http://localhost:8080/mirror").unwrap();
+    assert_eq!(with_slash, without_slash);
+
+    assert_eq!(
+        "http://localhost:8080/mirror/nar/abc.nar.xz",
+        without_slash.join("nar/abc.nar.xz").unwrap().as_str()
+    );
+}
+
+#[test]
+fn test_nar_url() {
+    let base = base_url("http://localhost:8080/mirror").unwrap();
+
+    assert_eq!(
+        "http://localhost:8080/mirror/nar/abc.nar.xz",
+        nar_url(&base, "nar/abc.nar.xz").unwrap().as_str()
+    );
+    assert_eq!(
+        "http://localhost:8080/nar/abc.nar.xz",
+        nar_url(&base, "http://localhost:8080/nar/abc.nar.xz")
+            .unwrap()
+            .as_str()
+    );
+
+    assert!(nar_url(&base, "http://169.254.169.254/latest/meta-data").is_err());
+    assert!(nar_url(&base, "//internal.example/abc.nar").is_err());
+    assert!(nar_url(&base, "https://localhost:8080/abc.nar").is_err());
+    assert!(nar_url(&base, "http://localhost:8081/abc.nar").is_err());
+    assert!(nar_url(&base, "file:///etc/passwd").is_err());
+}
+
+const STORE_PATH: &str = "/nix/store/xcp9cav49dmsjbwdjlmkjxj10gkpx553-hello-2.10";
+const STORE_PATH_HASH: &str = "xcp9cav49dmsjbwdjlmkjxj10gkpx553";
+
+/// Returns a narinfo of `STORE_PATH` signed by a keypair.
+fn signed_narinfo(keypair: &NixKeypair, nar: &[u8], url: &str) -> Vec<u8> {
+    let mut narinfo = NarInfo {
+        store_path: PathBuf::from(STORE_PATH),
+        url: url.to_string(),
+        compression: Compression::None,
+        file_hash: None,
+        file_size: None,
+        nar_hash: Hash::sha256_from_bytes(nar),
+        nar_size: nar.len(),
+        references: Vec::new(),
+        system: None,
+        deriver: None,
+        signatures: Vec::new(),
+        ca: None,
+    };
+    narinfo
+        .signatures
+        .push(keypair.sign(&narinfo.fingerprint()));
+
+    narinfo.to_string().unwrap().into_bytes()
+}
+
+/// Creates a cache proxying an upstream that trusts a keypair.
+async fn create_proxy_cache(
+    state: &TestState,
+    upstream: SocketAddr,
+    keypair: &NixKeypair,
+) -> CacheModel {
+    let cache = state.create_cache("proxy").await;
+
+    let mut update: cache::ActiveModel = cache.into();
+    update.upstream_proxy = Set(Some(DbJson(UpstreamProxy {
+        url: format!("http://{}/mirror", upstream),
+        public_keys: vec![keypair.export_public_key()],
+    })));
+    update.upstream_cache_key_names = Set(DbJson(vec![keypair.name().to_string()]));
+
+    update
+        .update(state.database().await.unwrap())
+        .await
+        .unwrap()
+}
+
+async fn find_object(state: &TestState, cache: &CacheModel) -> Option<object::Model> {
+    Object::find()
+        .filter(object::Column::CacheId.eq(cache.id))
+        .filter(object::Column::StorePathHash.eq(STORE_PATH_HASH))
+        .one(state.database().await.unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_fetch_path() {
+    let keypair = NixKeypair::generate("upstream-1").unwrap();
+    let nar = b"hello from upstream".to_vec();
+
+    let upstream = testing::serve_files(HashMap::from([
+        (
+            format!("/mirror/{}.narinfo", STORE_PATH_HASH),
+            signed_narinfo(&keypair, &nar, "nar/hello.nar"),
+        ),
+        ("/mirror/nar/hello.nar".to_string(), nar),
+    ]))
+    .await;
+
+    let state = TestState::new().await;
+    let cache = create_proxy_cache(&state, upstream, &keypair).await;
+    let cache_name = CacheName::new(cache.name.clone()).unwrap();
+
+    let store_path_hash = StorePathHash::new(STORE_PATH_HASH.to_string()).unwrap();
+    assert!(
+        fetch_path(&state, &cache_name, cache.clone(), &store_path_hash)
+            .await
+            .unwrap()
+    );
+
+    let object = find_object(&state, &cache).await.unwrap();
+    assert_eq!(STORE_PATH, object.store_path);
+
+    // Missing upstream
+    let missing = StorePathHash::new("0".repeat(32)).unwrap();
+    assert!(!fetch_path(&state, &cache_name, cache, &missing)
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn test_fetch_path_other_origin() {
+    let keypair = NixKeypair::generate("upstream-1").unwrap();
+    let nar = b"hello from elsewhere".to_vec();
+
+    let other =
+        testing::serve_files(HashMap::from([("/hello.nar".to_string(), nar.clone())])).await;
+
+    let upstream = testing::serve_files(HashMap::from([(
+        format!("/mirror/{}.narinfo", STORE_PATH_HASH),
+        signed_narinfo(&keypair, &nar, &format!("http://{}/hello.nar", other)),
+    )]))
+    .await;
+
+    let state = TestState::new().await;
+    let cache = create_proxy_cache(&state, upstream, &keypair).await;
+    let cache_name = CacheName::new(cache.name.clone()).unwrap();
+
+    let store_path_hash = StorePathHash::new(STORE_PATH_HASH.to_string()).unwrap();
+    assert!(
+        fetch_path(&state, &cache_name, cache.clone(), &store_path_hash)
+            .await
+            .is_err()
+    );
+    assert!(find_object(&state, &cache).await.is_none());
+}
+
+#[tokio::test]
+async fn test_fetch_path_without_proxy() {
+    let state = TestState::new().await;
+    let cache = state.create_cache("test").await;
+    let cache_name = CacheName::new(cache.name.clone()).unwrap();
+
+    // Caches without a proxy store it as NULL
+    let cache = cache::Entity::find_by_id(cache.id)
+        .one(state.database().await.unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(None, cache.upstream_proxy);
+
+    let store_path_hash = StorePathHash::new(STORE_PATH_HASH.to_string()).unwrap();
+    assert!(
+        !fetch_path(&state, &cache_name, cache.clone(), &store_path_hash)
+            .await
+            .unwrap()
+    );
+    assert!(find_object(&state, &cache).await.is_none());
+}
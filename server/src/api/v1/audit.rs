@@ -0,0 +1,64 @@
+//! Audit log endpoint.
+
+use axum::extract::{Extension, Json, Query};
+use tracing::instrument;
+
+use crate::audit::{find_entries, parse_action, to_audit_entry, AuditLogFilter};
+use crate::error::{ErrorKind, ServerError, ServerResult};
+use crate::{RequestState, State};
+use bunker::api::v1::audit::{AuditLogRequest, AuditLogResponse};
+
+/// The number of entries returned if the client doesn't ask for a limit.
+const DEFAULT_LIMIT: u64 = 100;
+
+/// The maximum number of entries returned in a page.
+const MAX_LIMIT: u64 = 1000;
+
+/// Queries the audit log of a cache.
+///
+/// - GET `/_api/v1/audit`
+#[instrument(skip_all, fields(query))]
+pub(crate) async fn get_audit_log(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Query(query): Query<AuditLogRequest>,
+) -> ServerResult<Json<AuditLogResponse>> {
+    let database = state.database().await?;
+    req_state
+        .auth
+        .auth_cache(database, &query.cache, |_, permission| {
+            permission.require_configure_cache()?;
+            Ok(())
+        })
+        .await?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let filter = AuditLogFilter {
+        cache: Some(query.cache.as_str().to_owned()),
+        subject: query.subject,
+        action: query
+            .action
+            .map(|action| parse_action(&action))
+            .transpose()
+            .map_err(ErrorKind::RequestError)?,
+        after: query.after,
+    };
+
+    // Fetch one more row to find out whether there is a next page
+    let mut entries = find_entries(database, &filter, limit + 1)
+        .await
+        .map_err(ServerError::database_error)?;
+
+    let next_cursor = if entries.len() as u64 > limit {
+        entries.truncate(limit as usize);
+        entries.last().map(|entry| entry.id)
+    } else {
+        None
+    };
+
+    Ok(Json(AuditLogResponse {
+        entries: entries.into_iter().map(to_audit_entry).collect(),
+        next_cursor,
+    }))
+}
@@ -2,20 +2,27 @@
 
 use anyhow::anyhow;
 use axum::extract::{Extension, Json, Path};
-use chrono::Utc;
+use chrono::{Duration as ChronoDuration, Utc};
+use reqwest::Url;
 use sea_orm::sea_query::{Expr, OnConflict};
 use sea_orm::ActiveValue::Set;
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use tracing::instrument;
-use crate::database::entity::cache::{self, Entity as Cache};
+
+use crate::audit;
+use crate::database::entity::audit_log::AuditAction;
+use crate::database::entity::cache::{self, Entity as Cache, RetiringKeypair};
 use crate::database::entity::Json as DbJson;
+use crate::database::BunkerDatabase;
 use crate::error::{ErrorKind, ServerError, ServerResult};
+use crate::signer::SigningKey;
 use crate::{RequestState, State};
 use bunker::api::v1::cache_config::{
-    CacheConfig, CreateCacheRequest, KeypairConfig, RetentionPeriodConfig,
+    CacheConfig, CachePublicKey, CreateCacheRequest, KeypairConfig, KeypairRotationConfig,
+    KeypairState, PqcKeypairConfig, QuotaConfig, RetentionPeriodConfig, UpstreamProxyConfig,
 };
 use bunker::cache::CacheName;
-use bunker::signing::NixKeypair;
+use bunker::signing::{pqc_key_name, NixKeypair, NixPublicKey, PqcKeypair, PqcPublicKey};
 #[instrument(skip_all, fields(cache_name))]
 pub(crate) async fn get_cache_config(
     Extension(state): Extension<State>,
@@ -23,29 +30,67 @@ pub(crate) async fn get_cache_config(
     Path(cache_name): Path<CacheName>,
 ) -> ServerResult<Json<CacheConfig>> {
     let database = state.database().await?;
-    let cache = req_state
+    let (cache, permission) = req_state
         .auth
         .auth_cache(database, &cache_name, |cache, permission| {
             permission.require_pull()?;
-            Ok(cache)
+            Ok((cache, permission.clone()))
         })
         .await?;
-    let public_key = cache.keypair()?.export_public_key();
+    let signing_key = cache.signing_key()?.public_key();
+    let public_key = signing_key.export();
+    let pqc_public_key = signing_key.pqc_public_key().map(PqcPublicKey::export);
+
+    let mut public_keys = vec![CachePublicKey {
+        public_key: public_key.clone(),
+        state: KeypairState::Active,
+    }];
+    for retiring in cache.retiring_keys()? {
+        public_keys.push(CachePublicKey {
+            public_key: retiring.public_key().export(),
+            state: KeypairState::Retiring,
+        });
+    }
     let retention_period_config = if let Some(period) = cache.retention_period {
         RetentionPeriodConfig::Period(period as u32)
     } else {
         RetentionPeriodConfig::Global
     };
+    let upstream_proxy_config = if let Some(proxy) = cache.upstream_proxy {
+        UpstreamProxyConfig::Enabled(proxy.0)
+    } else {
+        UpstreamProxyConfig::Disabled
+    };
+    let quota_config = if let Some(quota) = cache.quota {
+        QuotaConfig::Limit(quota as u64)
+    } else {
+        QuotaConfig::Unlimited
+    };
+    // Computing the usage is expensive, so it's only returned to
+    // those who manage the quota
+    let usage = if permission.configure_cache_retention {
+        Some(database.get_cache_usage(cache.id).await? as u64)
+    } else {
+        None
+    };
     Ok(Json(CacheConfig {
         substituter_endpoint: Some(req_state.substituter_endpoint(cache_name)?),
         api_endpoint: Some(req_state.api_endpoint()?),
         keypair: None,
         public_key: Some(public_key),
+        public_keys: Some(public_keys),
+        keypair_rotation: None,
+        pqc_keypair: None,
+        pqc_public_key,
         is_public: Some(cache.is_public),
         store_dir: Some(cache.store_dir),
         priority: Some(cache.priority),
         upstream_cache_key_names: Some(cache.upstream_cache_key_names.0),
         retention_period: Some(retention_period_config),
+        upstream_proxy: Some(upstream_proxy_config),
+        quota: Some(quota_config),
+        restricted_paths: Some(cache.restricted_paths.0),
+        usage,
     }))
 }
 #[instrument(skip_all, fields(cache_name, payload))]
@@ -71,12 +116,136 @@ pub(crate) async fn configure_cache(
 
     let mut modified = false;
 
+    let signing_key = cache.signing_key()?;
+    let mut key_name = signing_key.name().to_string();
+    let mut external = signing_key.is_external();
+
+    let now = Utc::now();
+    let mut retiring_keypairs: Vec<RetiringKeypair> = cache
+        .retiring_keypairs
+        .0
+        .iter()
+        .filter(|retiring| !retiring.is_retired(now))
+        .cloned()
+        .collect();
+
+    if payload.keypair.is_some() && payload.keypair_rotation.is_some() {
+        return Err(ErrorKind::RequestError(anyhow!(
+            "The keypair cannot be replaced and rotated at the same time"
+        ))
+        .into());
+    }
+
     if let Some(keypair_cfg) = payload.keypair {
-        let keypair = match keypair_cfg {
-            KeypairConfig::Generate => NixKeypair::generate(cache_name.as_str())?,
-            KeypairConfig::Keypair(k) => k,
+        let signing_key = signing_key_from_config(&state, cache_name.as_str(), keypair_cfg)?;
+
+        // Post-quantum keys are held in the same place as the signing key
+        if signing_key.is_external() != external && payload.pqc_keypair.is_none() {
+            update.pqc_keypair = Set(None);
+        }
+
+        key_name = signing_key.name().to_string();
+        external = signing_key.is_external();
+        update.keypair = Set(signing_key.export());
+        modified = true;
+    }
+    if let Some(rotation_cfg) = payload.keypair_rotation {
+        let retiring = |overlap: Option<u32>| RetiringKeypair {
+            keypair: cache.keypair.clone(),
+            pqc_keypair: cache.pqc_keypair.clone(),
+            retire_at: overlap.map(|secs| now + ChronoDuration::seconds(secs.into())),
         };
-        update.keypair = Set(keypair.export_keypair());
+
+        match rotation_cfg {
+            KeypairRotationConfig::Rotate(overlap) => {
+                check_key_location(&state, false)?;
+                retiring_keypairs.insert(0, retiring(overlap));
+
+                // Nix identifies trusted keys by name, so the new key needs a fresh one
+                let name = format!("{}-{}", cache_name.as_str(), now.format("%Y%m%d%H%M%S"));
+                let keypair = NixKeypair::generate(&name)?;
+
+                let pqc_keypair = if cache.pqc_keypair.is_some() {
+                    Some(PqcKeypair::generate(&pqc_key_name(&name))?.export_keypair())
+                } else {
+                    None
+                };
+
+                update.keypair = Set(keypair.export_keypair());
+                update.pqc_keypair = Set(pqc_keypair);
+                key_name = name;
+                external = false;
+            }
+            KeypairRotationConfig::RotateToExternal(public_key, overlap) => {
+                check_key_location(&state, true)?;
+                retiring_keypairs.insert(0, retiring(overlap));
+
+                let public_key = NixPublicKey::from_str(&public_key)?;
+
+                update.keypair = Set(public_key.export());
+                update.pqc_keypair = Set(None);
+                key_name = public_key.name().to_string();
+                external = true;
+            }
+            KeypairRotationConfig::RetireAll => {
+                retiring_keypairs.clear();
+            }
+        }
+
+        update.retiring_keypairs = Set(DbJson(retiring_keypairs.clone()));
+        modified = true;
+    }
+    if update.keypair.is_set() {
+        for retiring in &retiring_keypairs {
+            if SigningKey::from_stored(&retiring.keypair, None)?.name() == key_name {
+                return Err(ErrorKind::RequestError(anyhow!(
+                    "The key name \"{}\" is still used by a retiring keypair",
+                    key_name
+                ))
+                .into());
+            }
+        }
+    }
+    if let Some(pqc_keypair_cfg) = payload.pqc_keypair {
+        let pqc_keypair = match pqc_keypair_cfg {
+            PqcKeypairConfig::Generate => {
+                check_key_location(&state, false)?;
+                let pqc_keypair = PqcKeypair::generate(&pqc_key_name(&key_name))?;
+                Some((
+                    pqc_keypair.name().to_string(),
+                    pqc_keypair.export_keypair(),
+                    false,
+                ))
+            }
+            PqcKeypairConfig::Keypair(k) => {
+                check_key_location(&state, false)?;
+                Some((k.name().to_string(), k.export_keypair(), false))
+            }
+            PqcKeypairConfig::External(public_key) => {
+                check_key_location(&state, true)?;
+                let public_key = PqcPublicKey::from_str(&public_key)?;
+                Some((public_key.name().to_string(), public_key.export(), true))
+            }
+            PqcKeypairConfig::Disabled => None,
+        };
+
+        if let Some((name, _, pqc_external)) = &pqc_keypair {
+            if *name == key_name {
+                return Err(ErrorKind::RequestError(anyhow!(
+                    "The post-quantum key must not have the same name as the signing key"
+                ))
+                .into());
+            }
+
+            if *pqc_external != external {
+                return Err(ErrorKind::RequestError(anyhow!(
+                    "The post-quantum key must be held in the same place as the signing key"
+                ))
+                .into());
+            }
+        }
+
+        update.pqc_keypair = Set(pqc_keypair.map(|(_, stored, _)| stored));
         modified = true;
     }
     if let Some(is_public) = payload.is_public {
@@ -95,6 +264,10 @@ pub(crate) async fn configure_cache(
         update.upstream_cache_key_names = Set(DbJson(upstream_cache_key_names));
         modified = true;
     }
+    if let Some(restricted_paths) = payload.restricted_paths {
+        update.restricted_paths = Set(DbJson(restricted_paths));
+        modified = true;
+    }
     if let Some(retention_period_config) = payload.retention_period {
         permission.require_configure_cache_retention()?;
 
@@ -112,12 +285,55 @@ pub(crate) async fn configure_cache(
 
         modified = true;
     }
+    if let Some(quota_config) = payload.quota {
+        permission.require_configure_cache_retention()?;
+
+        match quota_config {
+            QuotaConfig::Unlimited => {
+                update.quota = Set(None);
+            }
+            QuotaConfig::Limit(quota) => {
+                update.quota =
+                    Set(Some(quota.try_into().map_err(|_| {
+                        ErrorKind::RequestError(anyhow!("Invalid quota"))
+                    })?));
+            }
+        }
+
+        modified = true;
+    }
+    if let Some(upstream_proxy_config) = payload.upstream_proxy {
+        match upstream_proxy_config {
+            UpstreamProxyConfig::Disabled => {
+                update.upstream_proxy = Set(None);
+            }
+            UpstreamProxyConfig::Enabled(proxy) => {
+                Url::parse(&proxy.url).map_err(ServerError::request_error)?;
+                for public_key in &proxy.public_keys {
+                    NixPublicKey::from_str(public_key)?;
+                }
+
+                update.upstream_proxy = Set(Some(DbJson(proxy)));
+            }
+        }
+
+        modified = true;
+    }
     if modified {
         Cache::update(update)
             .exec(database)
             .await
             .map_err(ServerError::database_error)?;
 
+        audit::record(
+            &state,
+            &req_state,
+            AuditAction::ConfigureCache,
+            Some(&cache_name),
+            None,
+        )
+        .await;
+
         Ok(())
     } else {
         Err(ErrorKind::RequestError(anyhow!("No modifiable fields were set.")).into())
@@ -151,6 +367,15 @@ pub(crate) async fn destroy_cache(
         if deletion.rows_affected == 0 {
             Err(ErrorKind::NoSuchCache.into())
         } else {
+            audit::record(
+                &state,
+                &req_state,
+                AuditAction::DestroyCache,
+                Some(&cache_name),
+                None,
+            )
+            .await;
+
             Ok(())
         }
     } else {
@@ -164,6 +389,15 @@ pub(crate) async fn destroy_cache(
         if deletion.rows_affected == 0 {
             Err(ErrorKind::NoSuchCache.into())
         } else {
+            audit::record(
+                &state,
+                &req_state,
+                AuditAction::DestroyCache,
+                Some(&cache_name),
+                None,
+            )
+            .await;
+
             Ok(())
         }
     }
@@ -180,14 +414,13 @@ pub(crate) async fn create_cache(
 
     let database = state.database().await?;
 
-    let keypair = match payload.keypair {
-        KeypairConfig::Generate => NixKeypair::generate(cache_name.as_str())?,
-        KeypairConfig::Keypair(k) => k,
-    };
+    let signing_key = signing_key_from_config(&state, cache_name.as_str(), payload.keypair)?;
 
     let num_inserted = Cache::insert(cache::ActiveModel {
         name: Set(cache_name.to_string()),
-        keypair: Set(keypair.export_keypair()),
+        keypair: Set(signing_key.export()),
+        retiring_keypairs: Set(DbJson(Vec::new())),
+        restricted_paths: Set(DbJson(Vec::new())),
         is_public: Set(payload.is_public),
         store_dir: Set(payload.store_dir),
         priority: Set(payload.priority),
@@ -207,6 +440,55 @@ pub(crate) async fn create_cache(
     if num_inserted == 0 {
         Err(ErrorKind::CacheAlreadyExists.into())
     } else {
+        audit::record(
+            &state,
+            &req_state,
+            AuditAction::CreateCache,
+            Some(&cache_name),
+            None,
+        )
+        .await;
+
         Ok(())
     }
 }
+
+/// Returns the signing key for a keypair configuration.
+fn signing_key_from_config(
+    state: &State,
+    cache_name: &str,
+    config: KeypairConfig,
+) -> ServerResult<SigningKey> {
+    let signing_key = match config {
+        KeypairConfig::Generate => {
+            check_key_location(state, false)?;
+            SigningKey::Local(NixKeypair::generate(cache_name)?)
+        }
+        KeypairConfig::Keypair(k) => {
+            check_key_location(state, false)?;
+            SigningKey::Local(k)
+        }
+        KeypairConfig::External(public_key) => {
+            check_key_location(state, true)?;
+            SigningKey::External(NixPublicKey::from_str(&public_key)?)
+        }
+    };
+
+    Ok(signing_key)
+}
+
+/// Ensures that a new key is held where the server keeps its keys.
+///
+/// With an external signer configured, private keys must not be
+/// stored in the database.
+fn check_key_location(state: &State, external: bool) -> ServerResult<()> {
+    match (state.signer.is_some(), external) {
+        (true, false) => {
+            Err(ErrorKind::RequestError(anyhow!("Keys must be held by the external signer")).into())
+        }
+        (false, true) => {
+            Err(ErrorKind::RequestError(anyhow!("No external signer is configured")).into())
+        }
+        _ => Ok(()),
+    }
+}
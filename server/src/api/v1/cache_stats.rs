@@ -0,0 +1,160 @@
+//! Cache statistics endpoint.
+
+use axum::extract::{Extension, Json, Path};
+use sea_orm::entity::prelude::*;
+use sea_orm::query::{JoinType, Order, QueryOrder, QuerySelect};
+use sea_orm::sea_query::{Alias, Query, SelectStatement, SimpleExpr};
+use sea_orm::FromQueryResult;
+use tracing::instrument;
+
+use crate::database::entity::chunk::{self, ChunkState, Entity as Chunk};
+use crate::database::entity::chunkref::{self, Entity as ChunkRef};
+use crate::database::entity::nar;
+use crate::database::entity::object::{self, Entity as Object};
+use crate::error::{ServerError, ServerResult};
+use crate::{RequestState, State};
+use bunker::api::v1::cache_stats::{CacheStats, UploaderStats};
+use bunker::cache::CacheName;
+
+/// Number of uploaders to include in the statistics.
+const TOP_UPLOADERS: u64 = 10;
+
+#[derive(Debug, FromQueryResult)]
+struct ObjectTotals {
+    object_count: i64,
+    nar_bytes: Option<i64>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct ChunkTotals {
+    chunk_bytes: Option<i64>,
+    physical_bytes: Option<i64>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct UploaderTotals {
+    created_by: Option<String>,
+    object_count: i64,
+    nar_bytes: Option<i64>,
+}
+
+/// Returns usage statistics of a cache.
+///
+/// - GET `/_api/v1/cache-stats/:cache`
+///
+/// Chunks are counted once even if multiple objects use them.
+#[instrument(skip_all, fields(cache_name))]
+pub(crate) async fn get_cache_stats(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Path(cache_name): Path<CacheName>,
+) -> ServerResult<Json<CacheStats>> {
+    let database = state.database().await?;
+    let cache = req_state
+        .auth
+        .auth_cache(database, &cache_name, |cache, permission| {
+            permission.require_configure_cache()?;
+            Ok(cache)
+        })
+        .await?;
+
+    let in_cache = Expr::col((Object, object::Column::CacheId)).eq(cache.id);
+    let in_other_caches = Expr::col((Object, object::Column::CacheId)).ne(cache.id);
+
+    let objects = Object::find()
+        .select_only()
+        .column_as(object::Column::Id.into_expr().count(), "object_count")
+        .column_as(sum_bigint(nar::Column::NarSize), "nar_bytes")
+        .join(JoinType::InnerJoin, object::Relation::Nar.def())
+        .filter(object::Column::CacheId.eq(cache.id))
+        .into_model::<ObjectTotals>()
+        .one(database)
+        .await
+        .map_err(ServerError::database_error)?;
+
+    let chunks = Chunk::find()
+        .select_only()
+        .column_as(sum_bigint(chunk::Column::ChunkSize), "chunk_bytes")
+        .column_as(sum_bigint(chunk::Column::FileSize), "physical_bytes")
+        .filter(chunk::Column::State.eq(ChunkState::Valid))
+        .filter(chunk::Column::Id.in_subquery(chunks_used_by(in_cache.clone())));
+
+    let all_chunks = chunks
+        .clone()
+        .into_model::<ChunkTotals>()
+        .one(database)
+        .await
+        .map_err(ServerError::database_error)?;
+
+    let shared_chunks = chunks
+        .filter(chunk::Column::Id.in_subquery(chunks_used_by(in_other_caches)))
+        .into_model::<ChunkTotals>()
+        .one(database)
+        .await
+        .map_err(ServerError::database_error)?;
+
+    let uploaders = Object::find()
+        .select_only()
+        .column(object::Column::CreatedBy)
+        .column_as(object::Column::Id.into_expr().count(), "object_count")
+        .column_as(sum_bigint(nar::Column::NarSize), "nar_bytes")
+        .join(JoinType::InnerJoin, object::Relation::Nar.def())
+        .filter(object::Column::CacheId.eq(cache.id))
+        .group_by(object::Column::CreatedBy)
+        .order_by(sum_bigint(nar::Column::NarSize), Order::Desc)
+        .limit(TOP_UPLOADERS)
+        .into_model::<UploaderTotals>()
+        .all(database)
+        .await
+        .map_err(ServerError::database_error)?;
+
+    let (object_count, nar_bytes) = objects
+        .map(|totals| (totals.object_count, totals.nar_bytes.unwrap_or(0)))
+        .unwrap_or_default();
+    let physical_bytes = all_chunks
+        .as_ref()
+        .and_then(|totals| totals.physical_bytes)
+        .unwrap_or(0);
+    let shared_bytes = shared_chunks
+        .and_then(|totals| totals.physical_bytes)
+        .unwrap_or(0);
+
+    Ok(Json(CacheStats {
+        object_count: object_count as u64,
+        nar_bytes: nar_bytes as u64,
+        chunk_bytes: all_chunks
+            .and_then(|totals| totals.chunk_bytes)
+            .unwrap_or(0) as u64,
+        physical_bytes: physical_bytes as u64,
+        shared_bytes: shared_bytes as u64,
+        unique_bytes: (physical_bytes - shared_bytes) as u64,
+        top_uploaders: uploaders
+            .into_iter()
+            .map(|uploader| UploaderStats {
+                username: uploader.created_by,
+                object_count: uploader.object_count as u64,
+                nar_bytes: uploader.nar_bytes.unwrap_or(0) as u64,
+            })
+            .collect(),
+    }))
+}
+
+/// Returns a subquery selecting the chunks used by the objects matching a condition.
+fn chunks_used_by(condition: SimpleExpr) -> SelectStatement {
+    Query::select()
+        .column((ChunkRef, chunkref::Column::ChunkId))
+        .from(ChunkRef)
+        .inner_join(
+            Object,
+            Expr::col((Object, object::Column::NarId)).equals((ChunkRef, chunkref::Column::NarId)),
+        )
+        .and_where(condition)
+        .to_owned()
+}
+
+/// Returns the sum of a column as a BIGINT.
+///
+/// SUM() over BIGINT is NUMERIC in PostgreSQL.
+fn sum_bigint(column: impl ColumnTrait) -> SimpleExpr {
+    column.into_expr().sum().cast_as(Alias::new("BIGINT"))
+}
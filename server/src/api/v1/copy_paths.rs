@@ -0,0 +1,211 @@
+//! Server-side copying of objects between caches.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::extract::{Extension, Json};
+use chrono::Utc;
+use sea_orm::entity::prelude::*;
+use sea_orm::query::QuerySelect;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue::Set, TransactionTrait};
+use tracing::instrument;
+
+use super::delete_paths::QUERY_BATCH_SIZE;
+use super::get_closure::find_closure;
+use crate::audit;
+use crate::database::entity::audit_log::AuditAction;
+use crate::database::entity::nar::{self, Entity as Nar, NarModel, NarState};
+use crate::database::entity::object::{self, Entity as Object, ObjectModel};
+use crate::database::{BunkerDatabase, NarGuard};
+use crate::error::{ServerError, ServerResult};
+use crate::{RequestState, State};
+use bunker::api::v1::copy_paths::{CopyPathsRequest, CopyPathsResponse};
+use bunker::hash::Hash;
+use bunker::nix_store::StorePathHash;
+
+/// The maximum number of objects inserted in a single statement.
+const INSERT_BATCH_SIZE: usize = 100;
+
+/// Copies store paths from one cache to another.
+///
+/// - POST `/_api/v1/copy-paths`
+///
+/// The new objects reference the NARs of the source objects, which
+/// are locked like in deduplicated uploads so garbage collection
+/// can't reap them in the meantime.
+#[instrument(skip_all, fields(payload))]
+pub(crate) async fn copy_paths(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Json(payload): Json<CopyPathsRequest>,
+) -> ServerResult<Json<CopyPathsResponse>> {
+    let database = state.database().await?;
+    let source = req_state
+        .auth
+        .auth_cache(database, &payload.source, |cache, permission| {
+            permission.require_pull()?;
+            Ok(cache)
+        })
+        .await?;
+    let destination = req_state
+        .auth
+        .auth_cache(database, &payload.destination, |cache, permission| {
+            permission.require_push()?;
+            Ok(cache)
+        })
+        .await?;
+
+    let mut hashes: Vec<String> = payload
+        .store_path_hashes
+        .iter()
+        .map(|h| h.as_str().to_owned())
+        .collect();
+
+    if payload.closure {
+        let closure = find_closure(database, source.id, hashes, |object| {
+            req_state
+                .auth
+                .get_permission_for_object(&payload.source, &source, object)
+                .pull
+        })
+        .await?;
+        hashes = closure
+            .paths
+            .into_iter()
+            .map(|(hash, _)| hash)
+            .chain(closure.missing)
+            .collect();
+    }
+
+    let mut objects: Vec<(ObjectModel, NarModel)> = Vec::new();
+    let mut existing: HashSet<String> = HashSet::new();
+    for batch in hashes.chunks(QUERY_BATCH_SIZE) {
+        objects.extend(
+            Object::find()
+                .find_also_related(Nar)
+                .filter(object::Column::CacheId.eq(source.id))
+                .filter(object::Column::StorePathHash.is_in(batch.iter().map(String::as_str)))
+                .filter(nar::Column::State.eq(NarState::Valid))
+                .all(database)
+                .await
+                .map_err(ServerError::database_error)?
+                .into_iter()
+                .filter_map(|(object, nar)| Some((object, nar?))),
+        );
+
+        existing.extend(
+            Object::find()
+                .select_only()
+                .column(object::Column::StorePathHash)
+                .filter(object::Column::CacheId.eq(destination.id))
+                .filter(object::Column::StorePathHash.is_in(batch.iter().map(String::as_str)))
+                .into_tuple::<String>()
+                .all(database)
+                .await
+                .map_err(ServerError::database_error)?,
+        );
+    }
+
+    // Restricted objects can't be copied with public permissions
+    objects.retain(|(object, _)| {
+        req_state
+            .auth
+            .get_permission_for_object(&payload.source, &source, object)
+            .pull
+    });
+
+    let (already_copied, mut objects): (Vec<_>, Vec<_>) = objects
+        .into_iter()
+        .partition(|(object, _)| existing.contains(&object.store_path_hash));
+
+    // Lock the NARs until the copies reference them
+    let mut nars: HashMap<String, NarGuard> = HashMap::new();
+    for (_, nar) in &objects {
+        if nars.contains_key(&nar.nar_hash) {
+            continue;
+        }
+
+        let nar_hash = Hash::from_typed(&nar.nar_hash)?;
+        if let Some(guard) = database.find_and_lock_nar(&nar_hash).await? {
+            nars.insert(nar.nar_hash.to_owned(), guard);
+        }
+    }
+
+    // Objects whose NARs are gone can't be copied
+    objects.retain(|(_, nar)| nars.contains_key(&nar.nar_hash));
+
+    let found: HashSet<&str> = objects
+        .iter()
+        .chain(&already_copied)
+        .map(|(object, _)| object.store_path_hash.as_str())
+        .collect();
+
+    // Malformed references can't be in the cache either way
+    let mut missing_paths: Vec<StorePathHash> = hashes
+        .iter()
+        .filter(|hash| !found.contains(hash.as_str()))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter_map(|hash| StorePathHash::new(hash.to_owned()).ok())
+        .collect();
+    missing_paths.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+    let now = Utc::now();
+    let username = req_state.auth.username().map(str::to_string);
+
+    let txn = database
+        .begin()
+        .await
+        .map_err(ServerError::database_error)?;
+
+    for batch in objects.chunks(INSERT_BATCH_SIZE) {
+        Object::insert_many(batch.iter().map(|(object, nar)| object::ActiveModel {
+            cache_id: Set(destination.id),
+            nar_id: Set(nars[&nar.nar_hash].id),
+            store_path_hash: Set(object.store_path_hash.to_owned()),
+            store_path: Set(object.store_path.to_owned()),
+            references: Set(object.references.to_owned()),
+            system: Set(object.system.to_owned()),
+            deriver: Set(object.deriver.to_owned()),
+            sigs: Set(object.sigs.to_owned()),
+            ca: Set(object.ca.to_owned()),
+            created_at: Set(now),
+            last_accessed_at: Set(None),
+            created_by: Set(username.to_owned()),
+            is_restricted: Set(object.is_restricted),
+            ..Default::default()
+        }))
+        .on_conflict(
+            OnConflict::columns([object::Column::CacheId, object::Column::StorePathHash])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&txn)
+        .await
+        .map_err(ServerError::database_error)?;
+    }
+
+    txn.commit().await.map_err(ServerError::database_error)?;
+
+    // Ensure they're not unlocked earlier
+    drop(nars);
+
+    let copied_paths: Vec<String> = objects
+        .into_iter()
+        .map(|(object, _)| object.store_path)
+        .collect();
+
+    audit::record_many(
+        &state,
+        &req_state,
+        AuditAction::Copy,
+        Some(&payload.destination),
+        &copied_paths,
+    )
+    .await;
+
+    Ok(Json(CopyPathsResponse {
+        copied_paths,
+        missing_paths,
+    }))
+}
@@ -0,0 +1,161 @@
+//! Object deletion endpoints.
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashSet;
+
+use axum::extract::{Extension, Json};
+use sea_orm::entity::prelude::*;
+use sea_orm::{DatabaseConnection, QuerySelect};
+use tracing::instrument;
+
+use super::get_closure::find_closure;
+use crate::audit;
+use crate::database::entity::audit_log::AuditAction;
+use crate::database::entity::object::{self, Entity as Object};
+use crate::error::{ErrorKind, ServerError, ServerResult};
+use crate::gc::find_protected_hashes;
+use crate::{RequestState, State};
+use bunker::api::v1::delete_paths::{DeletePathsRequest, DeletePathsResponse};
+
+/// The maximum number of store path hashes in a single query.
+pub(super) const QUERY_BATCH_SIZE: usize = 1000;
+
+/// Deletes store paths from a cache.
+///
+/// Objects in the closures of pinned paths are never deleted. When
+/// deleting closures, members that objects staying in the cache
+/// still depend on are kept as well.
+///
+/// - POST `/_api/v1/delete-paths`
+#[instrument(skip_all, fields(payload))]
+pub(crate) async fn delete_paths(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Json(payload): Json<DeletePathsRequest>,
+) -> ServerResult<Json<DeletePathsResponse>> {
+    let database = state.database().await?;
+    let cache = req_state
+        .auth
+        .auth_cache(database, &payload.cache, |cache, permission| {
+            permission.require_delete()?;
+            Ok(cache)
+        })
+        .await?;
+
+    let requested: Vec<String> = payload
+        .store_path_hashes
+        .iter()
+        .map(|h| h.as_str().to_owned())
+        .collect();
+
+    let candidates = if payload.closure {
+        find_closure(database, cache.id, requested.clone(), |_| true)
+            .await?
+            .paths
+    } else {
+        find_store_paths(database, cache.id, &requested).await?
+    };
+
+    let deleting: HashSet<String> = candidates.iter().map(|(hash, _)| hash.clone()).collect();
+    let (pinned, referenced) = find_protected_hashes(database, cache.id, &deleting)
+        .await
+        .map_err(ErrorKind::DatabaseError)?;
+
+    let requested: HashSet<String> = requested.into_iter().collect();
+    let mut hashes = Vec::new();
+    let mut pinned_paths = Vec::new();
+    let mut retained_paths = Vec::new();
+
+    for (hash, store_path) in candidates {
+        if pinned.contains(&hash) {
+            pinned_paths.push(store_path);
+        } else if !requested.contains(&hash) && referenced.contains(&hash) {
+            retained_paths.push(store_path);
+        } else {
+            hashes.push(hash);
+        }
+    }
+
+    let deleted_paths = delete_objects(database, cache.id, &hashes).await?;
+
+    audit::record_many(
+        &state,
+        &req_state,
+        AuditAction::Delete,
+        Some(&payload.cache),
+        &deleted_paths,
+    )
+    .await;
+
+    Ok(Json(DeletePathsResponse {
+        deleted_paths,
+        pinned_paths,
+        retained_paths,
+    }))
+}
+
+/// Returns the hashes and store paths of the objects in a cache.
+///
+/// Hashes that aren't in the cache are omitted.
+async fn find_store_paths(
+    database: &DatabaseConnection,
+    cache_id: i64,
+    store_path_hashes: &[String],
+) -> ServerResult<Vec<(String, String)>> {
+    let mut paths = Vec::new();
+
+    for batch in store_path_hashes.chunks(QUERY_BATCH_SIZE) {
+        let objects: Vec<(String, String)> = Object::find()
+            .select_only()
+            .column(object::Column::StorePathHash)
+            .column(object::Column::StorePath)
+            .filter(object::Column::CacheId.eq(cache_id))
+            .filter(object::Column::StorePathHash.is_in(batch.iter().map(String::as_str)))
+            .into_tuple()
+            .all(database)
+            .await
+            .map_err(ServerError::database_error)?;
+
+        paths.extend(objects);
+    }
+
+    Ok(paths)
+}
+
+/// Deletes objects from a cache, returning the store paths that were deleted.
+pub(super) async fn delete_objects(
+    database: &DatabaseConnection,
+    cache_id: i64,
+    store_path_hashes: &[String],
+) -> ServerResult<Vec<String>> {
+    let mut deleted_paths = Vec::new();
+
+    for batch in store_path_hashes.chunks(QUERY_BATCH_SIZE) {
+        let objects: Vec<(i64, String)> = Object::find()
+            .select_only()
+            .column(object::Column::Id)
+            .column(object::Column::StorePath)
+            .filter(object::Column::CacheId.eq(cache_id))
+            .filter(object::Column::StorePathHash.is_in(batch.iter().map(String::as_str)))
+            .into_tuple()
+            .all(database)
+            .await
+            .map_err(ServerError::database_error)?;
+
+        if objects.is_empty() {
+            continue;
+        }
+
+        Object::delete_many()
+            .filter(object::Column::Id.is_in(objects.iter().map(|(id, _)| *id)))
+            .exec(database)
+            .await
+            .map_err(ServerError::database_error)?;
+
+        deleted_paths.extend(objects.into_iter().map(|(_, store_path)| store_path));
+    }
+
+    Ok(deleted_paths)
+}
@@ -0,0 +1,125 @@
+use chrono::Utc;
+use sea_orm::ActiveValue::Set;
+
+use super::*;
+use crate::database::entity::pin::{self, Entity as Pin};
+use crate::database::BunkerDatabase;
+use crate::testing::{self, TestState};
+use bunker::cache::{CacheName, CacheNamePattern};
+use bunker::nix_store::StorePathHash;
+
+fn store_path(id: u32) -> String {
+    format!("/nix/store/{:0>32}-path-{}", id, id)
+}
+
+fn base_name(id: u32) -> String {
+    format!("{:0>32}-path-{}", id, id)
+}
+
+fn hash(id: u32) -> StorePathHash {
+    StorePathHash::new(format!("{:0>32}", id)).unwrap()
+}
+
+/// Returns a state with a cache of objects referencing each other.
+///
+/// Each object is a pair of its ID and the IDs it references.
+async fn setup(objects: &[(u32, &[u32])]) -> (TestState, RequestState) {
+    let state = TestState::new().await;
+    let cache = state.create_cache("test").await;
+
+    for (id, references) in objects {
+        let references: Vec<String> = references.iter().copied().map(base_name).collect();
+        let references: Vec<&str> = references.iter().map(String::as_str).collect();
+
+        state
+            .push(&cache, &store_path(*id), &references, &id.to_be_bytes())
+            .await;
+    }
+
+    let mut token = testing::token("alice");
+    token
+        .get_or_insert_permission_mut(CacheNamePattern::new("test".to_string()).unwrap())
+        .delete = true;
+    let req_state = state.request_state(Some(token));
+
+    (state, req_state)
+}
+
+async fn pin(state: &TestState, id: u32) {
+    let cache = state
+        .database()
+        .await
+        .unwrap()
+        .find_cache(&CacheName::new("test".to_string()).unwrap())
+        .await
+        .unwrap();
+
+    Pin::insert(pin::ActiveModel {
+        cache_id: Set(cache.id),
+        store_path_hash: Set(hash(id).as_str().to_string()),
+        created_at: Set(Utc::now()),
+        ..Default::default()
+    })
+    .exec(state.database().await.unwrap())
+    .await
+    .unwrap();
+}
+
+async fn delete(
+    state: &TestState,
+    req_state: &RequestState,
+    ids: &[u32],
+    closure: bool,
+) -> DeletePathsResponse {
+    let res = delete_paths(
+        state.extension(),
+        Extension(req_state.clone()),
+        Json(DeletePathsRequest {
+            cache: CacheName::new("test".to_string()).unwrap(),
+            store_path_hashes: ids.iter().copied().map(hash).collect(),
+            closure,
+        }),
+    )
+    .await
+    .unwrap();
+
+    res.0
+}
+
+fn sorted(mut paths: Vec<String>) -> Vec<String> {
+    paths.sort();
+    paths
+}
+
+#[tokio::test]
+async fn test_delete_closure_keeps_shared_paths() {
+    let (state, req_state) = setup(&[(1, &[1, 2, 3]), (2, &[3]), (3, &[]), (4, &[3])]).await;
+
+    // 3 is still referenced by 4
+    let res = delete(&state, &req_state, &[1], true).await;
+    assert_eq!(
+        vec![store_path(1), store_path(2)],
+        sorted(res.deleted_paths)
+    );
+    assert_eq!(vec![store_path(3)], res.retained_paths);
+    assert!(res.pinned_paths.is_empty());
+
+    // Explicitly requested paths are deleted even if referenced
+    let res = delete(&state, &req_state, &[3], false).await;
+    assert_eq!(vec![store_path(3)], res.deleted_paths);
+    assert!(res.retained_paths.is_empty());
+}
+
+#[tokio::test]
+async fn test_delete_skips_pinned_closures() {
+    let (state, req_state) = setup(&[(1, &[2]), (2, &[]), (3, &[2])]).await;
+    pin(&state, 1).await;
+
+    let res = delete(&state, &req_state, &[2], false).await;
+    assert!(res.deleted_paths.is_empty());
+    assert_eq!(vec![store_path(2)], res.pinned_paths);
+
+    let res = delete(&state, &req_state, &[3], true).await;
+    assert_eq!(vec![store_path(3)], res.deleted_paths);
+    assert_eq!(vec![store_path(2)], res.pinned_paths);
+}
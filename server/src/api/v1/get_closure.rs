@@ -0,0 +1,145 @@
+//! Closure query endpoint.
+
+use std::collections::HashSet;
+
+use axum::extract::{Extension, Json};
+use sea_orm::entity::prelude::*;
+use sea_orm::DatabaseConnection;
+use tracing::instrument;
+
+use super::delete_paths::QUERY_BATCH_SIZE;
+use crate::database::entity::object::{self, Entity as Object, ObjectModel};
+use crate::error::{ServerError, ServerResult};
+use crate::{RequestState, State};
+use bunker::api::v1::get_closure::{GetClosureRequest, GetClosureResponse};
+use bunker::nix_store::StorePathHash;
+
+/// The closure of store paths within a cache.
+pub(super) struct Closure {
+    /// The hashes and store paths of the members in the cache.
+    pub(super) paths: Vec<(String, String)>,
+
+    /// The hashes of the members that aren't in the cache.
+    pub(super) missing: Vec<String>,
+}
+
+/// Returns the closure of store paths in a cache.
+///
+/// Restricted paths the token can't pull are reported as missing.
+///
+/// - POST `/_api/v1/get-closure`
+#[instrument(skip_all, fields(payload))]
+pub(crate) async fn get_closure(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Json(payload): Json<GetClosureRequest>,
+) -> ServerResult<Json<GetClosureResponse>> {
+    let database = state.database().await?;
+    let cache = req_state
+        .auth
+        .auth_cache(database, &payload.cache, |cache, permission| {
+            permission.require_pull()?;
+            Ok(cache)
+        })
+        .await?;
+
+    let roots = payload
+        .store_path_hashes
+        .iter()
+        .map(|h| h.as_str().to_owned())
+        .collect();
+
+    let closure = find_closure(database, cache.id, roots, |object| {
+        req_state
+            .auth
+            .get_permission_for_object(&payload.cache, &cache, object)
+            .pull
+    })
+    .await?;
+
+    let mut paths: Vec<String> = closure
+        .paths
+        .into_iter()
+        .map(|(_, store_path)| store_path)
+        .collect();
+    paths.sort();
+
+    // Malformed references can't be in the cache either way
+    let mut missing_paths: Vec<StorePathHash> = closure
+        .missing
+        .into_iter()
+        .filter_map(|hash| StorePathHash::new(hash).ok())
+        .collect();
+    missing_paths.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+    Ok(Json(GetClosureResponse {
+        paths,
+        missing_paths,
+    }))
+}
+
+/// Finds the closure of store paths within a cache.
+///
+/// The references of paths that aren't in the cache are unknown,
+/// so they are not followed. Objects for which `is_visible` returns
+/// false are treated as if they weren't in the cache.
+pub(super) async fn find_closure(
+    database: &DatabaseConnection,
+    cache_id: i64,
+    roots: Vec<String>,
+    is_visible: impl Fn(&ObjectModel) -> bool,
+) -> ServerResult<Closure> {
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut closure = Closure {
+        paths: Vec::new(),
+        missing: Vec::new(),
+    };
+    let mut queue = roots;
+
+    while !queue.is_empty() {
+        let pending: Vec<String> = queue
+            .drain(..)
+            .filter(|hash| visited.insert(hash.to_owned()))
+            .collect();
+
+        for batch in pending.chunks(QUERY_BATCH_SIZE) {
+            let objects: Vec<ObjectModel> = Object::find()
+                .filter(object::Column::CacheId.eq(cache_id))
+                .filter(object::Column::StorePathHash.is_in(batch.iter().map(String::as_str)))
+                .all(database)
+                .await
+                .map_err(ServerError::database_error)?
+                .into_iter()
+                .filter(|object| is_visible(object))
+                .collect();
+
+            let found: HashSet<&str> = objects
+                .iter()
+                .map(|object| object.store_path_hash.as_str())
+                .collect();
+            closure.missing.extend(
+                batch
+                    .iter()
+                    .filter(|hash| !found.contains(hash.as_str()))
+                    .cloned(),
+            );
+
+            for object in objects {
+                for reference in object.references.0 {
+                    // References are store path base names
+                    let reference_hash = reference.get(..32).unwrap_or(&reference);
+
+                    if !visited.contains(reference_hash) {
+                        queue.push(reference_hash.to_owned());
+                    }
+                }
+
+                closure
+                    .paths
+                    .push((object.store_path_hash, object.store_path));
+            }
+        }
+    }
+
+    Ok(closure)
+}
@@ -1,10 +1,20 @@
+mod audit;
 mod cache_config;
-mod get_missing_paths;
-mod upload_path;
+mod cache_stats;
+mod copy_paths;
+mod delete_paths;
+mod get_closure;
+pub(crate) mod get_missing_paths;
+mod objects;
+mod pin;
+mod token_info;
+mod tokens;
+mod upload_build_log;
+pub(crate) mod upload_path;
 
 use axum::{
-    Router,
     routing::{delete, get, patch, post, put},
+    Router,
 };
 
 pub(crate) fn get_router() -> Router {
@@ -13,7 +23,14 @@ pub(crate) fn get_router() -> Router {
             "/_api/v1/get-missing-paths",
             post(get_missing_paths::get_missing_paths),
         )
+        .route("/_api/v1/get-closure", post(get_closure::get_closure))
         .route("/_api/v1/upload-path", put(upload_path::upload_path))
+        .route("/_api/v1/copy-paths", post(copy_paths::copy_paths))
+        .route("/_api/v1/delete-paths", post(delete_paths::delete_paths))
+        .route(
+            "/_api/v1/upload-build-log/:cache/:drv",
+            put(upload_build_log::upload_build_log),
+        )
         .route(
             "/:cache/bunker-cache-info",
             get(cache_config::get_cache_config),
@@ -34,4 +51,22 @@ pub(crate) fn get_router() -> Router {
             "/_api/v1/cache-config/:cache",
             delete(cache_config::destroy_cache),
         )
+        .route(
+            "/_api/v1/cache-stats/:cache",
+            get(cache_stats::get_cache_stats),
+        )
+        .route("/_api/v1/objects/:cache", get(objects::list_objects))
+        .route(
+            "/_api/v1/objects/:cache/:store_path_hash",
+            delete(objects::delete_object),
+        )
+        .route("/_api/v1/audit", get(audit::get_audit_log))
+        .route("/_api/v1/token-info", get(token_info::get_token_info))
+        .route("/_api/v1/tokens", post(tokens::create_token))
+        .route("/_api/v1/pins/:cache", get(pin::list_pins))
+        .route("/_api/v1/pins/:cache", post(pin::create_pin))
+        .route(
+            "/_api/v1/pins/:cache/:store_path_hash",
+            delete(pin::delete_pin),
+        )
 }
@@ -0,0 +1,217 @@
+//! Object listing and deletion endpoints.
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+use axum::extract::{Extension, Json, Path, Query};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sea_orm::sea_query::{Expr, LikeExpr};
+use sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use tracing::instrument;
+
+use super::delete_paths::delete_objects;
+use crate::audit;
+use crate::database::entity::audit_log::AuditAction;
+use crate::database::entity::nar::Entity as Nar;
+use crate::database::entity::object::{self, Entity as Object};
+use crate::error::{ErrorKind, ServerError, ServerResult};
+use crate::gc::find_protected_hashes;
+use crate::{RequestState, State};
+use bunker::api::v1::objects::{ListObjectsRequest, ListObjectsResponse, ObjectInfo};
+use bunker::cache::CacheName;
+use bunker::nix_store::StorePathHash;
+
+/// The number of objects returned if the client doesn't ask for a limit.
+const DEFAULT_LIMIT: u64 = 100;
+
+/// The maximum number of objects returned in a page.
+const MAX_LIMIT: u64 = 1000;
+
+/// Lists the objects in a cache.
+///
+/// Restricted objects the token can't pull are left out, so pages
+/// can have fewer objects than the limit even if more follow.
+///
+/// - GET `/_api/v1/objects/:cache`
+#[instrument(skip_all, fields(cache_name, query))]
+pub(crate) async fn list_objects(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Path(cache_name): Path<CacheName>,
+    Query(query): Query<ListObjectsRequest>,
+) -> ServerResult<Json<ListObjectsResponse>> {
+    let database = state.database().await?;
+    let cache = req_state
+        .auth
+        .auth_cache(database, &cache_name, |cache, permission| {
+            permission.require_pull()?;
+            Ok(cache)
+        })
+        .await?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let mut condition = Condition::all().add(object::Column::CacheId.eq(cache.id));
+
+    if let Some(after) = query.after {
+        condition = condition.add(object::Column::Id.gt(after));
+    }
+    if let Some(name) = query.name {
+        let pattern = LikeExpr::new(format!("%{}%", escape_like(&name))).escape('\\');
+        condition = condition.add(Expr::col((Object, object::Column::StorePath)).like(pattern));
+    }
+    if let Some(system) = query.system {
+        condition = condition.add(object::Column::System.eq(system));
+    }
+    if let Some(created_by) = query.created_by {
+        condition = condition.add(object::Column::CreatedBy.eq(created_by));
+    }
+    if let Some(created_after) = query.created_after {
+        condition = condition.add(object::Column::CreatedAt.gte(parse_timestamp(&created_after)?));
+    }
+    if let Some(created_before) = query.created_before {
+        condition = condition.add(object::Column::CreatedAt.lt(parse_timestamp(&created_before)?));
+    }
+    if let Some(accessed_after) = query.accessed_after {
+        condition =
+            condition.add(object::Column::LastAccessedAt.gte(parse_timestamp(&accessed_after)?));
+    }
+    if let Some(accessed_before) = query.accessed_before {
+        condition = condition.add(
+            Condition::any()
+                .add(object::Column::LastAccessedAt.lt(parse_timestamp(&accessed_before)?))
+                .add(object::Column::LastAccessedAt.is_null()),
+        );
+    }
+
+    // Fetch one more row to find out whether there is a next page
+    let mut rows = Object::find()
+        .find_also_related(Nar)
+        .filter(condition)
+        .order_by_asc(object::Column::Id)
+        .limit(limit + 1)
+        .all(database)
+        .await
+        .map_err(ServerError::database_error)?;
+
+    let next_cursor = if rows.len() as u64 > limit {
+        rows.truncate(limit as usize);
+        rows.last().map(|(object, _)| object.id)
+    } else {
+        None
+    };
+
+    #[allow(unsafe_code)]
+    let objects = rows
+        .into_iter()
+        .filter(|(object, _)| {
+            req_state
+                .auth
+                .get_permission_for_object(&cache_name, &cache, object)
+                .pull
+        })
+        .map(|(object, nar)| {
+            let nar = nar.ok_or_else(|| {
+                ErrorKind::DatabaseError(anyhow!("Object {} has no NAR", object.id))
+            })?;
+
+            Ok(ObjectInfo {
+                // Hashes are validated on insertion
+                store_path_hash: unsafe { StorePathHash::new_unchecked(object.store_path_hash) },
+                store_path: object.store_path,
+                system: object.system,
+                deriver: object.deriver,
+                references: object.references.0,
+                nar_hash: nar.nar_hash,
+                nar_size: nar
+                    .nar_size
+                    .try_into()
+                    .map_err(ServerError::database_error)?,
+                created_at: object.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                last_accessed_at: object
+                    .last_accessed_at
+                    .map(|accessed_at| accessed_at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                created_by: object.created_by,
+            })
+        })
+        .collect::<ServerResult<Vec<_>>>()?;
+
+    Ok(Json(ListObjectsResponse {
+        objects,
+        next_cursor,
+    }))
+}
+
+/// Deletes an object from a cache.
+///
+/// Objects in the closures of pinned paths can't be deleted.
+///
+/// - DELETE `/_api/v1/objects/:cache/:store_path_hash`
+#[instrument(skip_all, fields(cache_name, store_path_hash))]
+pub(crate) async fn delete_object(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Path((cache_name, store_path_hash)): Path<(CacheName, StorePathHash)>,
+) -> ServerResult<()> {
+    let database = state.database().await?;
+    let cache = req_state
+        .auth
+        .auth_cache(database, &cache_name, |cache, permission| {
+            permission.require_delete()?;
+            Ok(cache)
+        })
+        .await?;
+
+    let hashes = [store_path_hash.as_str().to_owned()];
+
+    let (pinned, _) = find_protected_hashes(database, cache.id, &HashSet::from(hashes.clone()))
+        .await
+        .map_err(ErrorKind::DatabaseError)?;
+    if pinned.contains(store_path_hash.as_str()) {
+        return Err(ErrorKind::RequestError(anyhow!(
+            "{} is in the closure of a pinned path",
+            store_path_hash.as_str()
+        ))
+        .into());
+    }
+
+    let deleted_paths = delete_objects(database, cache.id, &hashes).await?;
+
+    if deleted_paths.is_empty() {
+        return Err(ErrorKind::NoSuchObject.into());
+    }
+
+    audit::record_many(
+        &state,
+        &req_state,
+        AuditAction::Delete,
+        Some(&cache_name),
+        &deleted_paths,
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Escapes the wildcards in a LIKE pattern with backslashes.
+fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn parse_timestamp(timestamp: &str) -> ServerResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|e| {
+            ErrorKind::RequestError(anyhow!("Invalid timestamp \"{}\": {}", timestamp, e)).into()
+        })
+}
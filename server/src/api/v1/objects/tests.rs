@@ -0,0 +1,344 @@
+use chrono::TimeZone;
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use super::*;
+use crate::database::entity::cache::{self, Entity as Cache};
+use crate::testing::{self, TestState};
+use bunker::cache::CacheNamePattern;
+
+const FOO_BAR: &str = "/nix/store/00000000000000000000000000000001-foo_bar";
+const FOOXBAR: &str = "/nix/store/00000000000000000000000000000002-fooxbar";
+const BAZ: &str = "/nix/store/00000000000000000000000000000003-baz";
+
+fn timestamp(month: u32, day: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, month, day, 0, 0, 0).unwrap()
+}
+
+async fn set_column(
+    state: &TestState,
+    store_path: &str,
+    column: object::Column,
+    value: SimpleExpr,
+) {
+    Object::update_many()
+        .col_expr(column, value)
+        .filter(object::Column::StorePath.eq(store_path))
+        .exec(state.database().await.unwrap())
+        .await
+        .unwrap();
+}
+
+/// Returns a state with three objects and a request state that can pull them.
+async fn setup() -> (TestState, RequestState) {
+    let state = TestState::new().await;
+    let cache = state.create_cache("test").await;
+
+    let objects = [
+        (FOO_BAR, "x86_64-linux", "alice", timestamp(1, 1)),
+        (FOOXBAR, "aarch64-linux", "bob", timestamp(2, 1)),
+        (BAZ, "x86_64-linux", "alice", timestamp(3, 1)),
+    ];
+
+    for (store_path, system, created_by, created_at) in objects {
+        state
+            .push(&cache, store_path, &[], store_path.as_bytes())
+            .await;
+
+        set_column(
+            &state,
+            store_path,
+            object::Column::System,
+            Expr::value(system),
+        )
+        .await;
+        set_column(
+            &state,
+            store_path,
+            object::Column::CreatedBy,
+            Expr::value(created_by),
+        )
+        .await;
+        set_column(
+            &state,
+            store_path,
+            object::Column::CreatedAt,
+            Expr::value(created_at),
+        )
+        .await;
+    }
+
+    set_column(
+        &state,
+        BAZ,
+        object::Column::LastAccessedAt,
+        Expr::value(timestamp(3, 2)),
+    )
+    .await;
+
+    let mut token = testing::token("alice");
+    token
+        .get_or_insert_permission_mut(CacheNamePattern::new("test".to_string()).unwrap())
+        .pull = true;
+    let req_state = state.request_state(Some(token));
+
+    (state, req_state)
+}
+
+async fn list(
+    state: &TestState,
+    req_state: &RequestState,
+    query: ListObjectsRequest,
+) -> ServerResult<ListObjectsResponse> {
+    let res = list_objects(
+        state.extension(),
+        Extension(req_state.clone()),
+        Path(CacheName::new("test".to_string()).unwrap()),
+        Query(query),
+    )
+    .await?;
+
+    Ok(res.0)
+}
+
+async fn list_paths(
+    state: &TestState,
+    req_state: &RequestState,
+    query: ListObjectsRequest,
+) -> Vec<String> {
+    list(state, req_state, query)
+        .await
+        .unwrap()
+        .objects
+        .into_iter()
+        .map(|object| object.store_path)
+        .collect()
+}
+
+#[test]
+fn test_escape_like() {
+    assert_eq!("foo", escape_like("foo"));
+    assert_eq!("foo\\_bar", escape_like("foo_bar"));
+    assert_eq!("100\\%", escape_like("100%"));
+    assert_eq!("a\\\\b", escape_like("a\\b"));
+}
+
+#[tokio::test]
+async fn test_list_objects_filters() {
+    let (state, req_state) = setup().await;
+
+    let query = |f: fn(&mut ListObjectsRequest)| {
+        let mut query = ListObjectsRequest::default();
+        f(&mut query);
+        query
+    };
+
+    assert_eq!(
+        vec![FOO_BAR, FOOXBAR, BAZ],
+        list_paths(&state, &req_state, ListObjectsRequest::default()).await
+    );
+
+    // Wildcards in names are matched literally
+    assert_eq!(
+        vec![FOO_BAR, FOOXBAR],
+        list_paths(
+            &state,
+            &req_state,
+            query(|q| q.name = Some("foo".to_string()))
+        )
+        .await
+    );
+    assert_eq!(
+        vec![FOO_BAR],
+        list_paths(
+            &state,
+            &req_state,
+            query(|q| q.name = Some("foo_bar".to_string()))
+        )
+        .await
+    );
+    assert!(list_paths(
+        &state,
+        &req_state,
+        query(|q| q.name = Some("%".to_string()))
+    )
+    .await
+    .is_empty());
+
+    assert_eq!(
+        vec![FOO_BAR, BAZ],
+        list_paths(
+            &state,
+            &req_state,
+            query(|q| q.system = Some("x86_64-linux".to_string()))
+        )
+        .await
+    );
+    assert_eq!(
+        vec![FOOXBAR],
+        list_paths(
+            &state,
+            &req_state,
+            query(|q| q.created_by = Some("bob".to_string()))
+        )
+        .await
+    );
+
+    assert_eq!(
+        vec![FOOXBAR, BAZ],
+        list_paths(
+            &state,
+            &req_state,
+            query(|q| q.created_after = Some("2026-02-01T00:00:00Z".to_string()))
+        )
+        .await
+    );
+    assert_eq!(
+        vec![FOO_BAR],
+        list_paths(
+            &state,
+            &req_state,
+            query(|q| q.created_before = Some("2026-02-01T00:00:00Z".to_string()))
+        )
+        .await
+    );
+
+    // Objects that have never been accessed count as accessed before any time
+    assert_eq!(
+        vec![BAZ],
+        list_paths(
+            &state,
+            &req_state,
+            query(|q| q.accessed_after = Some("2026-03-01T00:00:00Z".to_string()))
+        )
+        .await
+    );
+    assert_eq!(
+        vec![FOO_BAR, FOOXBAR],
+        list_paths(
+            &state,
+            &req_state,
+            query(|q| q.accessed_before = Some("2026-03-01T00:00:00Z".to_string()))
+        )
+        .await
+    );
+
+    assert!(list(
+        &state,
+        &req_state,
+        query(|q| q.created_after = Some("yesterday".to_string()))
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_list_objects_pagination() {
+    let (state, req_state) = setup().await;
+
+    let first = list(
+        &state,
+        &req_state,
+        ListObjectsRequest {
+            limit: Some(2),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let paths: Vec<_> = first
+        .objects
+        .iter()
+        .map(|o| o.store_path.as_str())
+        .collect();
+    assert_eq!(vec![FOO_BAR, FOOXBAR], paths);
+    assert!(first.next_cursor.is_some());
+
+    let second = list(
+        &state,
+        &req_state,
+        ListObjectsRequest {
+            limit: Some(2),
+            after: first.next_cursor,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let paths: Vec<_> = second
+        .objects
+        .iter()
+        .map(|o| o.store_path.as_str())
+        .collect();
+    assert_eq!(vec![BAZ], paths);
+    assert_eq!(None, second.next_cursor);
+
+    // Filters apply across pages
+    let filtered = list(
+        &state,
+        &req_state,
+        ListObjectsRequest {
+            system: Some("x86_64-linux".to_string()),
+            limit: Some(1),
+            after: first.next_cursor,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let paths: Vec<_> = filtered
+        .objects
+        .iter()
+        .map(|o| o.store_path.as_str())
+        .collect();
+    assert_eq!(vec![BAZ], paths);
+    assert_eq!(None, filtered.next_cursor);
+}
+
+#[tokio::test]
+async fn test_list_objects_requires_pull() {
+    let (state, _) = setup().await;
+
+    let req_state = state.request_state(Some(testing::token("mallory")));
+    assert!(list(&state, &req_state, ListObjectsRequest::default())
+        .await
+        .is_err());
+
+    let req_state = state.request_state(None);
+    assert!(list(&state, &req_state, ListObjectsRequest::default())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_list_objects_hides_restricted_objects() {
+    let (state, req_state) = setup().await;
+
+    Cache::update_many()
+        .col_expr(cache::Column::IsPublic, Expr::value(true))
+        .exec(state.database().await.unwrap())
+        .await
+        .unwrap();
+    set_column(
+        &state,
+        FOOXBAR,
+        object::Column::IsRestricted,
+        Expr::value(true),
+    )
+    .await;
+
+    // Public permissions don't cover restricted objects
+    let anonymous = state.request_state(None);
+    assert_eq!(
+        vec![FOO_BAR, BAZ],
+        list_paths(&state, &anonymous, ListObjectsRequest::default()).await
+    );
+
+    assert_eq!(
+        vec![FOO_BAR, FOOXBAR, BAZ],
+        list_paths(&state, &req_state, ListObjectsRequest::default()).await
+    );
+}
@@ -0,0 +1,197 @@
+//! Pin management endpoints.
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use axum::extract::{Extension, Json, Path};
+use chrono::{Duration as ChronoDuration, SecondsFormat, Utc};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use tracing::instrument;
+
+use crate::audit;
+use crate::database::entity::audit_log::AuditAction;
+use crate::database::entity::object::{self, Entity as Object};
+use crate::database::entity::pin::{self, Entity as Pin};
+use crate::error::{ErrorKind, ServerError, ServerResult};
+use crate::{RequestState, State};
+use bunker::api::v1::pin::{CreatePinRequest, ListPinsResponse, PinInfo};
+use bunker::cache::CacheName;
+use bunker::nix_store::StorePathHash;
+
+/// Lists the pins in a cache.
+///
+/// - GET `/_api/v1/pins/:cache`
+///
+/// Expired pins that haven't been reaped yet are omitted.
+#[instrument(skip_all, fields(cache_name))]
+pub(crate) async fn list_pins(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Path(cache_name): Path<CacheName>,
+) -> ServerResult<Json<ListPinsResponse>> {
+    let database = state.database().await?;
+    let cache = req_state
+        .auth
+        .auth_cache(database, &cache_name, |cache, permission| {
+            permission.require_pin()?;
+            Ok(cache)
+        })
+        .await?;
+
+    let now = Utc::now();
+    let pins: Vec<pin::Model> = Pin::find()
+        .filter(pin::Column::CacheId.eq(cache.id))
+        .order_by_asc(pin::Column::CreatedAt)
+        .all(database)
+        .await
+        .map_err(ServerError::database_error)?
+        .into_iter()
+        .filter(|pin| !pin.is_expired(now))
+        .collect();
+
+    // Pins may refer to paths that haven't been pushed yet
+    let store_paths: HashMap<String, String> = Object::find()
+        .select_only()
+        .column(object::Column::StorePathHash)
+        .column(object::Column::StorePath)
+        .filter(object::Column::CacheId.eq(cache.id))
+        .filter(
+            object::Column::StorePathHash
+                .is_in(pins.iter().map(|pin| pin.store_path_hash.as_str())),
+        )
+        .into_tuple::<(String, String)>()
+        .all(database)
+        .await
+        .map_err(ServerError::database_error)?
+        .into_iter()
+        .collect();
+
+    #[allow(unsafe_code)]
+    let pins = pins
+        .into_iter()
+        .map(|pin| PinInfo {
+            store_path: store_paths.get(&pin.store_path_hash).cloned(),
+            // Hashes are validated on insertion
+            store_path_hash: unsafe { StorePathHash::new_unchecked(pin.store_path_hash) },
+            label: pin.label,
+            created_at: pin.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            expires_at: pin
+                .expires_at
+                .map(|expires_at| expires_at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            created_by: pin.created_by,
+        })
+        .collect();
+
+    Ok(Json(ListPinsResponse { pins }))
+}
+
+/// Pins a store path.
+///
+/// - POST `/_api/v1/pins/:cache`
+///
+/// An existing pin of the same path is replaced.
+#[instrument(skip_all, fields(cache_name, payload))]
+pub(crate) async fn create_pin(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Path(cache_name): Path<CacheName>,
+    Json(payload): Json<CreatePinRequest>,
+) -> ServerResult<()> {
+    let database = state.database().await?;
+    let cache = req_state
+        .auth
+        .auth_cache(database, &cache_name, |cache, permission| {
+            permission.require_pin()?;
+            Ok(cache)
+        })
+        .await?;
+
+    let now = Utc::now();
+    let expires_at = payload
+        .expires_in
+        .map(|expires_in| {
+            i64::try_from(expires_in)
+                .ok()
+                .and_then(ChronoDuration::try_seconds)
+                .and_then(|duration| now.checked_add_signed(duration))
+                .ok_or_else(|| ErrorKind::RequestError(anyhow!("Expiry timestamp overflowed")))
+        })
+        .transpose()?;
+
+    Pin::insert(pin::ActiveModel {
+        cache_id: Set(cache.id),
+        store_path_hash: Set(payload.store_path_hash.to_string()),
+        label: Set(payload.label),
+        expires_at: Set(expires_at),
+        created_at: Set(now),
+        created_by: Set(req_state.auth.username().map(str::to_string)),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::columns([pin::Column::CacheId, pin::Column::StorePathHash])
+            .update_columns([
+                pin::Column::Label,
+                pin::Column::ExpiresAt,
+                pin::Column::CreatedAt,
+                pin::Column::CreatedBy,
+            ])
+            .to_owned(),
+    )
+    .exec_without_returning(database)
+    .await
+    .map_err(ServerError::database_error)?;
+
+    audit::record(
+        &state,
+        &req_state,
+        AuditAction::Pin,
+        Some(&cache_name),
+        Some(payload.store_path_hash.to_string()),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Unpins a store path.
+///
+/// - DELETE `/_api/v1/pins/:cache/:store_path_hash`
+#[instrument(skip_all, fields(cache_name, store_path_hash))]
+pub(crate) async fn delete_pin(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Path((cache_name, store_path_hash)): Path<(CacheName, StorePathHash)>,
+) -> ServerResult<()> {
+    let database = state.database().await?;
+    let cache = req_state
+        .auth
+        .auth_cache(database, &cache_name, |cache, permission| {
+            permission.require_pin()?;
+            Ok(cache)
+        })
+        .await?;
+
+    let deletion = Pin::delete_many()
+        .filter(pin::Column::CacheId.eq(cache.id))
+        .filter(pin::Column::StorePathHash.eq(store_path_hash.as_str()))
+        .exec(database)
+        .await
+        .map_err(ServerError::database_error)?;
+
+    if deletion.rows_affected == 0 {
+        return Err(ErrorKind::NoSuchPin.into());
+    }
+
+    audit::record(
+        &state,
+        &req_state,
+        AuditAction::Unpin,
+        Some(&cache_name),
+        Some(store_path_hash.to_string()),
+    )
+    .await;
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+//! Token introspection endpoint.
+
+use axum::extract::{Extension, Json};
+use chrono::SecondsFormat;
+use tracing::instrument;
+
+use crate::error::ServerResult;
+use crate::RequestState;
+use bunker::api::v1::token_info::{CacheGrant, TokenInfo};
+
+/// Returns what the current token grants.
+///
+/// - GET `/_api/v1/token-info`
+///
+/// Revoked and invalid tokens are treated as absent.
+#[instrument(skip_all)]
+pub(crate) async fn get_token_info(
+    Extension(req_state): Extension<RequestState>,
+) -> ServerResult<Json<TokenInfo>> {
+    let Some(token) = req_state.auth.token.get() else {
+        return Ok(Json(TokenInfo::default()));
+    };
+
+    Ok(Json(TokenInfo {
+        subject: token.sub().map(str::to_owned),
+        token_id: token.jti().map(str::to_owned),
+        expires_at: token
+            .expires_at()
+            .map(|expires_at| expires_at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        caches: token
+            .permissions()
+            .map(|(pattern, permission)| CacheGrant {
+                pattern: pattern.as_str().to_owned(),
+                permissions: permission
+                    .granted()
+                    .into_iter()
+                    .map(str::to_owned)
+                    .collect(),
+            })
+            .collect(),
+    }))
+}
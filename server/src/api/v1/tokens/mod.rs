@@ -0,0 +1,83 @@
+//! Token issuance endpoint.
+
+#[cfg(test)]
+mod tests;
+
+use anyhow::anyhow;
+use axum::extract::{Extension, Json};
+use chrono::{Duration as ChronoDuration, SecondsFormat, Utc};
+use tracing::instrument;
+
+use crate::access::{tokens, Token};
+use crate::audit;
+use crate::database::entity::audit_log::AuditAction;
+use crate::error::{ErrorKind, ServerResult};
+use crate::{RequestState, State};
+use bunker::api::v1::tokens::{CreateTokenRequest, CreateTokenResponse};
+use bunker::cache::CacheNamePattern;
+
+/// Issues a token on behalf of the current token.
+///
+/// - POST `/_api/v1/tokens`
+///
+/// The new token has the same subject as the current one. It can
+/// only grant permissions the current token has, and cannot outlive it.
+#[instrument(skip_all)]
+pub(crate) async fn create_token(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Json(payload): Json<CreateTokenRequest>,
+) -> ServerResult<Json<CreateTokenResponse>> {
+    let Some(parent) = req_state.auth.token.get() else {
+        return Err(ErrorKind::Unauthorized.into());
+    };
+
+    let exp = i64::try_from(payload.validity)
+        .ok()
+        .and_then(ChronoDuration::try_seconds)
+        .and_then(|duration| Utc::now().checked_add_signed(duration))
+        .ok_or_else(|| ErrorKind::RequestError(anyhow!("Expiry timestamp overflowed")))?;
+
+    let mut token = Token::new(parent.sub().unwrap_or_default().to_owned(), &exp);
+    for grant in payload.caches {
+        let pattern = CacheNamePattern::new(grant.pattern)?;
+        let permission = token.get_or_insert_permission_mut(pattern);
+        for name in &grant.permissions {
+            permission.grant(name)?;
+        }
+    }
+
+    parent.check_delegation(&token)?;
+
+    let signature_type = state.config.jwt.signing_config.clone().into();
+    let encoded_token = token.encode(
+        &signature_type,
+        &state.config.jwt.token_bound_issuer,
+        &state.config.jwt.token_bound_audiences,
+    )?;
+
+    let database = state.database().await?;
+    tokens::insert_token(database, &token, parent.jti()).await?;
+
+    tracing::info!(
+        "Issued token {} on behalf of token {}",
+        token.jti().unwrap_or_default(),
+        parent.jti().unwrap_or("(no id)")
+    );
+
+    // The target is the new token, while the entry is attributed to the parent
+    audit::record(
+        &state,
+        &req_state,
+        AuditAction::CreateToken,
+        None,
+        token.jti().map(str::to_owned),
+    )
+    .await;
+
+    Ok(Json(CreateTokenResponse {
+        token: encoded_token,
+        token_id: token.jti().map(str::to_owned),
+        expires_at: exp.to_rfc3339_opts(SecondsFormat::Secs, true),
+    }))
+}
@@ -0,0 +1,108 @@
+use super::*;
+use crate::access::tokens::{insert_token, revoke_tokens};
+use crate::audit::{self, AuditLogFilter};
+use crate::database::entity::audit_log::AuditAction;
+use crate::database::BunkerDatabase;
+use crate::testing::{self, TestState};
+
+/// Issues a token on behalf of another and returns it.
+async fn create_child(state: &TestState, parent: Token, validity: u64) -> Token {
+    let res = create_token(
+        state.extension(),
+        Extension(state.request_state(Some(parent))),
+        Json(CreateTokenRequest {
+            validity,
+            caches: Vec::new(),
+        }),
+    )
+    .await
+    .unwrap();
+
+    let signature_type = state.config.jwt.signing_config.clone().into();
+    let child = Token::from_jwt(
+        &res.0.token,
+        &signature_type,
+        &state.config.jwt.token_bound_issuer,
+        &state.config.jwt.token_bound_audiences,
+    )
+    .unwrap();
+    assert_eq!(res.0.token_id.as_deref(), child.jti());
+
+    child
+}
+
+fn jti(token: &Token) -> String {
+    token.jti().unwrap().to_owned()
+}
+
+async fn is_revoked(state: &TestState, jti: &str) -> bool {
+    state
+        .database()
+        .await
+        .unwrap()
+        .is_token_revoked(jti)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_revoking_parent_revokes_children() {
+    let state = TestState::new().await;
+    let db = state.database().await.unwrap();
+
+    let parent = testing::token("alice");
+    let parent_jti = jti(&parent);
+    insert_token(db, &parent, None).await.unwrap();
+
+    let child = create_child(&state, parent, 3600).await;
+    let child_jti = jti(&child);
+    let grandchild_jti = jti(&create_child(&state, child, 60).await);
+
+    let other = testing::token("alice");
+    let other_jti = jti(&other);
+    insert_token(db, &other, None).await.unwrap();
+    let other_child_jti = jti(&create_child(&state, other, 3600).await);
+
+    for jti in [
+        &parent_jti,
+        &child_jti,
+        &grandchild_jti,
+        &other_jti,
+        &other_child_jti,
+    ] {
+        assert!(!is_revoked(&state, jti).await);
+    }
+
+    // Revoking a child leaves its parent alone
+    let revoked = revoke_tokens(state.config.clone(), &[other_child_jti.clone()], None)
+        .await
+        .unwrap();
+    assert_eq!(vec![other_child_jti.clone()], revoked);
+    assert!(is_revoked(&state, &other_child_jti).await);
+    assert!(!is_revoked(&state, &other_jti).await);
+
+    let revoked = revoke_tokens(state.config.clone(), &[parent_jti.clone()], None)
+        .await
+        .unwrap();
+    assert_eq!(vec![parent_jti.clone()], revoked);
+
+    for jti in [&parent_jti, &child_jti, &grandchild_jti] {
+        assert!(is_revoked(&state, jti).await);
+    }
+    assert!(!is_revoked(&state, &other_jti).await);
+
+    let filter = AuditLogFilter {
+        action: Some(AuditAction::RevokeToken),
+        ..Default::default()
+    };
+    let entries = audit::query_audit_log(state.config.clone(), filter, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![Some(other_child_jti), Some(parent_jti)],
+        entries
+            .into_iter()
+            .map(|entry| entry.target)
+            .collect::<Vec<_>>()
+    );
+}
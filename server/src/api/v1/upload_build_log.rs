@@ -0,0 +1,219 @@
+//! Build log upload endpoint.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use axum::{
+    body::Body,
+    extract::{Extension, Path},
+};
+use chrono::Utc;
+use futures::StreamExt;
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::{LockType, OnConflict};
+use sea_orm::ActiveValue::{NotSet, Set};
+use sea_orm::{QuerySelect, TransactionTrait};
+use tokio_util::io::StreamReader;
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::upload_path::{get_compressor_fn, CompressionStream};
+use crate::database::entity::build_log::{self, BuildLogModel, Entity as BuildLog};
+use crate::database::entity::Json as DbJson;
+use crate::error::{ErrorKind, ServerError, ServerResult};
+use crate::narinfo::Compression;
+use crate::{RequestState, State};
+use bunker::cache::CacheName;
+use bunker::nix_store::StorePathHash;
+use bunker::util::Finally;
+
+/// The maximum size of an uncompressed build log.
+const MAX_BUILD_LOG_SIZE: usize = 64 * 1024 * 1024; // 64 MiB
+
+/// Uploads a build log.
+///
+/// - PUT `/_api/v1/upload-build-log/:cache/{drvName}`
+///
+/// The request body is the uncompressed log, which must not exceed
+/// `MAX_BUILD_LOG_SIZE`. An existing log for the same derivation in
+/// the cache is replaced.
+#[instrument(skip_all, fields(cache_name, drv_name))]
+pub(crate) async fn upload_build_log(
+    Extension(state): Extension<State>,
+    Extension(req_state): Extension<RequestState>,
+    Path((cache_name, drv_name)): Path<(CacheName, String)>,
+    body: Body,
+) -> ServerResult<()> {
+    validate_drv_name(&drv_name)?;
+
+    let database = state.database().await?;
+    let cache = req_state
+        .auth
+        .auth_cache(database, &cache_name, |cache, permission| {
+            permission.require_push()?;
+            Ok(cache)
+        })
+        .await?;
+
+    let username = req_state.auth.username().map(str::to_string);
+
+    // The body may be chunked, so the limit is enforced while streaming
+    let too_large = Arc::new(AtomicBool::new(false));
+    let stream = body.into_data_stream();
+    let stream = StreamReader::new(stream.scan(0, {
+        let too_large = too_large.clone();
+        move |received, r| {
+            let r = r
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+                .and_then(|bytes| {
+                    *received += bytes.len();
+                    if *received > MAX_BUILD_LOG_SIZE {
+                        too_large.store(true, Ordering::Relaxed);
+                        Err(io::Error::new(
+                            io::ErrorKind::Other,
+                            "Build log is too large",
+                        ))
+                    } else {
+                        Ok(bytes)
+                    }
+                });
+            futures::future::ready(Some(r))
+        }
+    }));
+
+    let compression_config = &state.config.compression;
+    let compression_type = compression_config.r#type;
+    let compression: Compression = compression_type.into();
+
+    let key = format!("{}.log", Uuid::new_v4());
+
+    let backend = state.storage().await?;
+    let remote_file = backend.make_db_reference(key.clone()).await?;
+    let remote_file_id = remote_file.remote_file_id();
+
+    let cleanup = Finally::new({
+        let backend = backend.clone();
+        let key = key.clone();
+
+        async move {
+            tracing::warn!("Error occurred - Cleaning up uploaded log");
+
+            if let Err(e) = backend.delete_file(key).await {
+                tracing::warn!("Failed to clean up failed upload: {}", e);
+            }
+        }
+    });
+
+    // Compress and stream to the storage backend
+    let compressor = get_compressor_fn(compression_type, compression_config.level());
+    let mut stream = CompressionStream::new(stream, compressor);
+
+    backend
+        .upload_file(key, stream.stream())
+        .await
+        .map_err(|e| {
+            if too_large.load(Ordering::Relaxed) {
+                ErrorKind::RequestError(anyhow!("Build log exceeds {} bytes", MAX_BUILD_LOG_SIZE))
+                    .into()
+            } else {
+                ServerError::storage_error(e)
+            }
+        })?;
+
+    let (_, log_size) = stream.nar_hash_and_size().unwrap();
+    let log_size = i64::try_from(*log_size).map_err(ServerError::request_error)?;
+
+    let log = build_log::ActiveModel {
+        cache_id: Set(Some(cache.id)),
+        drv_name: Set(drv_name),
+        compression: Set(compression.to_string()),
+        remote_file: Set(DbJson(remote_file)),
+        remote_file_id: Set(remote_file_id),
+        log_size: Set(log_size),
+        created_at: Set(Utc::now()),
+        created_by: Set(username),
+        ..Default::default()
+    };
+    let replaced = store_build_log(database, log)
+        .await
+        .map_err(ServerError::database_error)?;
+
+    cleanup.cancel();
+
+    // The replaced log is no longer referenced
+    if let Some(replaced) = replaced {
+        if let Err(e) = backend.delete_file_db(&replaced.remote_file.0).await {
+            tracing::warn!("Failed to delete replaced log: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Inserts a log or replaces the existing log of the derivation.
+///
+/// Returns the replaced log. The existing row is locked until it's
+/// replaced, so concurrent uploads each get the log they replaced
+/// and no remote file is left unreferenced.
+async fn store_build_log(
+    database: &DatabaseConnection,
+    log: build_log::ActiveModel,
+) -> Result<Option<BuildLogModel>, DbErr> {
+    loop {
+        let txn = database.begin().await?;
+
+        let inserted = BuildLog::insert(log.clone())
+            .on_conflict(
+                OnConflict::columns([build_log::Column::CacheId, build_log::Column::DrvName])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(&txn)
+            .await?;
+
+        if inserted != 0 {
+            txn.commit().await?;
+            return Ok(None);
+        }
+
+        let existing = BuildLog::find()
+            .filter(build_log::Column::CacheId.eq(log.cache_id.clone().unwrap()))
+            .filter(build_log::Column::DrvName.eq(log.drv_name.clone().unwrap()))
+            .lock(LockType::Update)
+            .one(&txn)
+            .await?;
+
+        // The existing log was removed in the meantime
+        let Some(existing) = existing else {
+            txn.rollback().await?;
+            continue;
+        };
+
+        let mut update = log.clone();
+        update.id = Set(existing.id);
+        update.cache_id = NotSet;
+        update.drv_name = NotSet;
+        update.update(&txn).await?;
+
+        txn.commit().await?;
+        return Ok(Some(existing));
+    }
+}
+
+/// Validates the base name of a derivation.
+fn validate_drv_name(drv_name: &str) -> ServerResult<()> {
+    let invalid = || ErrorKind::RequestError(anyhow!("Invalid derivation name"));
+
+    let name = drv_name.strip_suffix(".drv").ok_or_else(invalid)?;
+    let (hash, name) = name.split_once('-').ok_or_else(invalid)?;
+
+    StorePathHash::new(hash.to_string())?;
+
+    if name.is_empty() || name.contains('/') {
+        return Err(invalid().into());
+    }
+
+    Ok(())
+}
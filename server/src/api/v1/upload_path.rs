@@ -29,6 +29,7 @@ use tokio_util::io::StreamReader;
 use tracing::instrument;
 use uuid::Uuid;
 
+use crate::audit;
 use crate::config::CompressionType;
 use crate::error::{ErrorKind, ServerError, ServerResult};
 use crate::narinfo::Compression;
@@ -42,6 +43,7 @@ use bunker::hash::Hash;
 use bunker::stream::{read_chunk_async, StreamHasher};
 use bunker::util::Finally;
 
+use crate::database::entity::audit_log::AuditAction;
 use crate::database::entity::cache;
 use crate::database::entity::chunk::{self, ChunkState, Entity as Chunk};
 use crate::database::entity::chunkref::{self, Entity as ChunkRef};
@@ -49,6 +51,8 @@ use crate::database::entity::nar::{self, Entity as Nar, NarState};
 use crate::database::entity::object::{self, Entity as Object, InsertExt};
 use crate::database::entity::Json as DbJson;
 use crate::database::{BunkerDatabase, ChunkGuard, NarGuard};
+use crate::encryption::encrypt_stream;
+use crate::metrics;
 
 const CONCURRENT_CHUNK_UPLOADS: usize = 10;
 
@@ -84,7 +88,7 @@ struct UploadChunkResult {
 ///                                                │
 ///                                                └───────►File Size
 /// ```
-struct CompressionStream {
+pub(super) struct CompressionStream {
     stream: Box<dyn AsyncRead + Unpin + Send>,
     nar_compute: Arc<OnceCell<(DigestOutput<Sha256>, usize)>>,
     file_compute: Arc<OnceCell<(DigestOutput<Sha256>, usize)>>,
@@ -168,10 +172,40 @@ pub(crate) async fn upload_path(
         .await?;
 
     let username = req_state.auth.username().map(str::to_string);
+    let cache_name = cache_name.clone();
+    let store_path = upload_info.store_path.clone();
+
+    let result = ingest_path(username, cache, upload_info, stream, database, &state).await?;
+
+    audit::record(
+        &state,
+        &req_state,
+        AuditAction::Push,
+        Some(&cache_name),
+        Some(store_path),
+    )
+    .await;
+
+    Ok(result)
+}
+
+/// Adds a path to a cache from a NAR stream.
+///
+/// The NAR is deduplicated against the global cache if possible.
+/// Its hash and size are verified against `upload_info`.
+pub(crate) async fn ingest_path(
+    username: Option<String>,
+    cache: cache::Model,
+    upload_info: UploadPathNarInfo,
+    stream: impl AsyncRead + Send + Unpin + 'static,
+    database: &DatabaseConnection,
+    state: &State,
+) -> ServerResult<Json<UploadPathResult>> {
+    let nar_size = upload_info.nar_size;
 
     // Try to acquire a lock on an existing NAR
     let existing_nar = database.find_and_lock_nar(&upload_info.nar_hash).await?;
-    match existing_nar {
+    let result = match existing_nar {
         Some(existing_nar) => {
             // Deduplicate?
             let missing_chunk = ChunkRef::find()
@@ -203,7 +237,11 @@ pub(crate) async fn upload_path(
             // New NAR
             upload_path_new(username, cache, upload_info, stream, database, &state).await
         }
-    }
+    }?;
+
+    metrics::record_upload(&result.kind, nar_size);
+
+    Ok(result)
 }
 
 /// Uploads a path when there is already a matching NAR in the global cache.
@@ -647,6 +685,14 @@ async fn upload_chunk(
 
     let chunk_size_db = i64::try_from(given_chunk_size).map_err(ServerError::request_error)?;
 
+    let (data_key, encryption_key) = match &state.master_key {
+        Some(master_key) => {
+            let (data_key, sealed) = master_key.generate_data_key()?;
+            (Some(data_key), Some(sealed))
+        }
+        None => (None, None),
+    };
+
     let chunk_id = {
         let model = chunk::ActiveModel {
             state: Set(ChunkState::PendingUpload),
@@ -658,6 +704,7 @@ async fn upload_chunk(
 
             remote_file: Set(DbJson(remote_file)),
             remote_file_id: Set(remote_file_id),
+            encryption_key: Set(encryption_key),
 
             created_at: Set(Utc::now()),
             ..Default::default()
@@ -693,14 +740,18 @@ async fn upload_chunk(
         }
     });
 
-    // Compress and stream to the storage backend
+    // Compress, encrypt if enabled, and stream to the storage backend
     let compressor = get_compressor_fn(compression_type, compression_level);
     let mut stream = CompressionStream::new(data.into_async_read(), compressor);
 
-    backend
-        .upload_file(key, stream.stream())
-        .await
-        .map_err(ServerError::storage_error)?;
+    match data_key {
+        Some(data_key) => {
+            let mut stream = encrypt_stream(stream.stream(), data_key);
+            backend.upload_file(key, &mut stream).await
+        }
+        None => backend.upload_file(key, stream.stream()).await,
+    }
+    .map_err(ServerError::storage_error)?;
 
     // Confirm that the chunk hash is correct
     let (chunk_hash, chunk_size) = stream.nar_hash_and_size().unwrap();
@@ -758,7 +809,7 @@ async fn upload_chunk(
 }
 
 /// Returns a compressor function that takes some stream as input.
-fn get_compressor_fn<C: AsyncBufRead + Unpin + Send + 'static>(
+pub(super) fn get_compressor_fn<C: AsyncBufRead + Unpin + Send + 'static>(
     ctype: CompressionType,
     level: CompressionLevel,
 ) -> CompressorFn<C> {
@@ -810,7 +861,10 @@ impl ChunkData {
 
 impl CompressionStream {
     /// Creates a new compression stream.
-    fn new<R>(stream: R, compressor: CompressorFn<BufReader<StreamHasher<R, Sha256>>>) -> Self
+    pub(super) fn new<R>(
+        stream: R,
+        compressor: CompressorFn<BufReader<StreamHasher<R, Sha256>>>,
+    ) -> Self
     where
         R: AsyncRead + Unpin + Send + 'static,
     {
@@ -854,7 +908,7 @@ impl CompressionStream {
     */
 
     /// Returns the stream of the compressed object.
-    fn stream(&mut self) -> &mut (impl AsyncRead + Unpin) {
+    pub(super) fn stream(&mut self) -> &mut (impl AsyncRead + Unpin) {
         &mut self.stream
     }
 
@@ -862,7 +916,7 @@ impl CompressionStream {
     ///
     /// The hash is only finalized when the stream is fully read.
     /// Otherwise, returns `None`.
-    fn nar_hash_and_size(&self) -> Option<&(DigestOutput<Sha256>, usize)> {
+    pub(super) fn nar_hash_and_size(&self) -> Option<&(DigestOutput<Sha256>, usize)> {
         self.nar_compute.get()
     }
 
@@ -884,6 +938,7 @@ impl UploadPathNarInfoExt for UploadPathNarInfo {
             deriver: Set(self.deriver.clone()),
             sigs: Set(DbJson(self.sigs.clone())),
             ca: Set(self.ca.clone()),
+            is_restricted: Set(self.restricted),
             ..Default::default()
         }
     }
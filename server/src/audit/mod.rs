@@ -0,0 +1,399 @@
+//! Audit log.
+//!
+//! Administrative and write operations are recorded along with the
+//! subject and ID of the token they were performed with. Entries form
+//! a hash chain: Each entry includes the hash of the previous one, so
+//! modifying, reordering or removing entries breaks the chain and is
+//! detected by `bunkeradm verify-audit-log`. The hashes are HMACs keyed
+//! with `audit.hmac-key-base64`, so the chain can't be recomputed by
+//! someone who can only write to the database. Removing the most recent
+//! entries can't be detected from the chain alone, which is why entries
+//! can also be exported as JSON Lines to be ingested elsewhere.
+//!
+//! Operations performed with `bunkeradm` are recorded without a token.
+//! Entries that aren't associated with a cache, such as those of token
+//! issuance and revocation, can be queried with `bunkeradm audit-log`.
+
+#[cfg(test)]
+mod tests;
+
+use anyhow::{anyhow, Result};
+use chrono::{SecondsFormat, SubsecRound, Utc};
+use ring::hmac;
+use sea_orm::entity::prelude::*;
+use sea_orm::query::{Condition, QueryOrder, QuerySelect};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue::NotSet;
+use serde::Serialize;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tracing::instrument;
+
+use super::{RequestState, State, StateInner};
+use crate::access::Token;
+use crate::config::Config;
+use crate::database::entity::audit_log::{self, AuditAction, AuditLogModel, Entity as AuditLog};
+use bunker::api::v1::audit::AuditEntry;
+use bunker::cache::CacheName;
+
+/// Number of times to retry appending an entry if the chain moved.
+const MAX_APPEND_ATTEMPTS: usize = 5;
+
+/// Number of rows to fetch from the database at once.
+const BATCH_SIZE: u64 = 500;
+
+/// The outcome of verifying the audit log.
+#[derive(Debug, Default)]
+pub struct AuditVerificationReport {
+    /// Number of entries that were checked.
+    pub entries_verified: u64,
+
+    /// ID of the first entry that breaks the chain.
+    pub first_invalid_entry: Option<i64>,
+}
+
+/// Filters for querying the audit log.
+#[derive(Debug, Clone, Default)]
+pub struct AuditLogFilter {
+    /// Only return entries of this cache.
+    pub cache: Option<String>,
+
+    /// Only return entries of tokens with this subject.
+    pub subject: Option<String>,
+
+    /// Only return entries of this action.
+    pub action: Option<AuditAction>,
+
+    /// Only return entries after this ID.
+    pub after: Option<i64>,
+}
+
+/// The fields of an entry that are covered by its hash.
+#[derive(Serialize)]
+struct HashedEntry<'a> {
+    prev_hash: &'a str,
+    timestamp: String,
+    subject: Option<&'a str>,
+    token_id: Option<&'a str>,
+    action: String,
+    cache: Option<&'a str>,
+    target: Option<&'a str>,
+}
+
+/// Records an operation in the audit log.
+///
+/// Failures are logged and don't fail the request, since the
+/// operation has already been performed.
+pub(crate) async fn record(
+    state: &State,
+    req_state: &RequestState,
+    action: AuditAction,
+    cache: Option<&CacheName>,
+    target: Option<String>,
+) {
+    let entry = new_entry(req_state.auth.token.get(), action, cache, target);
+
+    if let Err(e) = append(state, vec![entry]).await {
+        tracing::error!("Failed to record {:?} in the audit log: {}", action, e);
+    }
+}
+
+/// Records an operation on several targets in the audit log.
+///
+/// Each target gets its own entry, and all of them are appended
+/// in one go.
+pub(crate) async fn record_many(
+    state: &State,
+    req_state: &RequestState,
+    action: AuditAction,
+    cache: Option<&CacheName>,
+    targets: &[String],
+) {
+    let token = req_state.auth.token.get();
+    let entries = targets
+        .iter()
+        .map(|target| new_entry(token, action, cache, Some(target.to_owned())))
+        .collect();
+
+    if let Err(e) = append(state, entries).await {
+        tracing::error!("Failed to record {:?} in the audit log: {}", action, e);
+    }
+}
+
+/// Records an operation performed with `bunkeradm`.
+///
+/// Unlike [`record`], failures are returned since nothing has been
+/// reported to the administrator yet.
+pub(crate) async fn record_admin(
+    state: &StateInner,
+    action: AuditAction,
+    targets: &[String],
+) -> Result<()> {
+    let entries = targets
+        .iter()
+        .map(|target| new_entry(None, action, None, Some(target.to_owned())))
+        .collect();
+
+    append(state, entries).await
+}
+
+/// Returns an entry attributed to a token.
+fn new_entry(
+    token: Option<&Token>,
+    action: AuditAction,
+    cache: Option<&CacheName>,
+    target: Option<String>,
+) -> AuditLogModel {
+    AuditLogModel {
+        id: 0,
+        created_at: Utc::now().trunc_subsecs(0),
+        subject: token.and_then(|token| token.sub()).map(str::to_string),
+        token_id: token.and_then(|token| token.jti()).map(str::to_string),
+        action,
+        cache: cache.map(|cache| cache.as_str().to_string()),
+        target,
+        prev_hash: String::new(),
+        hash: String::new(),
+    }
+}
+
+/// Appends entries to the chain and exports them.
+#[instrument(skip_all)]
+async fn append(state: &StateInner, entries: Vec<AuditLogModel>) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let db = state.database().await?;
+
+    // Writers in this process take turns, and the unique `prev_hash`
+    // keeps other replicas from forking the chain
+    let _guard = state.audit_lock.lock().await;
+
+    let mut prev_hash = latest_hash(db).await?;
+    for mut entry in entries {
+        insert_entry(db, &state.config.audit.hmac_key, &mut entry, &mut prev_hash).await?;
+
+        // Exported while holding the lock so the file is in chain order
+        if let Some(path) = &state.config.audit.export_file {
+            export(path, entry).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Inserts an entry after the entry with the hash `prev_hash`.
+///
+/// If another writer appended an entry first, the entry is chained
+/// to the latest entry instead. On success, `prev_hash` is updated
+/// to the hash of the inserted entry.
+async fn insert_entry(
+    db: &DatabaseConnection,
+    key: &[u8; 32],
+    entry: &mut AuditLogModel,
+    prev_hash: &mut String,
+) -> Result<()> {
+    for _ in 0..MAX_APPEND_ATTEMPTS {
+        entry.prev_hash = prev_hash.clone();
+        entry.hash = compute_hash(key, entry);
+
+        let mut model = audit_log::ActiveModel::from(entry.clone());
+        model.id = NotSet;
+
+        let insertion = AuditLog::insert(model)
+            .on_conflict(
+                OnConflict::column(audit_log::Column::PrevHash)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec(db)
+            .await;
+
+        match insertion {
+            Ok(insertion) => {
+                entry.id = insertion.last_insert_id;
+                *prev_hash = entry.hash.clone();
+
+                return Ok(());
+            }
+            Err(DbErr::RecordNotInserted) => {
+                // Another writer appended an entry first
+                *prev_hash = latest_hash(db).await?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(anyhow!(
+        "Gave up appending after {} attempts",
+        MAX_APPEND_ATTEMPTS
+    ))
+}
+
+/// Returns the hash of the latest entry, or an empty string if there are none.
+async fn latest_hash(db: &DatabaseConnection) -> Result<String> {
+    Ok(AuditLog::find()
+        .order_by_desc(audit_log::Column::Id)
+        .one(db)
+        .await?
+        .map(|latest| latest.hash)
+        .unwrap_or_default())
+}
+
+/// Appends an entry to the export file as a JSON line.
+async fn export(path: &std::path::Path, entry: AuditLogModel) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(&to_audit_entry(entry))?;
+    line.push(b'\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(&line).await?;
+
+    Ok(())
+}
+
+/// Returns the HMAC of an entry.
+///
+/// The ID and the stored hash itself aren't covered.
+fn compute_hash(key: &[u8; 32], entry: &AuditLogModel) -> String {
+    let hashed = HashedEntry {
+        prev_hash: &entry.prev_hash,
+        timestamp: entry.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        subject: entry.subject.as_deref(),
+        token_id: entry.token_id.as_deref(),
+        action: entry.action.to_value(),
+        cache: entry.cache.as_deref(),
+        target: entry.target.as_deref(),
+    };
+
+    let json = serde_json::to_vec(&hashed).expect("Audit log entries are always serializable");
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hex::encode(hmac::sign(&key, &json))
+}
+
+/// Parses the name of an action (e.g., "push").
+pub fn parse_action(action: &str) -> Result<AuditAction> {
+    AuditAction::try_from_value(&action.to_owned())
+        .map_err(|_| anyhow!("Unknown audit action \"{}\"", action))
+}
+
+/// Returns the entries matching a filter in the order they were recorded.
+pub(crate) async fn find_entries(
+    db: &DatabaseConnection,
+    filter: &AuditLogFilter,
+    limit: u64,
+) -> Result<Vec<AuditLogModel>, DbErr> {
+    let mut condition = Condition::all();
+
+    if let Some(cache) = &filter.cache {
+        condition = condition.add(audit_log::Column::Cache.eq(cache.as_str()));
+    }
+    if let Some(subject) = &filter.subject {
+        condition = condition.add(audit_log::Column::Subject.eq(subject.as_str()));
+    }
+    if let Some(action) = filter.action {
+        condition = condition.add(audit_log::Column::Action.eq(action));
+    }
+    if let Some(after) = filter.after {
+        condition = condition.add(audit_log::Column::Id.gt(after));
+    }
+
+    AuditLog::find()
+        .filter(condition)
+        .order_by_asc(audit_log::Column::Id)
+        .limit(limit)
+        .all(db)
+        .await
+}
+
+/// Queries the whole audit log, including entries without a cache.
+///
+/// Returns at most `limit` entries if specified.
+#[instrument(skip_all)]
+pub async fn query_audit_log(
+    config: Config,
+    mut filter: AuditLogFilter,
+    limit: Option<u64>,
+) -> Result<Vec<AuditEntry>> {
+    let state = StateInner::new(config).await;
+    let db = state.database().await?;
+
+    let mut entries = Vec::new();
+    loop {
+        let remaining = limit.map_or(BATCH_SIZE, |limit| limit - entries.len() as u64);
+        if remaining == 0 {
+            break;
+        }
+
+        let batch = find_entries(db, &filter, remaining.min(BATCH_SIZE)).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        filter.after = Some(last.id);
+
+        entries.extend(batch.into_iter().map(to_audit_entry));
+    }
+
+    Ok(entries)
+}
+
+/// Converts an entry to its API representation.
+pub(crate) fn to_audit_entry(entry: AuditLogModel) -> AuditEntry {
+    AuditEntry {
+        id: entry.id,
+        timestamp: entry.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        subject: entry.subject,
+        token_id: entry.token_id,
+        action: entry.action.to_value(),
+        cache: entry.cache,
+        target: entry.target,
+        prev_hash: entry.prev_hash,
+        hash: entry.hash,
+    }
+}
+
+/// Verifies the hash chain of the audit log.
+///
+/// Verification stops at the first entry that breaks the chain.
+#[instrument(skip_all)]
+pub async fn verify_audit_log(config: Config) -> Result<AuditVerificationReport> {
+    let state = StateInner::new(config).await;
+    let db = state.database().await?;
+
+    let mut report = AuditVerificationReport::default();
+    let mut expected_prev_hash = String::new();
+    let mut last_id = 0;
+
+    loop {
+        let entries: Vec<AuditLogModel> = AuditLog::find()
+            .filter(audit_log::Column::Id.gt(last_id))
+            .order_by_asc(audit_log::Column::Id)
+            .limit(BATCH_SIZE)
+            .all(db)
+            .await?;
+
+        let Some(last) = entries.last() else {
+            break;
+        };
+        last_id = last.id;
+
+        for entry in entries {
+            if entry.prev_hash != expected_prev_hash
+                || compute_hash(&state.config.audit.hmac_key, &entry) != entry.hash
+            {
+                tracing::warn!("Audit log entry {} breaks the chain", entry.id);
+                report.first_invalid_entry = Some(entry.id);
+                return Ok(report);
+            }
+
+            report.entries_verified += 1;
+            expected_prev_hash = entry.hash;
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,84 @@
+use sea_orm::sea_query::Expr;
+
+use super::*;
+use crate::testing::{self, TestState};
+
+const PATHS: [&str; 3] = [
+    "/nix/store/00000000000000000000000000000001-a",
+    "/nix/store/00000000000000000000000000000002-b",
+    "/nix/store/00000000000000000000000000000003-c",
+];
+
+/// Returns a state with an entry for each of `PATHS`.
+async fn setup() -> (TestState, Vec<AuditLogModel>) {
+    let state = TestState::new().await;
+    let req_state = state.request_state(Some(testing::token("alice")));
+    let cache = CacheName::new("test".to_string()).unwrap();
+
+    let targets: Vec<String> = PATHS.iter().map(|path| path.to_string()).collect();
+    record_many(
+        &state,
+        &req_state,
+        AuditAction::Delete,
+        Some(&cache),
+        &targets,
+    )
+    .await;
+
+    let entries = AuditLog::find()
+        .order_by_asc(audit_log::Column::Id)
+        .all(state.database().await.unwrap())
+        .await
+        .unwrap();
+
+    (state, entries)
+}
+
+#[tokio::test]
+async fn test_record_many() {
+    let (state, entries) = setup().await;
+
+    assert_eq!(PATHS.len(), entries.len());
+
+    let mut prev_hash = String::new();
+    for (entry, path) in entries.iter().zip(PATHS) {
+        assert_eq!(Some(path), entry.target.as_deref());
+        assert_eq!(Some("alice"), entry.subject.as_deref());
+        assert_eq!(AuditAction::Delete, entry.action);
+        assert_eq!(prev_hash, entry.prev_hash);
+        prev_hash = entry.hash.clone();
+    }
+
+    let report = verify_audit_log(state.config.clone()).await.unwrap();
+    assert_eq!(PATHS.len() as u64, report.entries_verified);
+    assert_eq!(None, report.first_invalid_entry);
+}
+
+#[tokio::test]
+async fn test_verify_detects_modified_entries() {
+    let (state, entries) = setup().await;
+
+    AuditLog::update_many()
+        .col_expr(audit_log::Column::Target, Expr::value("/nix/store/evil"))
+        .filter(audit_log::Column::Id.eq(entries[1].id))
+        .exec(state.database().await.unwrap())
+        .await
+        .unwrap();
+
+    let report = verify_audit_log(state.config.clone()).await.unwrap();
+    assert_eq!(1, report.entries_verified);
+    assert_eq!(Some(entries[1].id), report.first_invalid_entry);
+}
+
+#[tokio::test]
+async fn test_verify_requires_key() {
+    let (state, entries) = setup().await;
+
+    // The chain can't be recomputed without the key
+    let mut config = state.config.clone();
+    config.audit.hmac_key = [1; 32];
+
+    let report = verify_audit_log(config).await.unwrap();
+    assert_eq!(0, report.entries_verified);
+    assert_eq!(Some(entries[0].id), report.first_invalid_entry);
+}
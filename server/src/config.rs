@@ -3,24 +3,27 @@
 // Copyright (C) 2025 Qompass AI, All rights reserved
 /////////////////////////////////////////////////////
 //! Server configuration.
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::env;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
+
 use anyhow::Result;
 use async_compression::Level as CompressionLevel;
-use bunker_token::SignatureType;
 use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use bunker_token::SignatureType;
 use derivative::Derivative;
 use serde::{de, Deserialize};
 use xdg::BaseDirectories;
+
 use crate::access::{
     decode_token_hs256_secret_base64, decode_token_rs256_pubkey_base64,
     decode_token_rs256_secret_base64, HS256Key, RS256KeyPair, RS256PublicKey,
 };
 use crate::narinfo::Compression as NixCompression;
 use crate::storage::{LocalStorageConfig, S3StorageConfig};
+use bunker::cache::CacheNamePattern;
 
 /// Application prefix in XDG base directories.
 ///
@@ -47,6 +50,12 @@ const ENV_TOKEN_RS256_PUBKEY_BASE64: &str = "BUNKER_SERVER_TOKEN_RS256_PUBKEY_BA
 /// Environment variable storing the database connection string.
 const ENV_DATABASE_URL: &str = "BUNKER_SERVER_DATABASE_URL";
 
+/// Environment variable storing the base64-encoded master key for encryption at rest.
+const ENV_ENCRYPTION_MASTER_KEY_BASE64: &str = "BUNKER_SERVER_ENCRYPTION_MASTER_KEY_BASE64";
+
+/// Environment variable storing the base64-encoded HMAC key of the audit log.
+const ENV_AUDIT_HMAC_KEY_BASE64: &str = "BUNKER_SERVER_AUDIT_HMAC_KEY_BASE64";
+
 /// Configuration for the Bunker Server.
 #[derive(Clone, Derivative, Deserialize)]
 #[derivative(Debug)]
@@ -123,6 +132,35 @@ pub struct Config {
     #[serde(default = "Default::default")]
     pub garbage_collection: GarbageCollectionConfig,
 
+    /// Storage verification.
+    #[serde(default = "Default::default")]
+    pub verification: VerificationConfig,
+
+    /// Encryption at rest.
+    ///
+    /// If configured, newly-uploaded chunks are encrypted before
+    /// they are written to the storage backend.
+    #[serde(default = "Default::default")]
+    pub encryption: Option<EncryptionConfig>,
+
+    /// External signer.
+    ///
+    /// If configured, private keys are held by an external signer and
+    /// the database only stores references to them.
+    #[serde(default = "Default::default")]
+    pub signer: Option<SignerConfig>,
+
+    /// Prometheus metrics.
+    ///
+    /// If configured, metrics are served at `/metrics` on a separate
+    /// listen address.
+    #[serde(default = "Default::default")]
+    pub metrics: Option<MetricsConfig>,
+
+    /// Audit log.
+    #[serde(default = "Default::default")]
+    pub audit: AuditConfig,
+
     /// JSON Web Token.
     #[serde(default = "Default::default")]
     pub jwt: JWTConfig,
@@ -163,6 +201,101 @@ pub struct JWTConfig {
     #[serde(default = "load_jwt_signing_config_from_env")]
     #[derivative(Debug = "ignore")]
     pub signing_config: JWTSigningConfig,
+
+    /// External OIDC token issuers.
+    ///
+    /// Tokens signed by the keys of an issuer are accepted as well,
+    /// with cache permissions derived from their claims.
+    #[serde(default = "Default::default")]
+    pub oidc: Vec<OidcConfig>,
+}
+
+/// External OIDC token issuer configuration.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcConfig {
+    /// The `iss` claim of tokens from this issuer.
+    pub issuer: String,
+
+    /// The `aud` claim of tokens from this issuer.
+    ///
+    /// Tokens must contain one of the configured values. This is
+    /// required since issuers like GitHub Actions sign tokens for
+    /// any audience a job asks for.
+    #[serde(deserialize_with = "deserialize_oidc_audiences")]
+    pub audiences: HashSet<String>,
+
+    /// Where to load the JSON Web Key Set of the issuer from.
+    #[serde(flatten)]
+    pub jwks: JwksSource,
+
+    /// The frequency to reload the JSON Web Key Set at.
+    #[serde(rename = "refresh-interval")]
+    #[serde(with = "humantime_serde", default = "default_jwks_refresh_interval")]
+    pub refresh_interval: Duration,
+
+    /// Rules mapping token claims to cache permissions.
+    ///
+    /// Permissions from all matching rules are granted. Tokens
+    /// matching no rules have no permissions.
+    #[serde(default = "Default::default")]
+    pub rules: Vec<ClaimMappingRule>,
+}
+
+/// Source of a JSON Web Key Set.
+#[derive(Debug, Clone, Deserialize)]
+pub enum JwksSource {
+    /// URL to fetch the JWKS from.
+    #[serde(rename = "jwks-url")]
+    Url(String),
+
+    /// Path to a local JWKS file.
+    #[serde(rename = "jwks-file")]
+    File(PathBuf),
+}
+
+/// A rule mapping token claims to cache permissions.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClaimMappingRule {
+    /// Claims that must match for the rule to apply.
+    ///
+    /// Values can include wildcards ('*' and '?').
+    pub claims: BTreeMap<String, String>,
+
+    /// Caches the token can pull from.
+    #[serde(default = "Default::default")]
+    pub pull: Vec<CacheNamePattern>,
+
+    /// Caches the token can push to.
+    #[serde(default = "Default::default")]
+    pub push: Vec<CacheNamePattern>,
+
+    /// Caches the token can delete objects from.
+    #[serde(default = "Default::default")]
+    pub delete: Vec<CacheNamePattern>,
+
+    /// Caches the token can create.
+    #[serde(rename = "create-cache")]
+    #[serde(default = "Default::default")]
+    pub create_cache: Vec<CacheNamePattern>,
+
+    /// Caches the token can reconfigure.
+    #[serde(rename = "configure-cache")]
+    #[serde(default = "Default::default")]
+    pub configure_cache: Vec<CacheNamePattern>,
+
+    /// Caches the token can configure retention and quota of.
+    #[serde(rename = "configure-cache-retention")]
+    #[serde(default = "Default::default")]
+    pub configure_cache_retention: Vec<CacheNamePattern>,
+
+    /// Caches the token can destroy.
+    #[serde(rename = "destroy-cache")]
+    #[serde(default = "Default::default")]
+    pub destroy_cache: Vec<CacheNamePattern>,
+
+    /// Caches the token can manage pins in.
+    #[serde(default = "Default::default")]
+    pub pin: Vec<CacheNamePattern>,
 }
 
 /// JSON Web Token signing configuration.
@@ -228,6 +361,42 @@ pub enum StorageConfig {
     S3(S3StorageConfig),
 }
 
+/// Encryption at rest configuration.
+#[derive(Clone, Derivative, Deserialize)]
+#[derivative(Debug)]
+pub struct EncryptionConfig {
+    /// The master key.
+    ///
+    /// Set this to a base64-encoded 256-bit key. The data keys of
+    /// encrypted chunks are sealed with it, so encrypted chunks cannot
+    /// be read without it.
+    #[serde(rename = "master-key-base64")]
+    #[serde(deserialize_with = "deserialize_master_key_base64")]
+    #[serde(default = "load_master_key_from_env")]
+    #[derivative(Debug = "ignore")]
+    pub master_key: [u8; 32],
+}
+
+/// External signer configuration.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum SignerConfig {
+    /// A signing daemon listening on a Unix socket.
+    #[serde(rename = "unix-socket")]
+    UnixSocket(UnixSocketSignerConfig),
+}
+
+/// Unix socket signer configuration.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UnixSocketSignerConfig {
+    /// Path to the socket.
+    pub path: PathBuf,
+
+    /// How long to wait for a signature.
+    #[serde(with = "humantime_serde", default = "default_signer_timeout")]
+    pub timeout: Duration,
+}
+
 /// Data chunking.
 ///
 /// This must be set, but a default set of values is provided
@@ -300,6 +469,56 @@ pub enum CompressionType {
     Xz,
 }
 
+/// Prometheus metrics configuration.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetricsConfig {
+    /// Socket address to serve metrics on.
+    ///
+    /// Metrics are never served on the main listen address, so they
+    /// aren't exposed to everyone who can reach the API.
+    pub listen: SocketAddr,
+}
+
+/// Audit log configuration.
+#[derive(Clone, Derivative, Deserialize)]
+#[derivative(Debug)]
+pub struct AuditConfig {
+    /// File to append entries to, in JSON Lines format.
+    ///
+    /// This is useful for ingestion into a SIEM. Entries are
+    /// recorded in the database regardless.
+    #[serde(rename = "export-file")]
+    #[serde(default = "Default::default")]
+    pub export_file: Option<PathBuf>,
+
+    /// The key the hash chain is computed with.
+    ///
+    /// Set this to a base64-encoded 256-bit key. Without it, entries
+    /// can't be rewritten with a valid chain, even by someone with
+    /// write access to the database.
+    #[serde(rename = "hmac-key-base64")]
+    #[serde(deserialize_with = "deserialize_audit_hmac_key_base64")]
+    #[serde(default = "load_audit_hmac_key_from_env")]
+    #[derivative(Debug = "ignore")]
+    pub hmac_key: [u8; 32],
+}
+
+/// Storage verification config.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerificationConfig {
+    /// The frequency to verify the stored chunks at.
+    ///
+    /// If zero (default), periodic verification is disabled, but
+    /// it can still be run manually with `bunkeradm verify`.
+    #[serde(with = "humantime_serde", default = "default_verification_interval")]
+    pub interval: Duration,
+
+    /// Whether to reassemble NARs and verify their NAR hashes as well.
+    #[serde(rename = "verify-nars")]
+    #[serde(default = "Default::default")]
+    pub verify_nars: bool,
+}
+
 /// Garbage collection config.
 #[derive(Debug, Clone, Deserialize)]
 pub struct GarbageCollectionConfig {
@@ -314,13 +533,19 @@ pub struct GarbageCollectionConfig {
     ///
     /// Objects are subject to garbage collection if both the
     /// `created_at` and `last_accessed_at` timestamps are older
-    /// than the retention period.
+    /// than the retention period. Objects referenced by live objects
+    /// in the same cache are retained regardless.
     ///
     /// Zero (default) means time-based garbage-collection is
     /// disabled by default. You can enable it on a per-cache basis.
     #[serde(rename = "default-retention-period")]
     #[serde(with = "humantime_serde", default = "default_default_retention_period")]
     pub default_retention_period: Duration,
+
+    /// The maximum number of chunks to delete from storage concurrently.
+    #[serde(rename = "deletion-concurrency")]
+    #[serde(default = "default_gc_deletion_concurrency")]
+    pub deletion_concurrency: usize,
 }
 
 fn load_jwt_signing_config_from_env() -> JWTSigningConfig {
@@ -412,6 +637,46 @@ fn load_token_rs256_pubkey_from_env() -> Option<JWTSigningConfig> {
     Some(JWTSigningConfig::RS256VerifyOnly(pubkey))
 }
 
+fn load_master_key_from_env() -> [u8; 32] {
+    let s = read_non_empty_var(ENV_ENCRYPTION_MASTER_KEY_BASE64)
+        .expect("Master key environment cannot be read")
+        .unwrap_or_else(|| {
+            panic!(
+                "The master key must be specified in either encryption.master-key-base64 \
+                or the {ENV_ENCRYPTION_MASTER_KEY_BASE64} environment."
+            )
+        });
+
+    decode_master_key_base64(&s).expect("Master key cannot be decoded")
+}
+
+fn decode_master_key_base64(s: &str) -> Result<[u8; 32]> {
+    let key = BASE64_STANDARD.decode(s)?;
+
+    key.try_into()
+        .map_err(|_| anyhow::anyhow!("The master key must be 32 bytes"))
+}
+
+fn load_audit_hmac_key_from_env() -> [u8; 32] {
+    let s = read_non_empty_var(ENV_AUDIT_HMAC_KEY_BASE64)
+        .expect("Audit log HMAC key environment cannot be read")
+        .unwrap_or_else(|| {
+            panic!(
+                "The audit log HMAC key must be specified in either audit.hmac-key-base64 \
+                or the {ENV_AUDIT_HMAC_KEY_BASE64} environment."
+            )
+        });
+
+    decode_audit_hmac_key_base64(&s).expect("Audit log HMAC key cannot be decoded")
+}
+
+fn decode_audit_hmac_key_base64(s: &str) -> Result<[u8; 32]> {
+    let key = BASE64_STANDARD.decode(s)?;
+
+    key.try_into()
+        .map_err(|_| anyhow::anyhow!("The audit log HMAC key must be 32 bytes"))
+}
+
 fn load_database_url_from_env() -> String {
     env::var(ENV_DATABASE_URL).expect(&format!(
         "Database URL must be specified in either database.url \
@@ -425,6 +690,16 @@ impl Default for JWTConfig {
             token_bound_issuer: None,
             token_bound_audiences: None,
             signing_config: load_jwt_signing_config_from_env(),
+            oidc: Vec::new(),
+        }
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            export_file: None,
+            hmac_key: load_audit_hmac_key_from_env(),
         }
     }
 }
@@ -469,6 +744,16 @@ impl Default for GarbageCollectionConfig {
         Self {
             interval: Duration::from_secs(43200),
             default_retention_period: Duration::ZERO,
+            deletion_concurrency: default_gc_deletion_concurrency(),
+        }
+    }
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::ZERO,
+            verify_nars: false,
         }
     }
 }
@@ -509,6 +794,30 @@ where
     Ok(key)
 }
 
+fn deserialize_master_key_base64<'de, D>(deserializer: D) -> Result<[u8; 32], D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    use de::Error;
+
+    let s = String::deserialize(deserializer)?;
+    let key = decode_master_key_base64(&s).map_err(Error::custom)?;
+
+    Ok(key)
+}
+
+fn deserialize_audit_hmac_key_base64<'de, D>(deserializer: D) -> Result<[u8; 32], D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    use de::Error;
+
+    let s = String::deserialize(deserializer)?;
+    let key = decode_audit_hmac_key_base64(&s).map_err(Error::custom)?;
+
+    Ok(key)
+}
+
 fn deserialize_token_rs256_secret_base64<'de, D>(deserializer: D) -> Result<RS256KeyPair, D::Error>
 where
     D: de::Deserializer<'de>,
@@ -535,6 +844,22 @@ where
     Ok(key)
 }
 
+fn deserialize_oidc_audiences<'de, D>(deserializer: D) -> Result<HashSet<String>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    use de::Error;
+
+    let audiences = HashSet::<String>::deserialize(deserializer)?;
+    if audiences.is_empty() {
+        return Err(Error::custom(
+            "OIDC issuers must have at least one audience",
+        ));
+    }
+
+    Ok(audiences)
+}
+
 fn default_listen_address() -> SocketAddr {
     "[::]:8080".parse().unwrap()
 }
@@ -555,10 +880,26 @@ fn default_gc_interval() -> Duration {
     Duration::from_secs(43200)
 }
 
+fn default_gc_deletion_concurrency() -> usize {
+    20
+}
+
+fn default_jwks_refresh_interval() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_verification_interval() -> Duration {
+    Duration::ZERO
+}
+
 fn default_default_retention_period() -> Duration {
     Duration::ZERO
 }
 
+fn default_signer_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
 fn load_config_from_path(path: &Path) -> Result<Config> {
     tracing::info!("Using configurations: {:?}", path);
 
@@ -0,0 +1,97 @@
+//! An entry in the audit log.
+
+use sea_orm::entity::prelude::*;
+
+pub type AuditLogModel = Model;
+
+/// An administrative or write operation.
+#[derive(EnumIter, DeriveActiveEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(32))")]
+pub enum AuditAction {
+    /// A cache was created.
+    #[sea_orm(string_value = "create-cache")]
+    CreateCache,
+
+    /// A cache was reconfigured.
+    #[sea_orm(string_value = "configure-cache")]
+    ConfigureCache,
+
+    /// A cache was destroyed.
+    #[sea_orm(string_value = "destroy-cache")]
+    DestroyCache,
+
+    /// A store path was pushed.
+    #[sea_orm(string_value = "push")]
+    Push,
+
+    /// Store paths were copied from another cache.
+    #[sea_orm(string_value = "copy")]
+    Copy,
+
+    /// Store paths were deleted.
+    #[sea_orm(string_value = "delete")]
+    Delete,
+
+    /// A store path was pinned.
+    #[sea_orm(string_value = "pin")]
+    Pin,
+
+    /// A store path was unpinned.
+    #[sea_orm(string_value = "unpin")]
+    Unpin,
+
+    /// A token was issued.
+    #[sea_orm(string_value = "create-token")]
+    CreateToken,
+
+    /// A token was revoked.
+    #[sea_orm(string_value = "revoke-token")]
+    RevokeToken,
+}
+
+/// An entry in the audit log.
+///
+/// Each entry includes the hash of the previous one, so entries
+/// cannot be modified or removed without breaking the chain.
+#[derive(Debug, Clone, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "audit_log")]
+pub struct Model {
+    /// Unique numeric ID of the entry.
+    #[sea_orm(primary_key)]
+    pub id: i64,
+
+    /// Timestamp when the operation was performed.
+    pub created_at: ChronoDateTimeUtc,
+
+    /// The subject of the token used.
+    pub subject: Option<String>,
+
+    /// The ID of the token used.
+    pub token_id: Option<String>,
+
+    /// The operation performed.
+    pub action: AuditAction,
+
+    /// The name of the cache operated on.
+    #[sea_orm(indexed)]
+    pub cache: Option<String>,
+
+    /// The object of the operation, such as a store path.
+    #[sea_orm(column_type = "Text", nullable)]
+    pub target: Option<String>,
+
+    /// The hash of the previous entry.
+    ///
+    /// This is empty for the first entry. It's unique so
+    /// concurrent writers cannot fork the chain.
+    #[sea_orm(unique)]
+    pub prev_hash: String,
+
+    /// The hash of this entry, including `prev_hash`.
+    pub hash: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,71 @@
+//! A build log in a local cache.
+
+use sea_orm::entity::prelude::*;
+
+use super::Json;
+use crate::storage::RemoteFile;
+
+pub type BuildLogModel = Model;
+
+/// A build log in a local cache.
+///
+/// Logs are keyed by the base name of the derivation that
+/// produced them and are served at `/:cache/log/{drvName}`.
+/// Unlike NARs, logs are neither chunked nor deduplicated.
+#[derive(Debug, Clone, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "build_log")]
+pub struct Model {
+    /// Unique numeric ID of the log.
+    #[sea_orm(primary_key)]
+    pub id: i64,
+
+    /// ID of the cache the log belongs to.
+    ///
+    /// This is unset once the cache is destroyed, and the orphaned
+    /// log is deleted from the storage backend during garbage collection.
+    #[sea_orm(indexed)]
+    pub cache_id: Option<i64>,
+
+    /// Base name of the derivation.
+    ///
+    /// For example, `7xxy4lbhbqfg9j0gbfxpgv2wq0x3pm8q-hello-2.12.1.drv`.
+    pub drv_name: String,
+
+    /// The type of compression in use.
+    #[sea_orm(column_type = "String(Some(10))")]
+    pub compression: String,
+
+    /// The remote file backing this log.
+    pub remote_file: Json<RemoteFile>,
+
+    /// Unique string identifying the remote file.
+    #[sea_orm(unique)]
+    pub remote_file_id: String,
+
+    /// Size of the uncompressed log.
+    pub log_size: i64,
+
+    /// Timestamp when the log is uploaded.
+    pub created_at: ChronoDateTimeUtc,
+
+    /// The user that uploaded the log.
+    pub created_by: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::cache::Entity",
+        from = "Column::CacheId",
+        to = "super::cache::Column::Id"
+    )]
+    Cache,
+}
+
+impl Related<super::cache::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Cache.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
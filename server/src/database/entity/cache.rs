@@ -1,10 +1,15 @@
 //! A binary cache.
 
+use chrono::Utc;
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use wildmatch::WildMatch;
 
+use super::object::ObjectModel;
 use super::Json;
-use bunker::error::BunkerResult;
-use bunker::signing::NixKeypair;
+use crate::error::ServerResult;
+use crate::signer::SigningKey;
+use bunker::api::v1::cache_config::UpstreamProxy;
 
 pub type CacheModel = Model;
 
@@ -21,8 +26,24 @@ pub struct Model {
     pub name: String,
 
     /// Signing keypair for the cache.
+    ///
+    /// If the cache is signed by an external signer, this is the
+    /// public key that references the key in the signer.
     pub keypair: String,
 
+    /// Post-quantum signing keypair for the cache.
+    ///
+    /// If set, narinfos are signed with both keypairs.
+    #[sea_orm(column_type = "Text", nullable)]
+    pub pqc_keypair: Option<String>,
+
+    /// Previous signing keypairs that are being rotated out.
+    ///
+    /// Narinfos are still signed with these keypairs until they
+    /// are retired.
+    #[sea_orm(column_type = "Text")]
+    pub retiring_keypairs: Json<Vec<RetiringKeypair>>,
+
     /// Whether the cache is public or not.
     ///
     /// Anonymous clients are implicitly granted the "pull"
@@ -49,6 +70,36 @@ pub struct Model {
 
     /// The retention period of the cache, in seconds.
     pub retention_period: Option<i32>,
+
+    /// The upstream substituter to fetch missing paths from.
+    pub upstream_proxy: Option<Json<UpstreamProxy>>,
+
+    /// The storage quota of the cache, in bytes.
+    ///
+    /// Usage is the sum of the NAR sizes of all objects in the cache.
+    pub quota: Option<i64>,
+
+    /// Globs of store path names that require a token to pull.
+    ///
+    /// Matching objects can't be pulled anonymously even if the
+    /// cache is public.
+    #[sea_orm(column_type = "Text")]
+    pub restricted_paths: Json<Vec<String>>,
+}
+
+/// A signing keypair that is being rotated out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetiringKeypair {
+    /// The Ed25519 keypair.
+    pub keypair: String,
+
+    /// The post-quantum keypair, if hybrid signing was enabled.
+    pub pqc_keypair: Option<String>,
+
+    /// Timestamp when the keypair stops being used.
+    ///
+    /// If unset, the keypair is used until it's retired manually.
+    pub retire_at: Option<ChronoDateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -58,8 +109,63 @@ pub enum Relation {
 }
 
 impl Model {
-    pub fn keypair(&self) -> BunkerResult<NixKeypair> {
-        NixKeypair::from_str(&self.keypair)
+    /// Returns the active signing key.
+    pub fn signing_key(&self) -> ServerResult<SigningKey> {
+        SigningKey::from_stored(&self.keypair, self.pqc_keypair.as_deref())
+    }
+
+    /// Returns the retiring signing keys that haven't been retired yet.
+    pub fn retiring_keys(&self) -> ServerResult<Vec<SigningKey>> {
+        let now = Utc::now();
+
+        self.retiring_keypairs
+            .0
+            .iter()
+            .filter(|retiring| !retiring.is_retired(now))
+            .map(|retiring| {
+                SigningKey::from_stored(&retiring.keypair, retiring.pqc_keypair.as_deref())
+            })
+            .collect()
+    }
+
+    /// Returns all keys that narinfos are signed with.
+    ///
+    /// The active key comes first.
+    pub fn signing_keys(&self) -> ServerResult<Vec<SigningKey>> {
+        let mut keys = vec![self.signing_key()?];
+        keys.extend(self.retiring_keys()?);
+
+        Ok(keys)
+    }
+
+    /// Returns whether pulling an object requires explicit permission.
+    ///
+    /// Objects are restricted if they were flagged at push time, or
+    /// if their names match one of the restricted path globs.
+    pub fn is_object_restricted(&self, object: &ObjectModel) -> bool {
+        if object.is_restricted {
+            return true;
+        }
+
+        // /nix/store/{hash}-{name}
+        let name = object
+            .store_path
+            .rsplit('/')
+            .next()
+            .and_then(|base| base.split_once('-'))
+            .map_or("", |(_, name)| name);
+
+        self.restricted_paths
+            .0
+            .iter()
+            .any(|glob| WildMatch::new(glob).matches(name))
+    }
+}
+
+impl RetiringKeypair {
+    /// Returns whether the keypair has been retired.
+    pub fn is_retired(&self, now: ChronoDateTimeUtc) -> bool {
+        self.retire_at.is_some_and(|retire_at| retire_at <= now)
     }
 }
 
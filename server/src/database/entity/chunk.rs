@@ -91,6 +91,12 @@ pub struct Model {
     #[sea_orm(unique)]
     pub remote_file_id: String,
 
+    /// The sealed data key the chunk is encrypted with.
+    ///
+    /// If unset, the chunk is stored unencrypted.
+    #[sea_orm(column_type = "Text", nullable)]
+    pub encryption_key: Option<String>,
+
     /// Number of processes holding this chunk.
     ///
     /// This is for preventing garbage collection of chunks when
@@ -98,6 +104,17 @@ pub struct Model {
     /// there are no existing NAR references.
     pub holders_count: i32,
 
+    /// Number of failed attempts to delete the chunk from storage.
+    ///
+    /// This is only meaningful for chunks in the `Deleted` state.
+    pub deletion_failures: i32,
+
+    /// Timestamp after which deletion will be retried.
+    ///
+    /// If unset, the chunk will be deleted in the next
+    /// garbage collection run.
+    pub next_deletion_attempt_at: Option<ChronoDateTimeUtc>,
+
     /// Timestamp when the chunk is created.
     pub created_at: ChronoDateTimeUtc,
 }
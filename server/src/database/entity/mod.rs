@@ -2,14 +2,19 @@
 //!
 //! We use SeaORM and target PostgreSQL (production) and SQLite (development).
 
+pub mod audit_log;
+pub mod build_log;
 pub mod cache;
 pub mod chunk;
 pub mod chunkref;
 pub mod nar;
+pub mod nar_listing;
 pub mod object;
+pub mod pin;
+pub mod token;
 
 use sea_orm::entity::Value;
-use sea_orm::sea_query::{ArrayType, ColumnType, ValueType, ValueTypeErr};
+use sea_orm::sea_query::{ArrayType, ColumnType, Nullable, ValueType, ValueTypeErr};
 use sea_orm::{DbErr, QueryResult, TryGetError, TryGetable};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
@@ -30,7 +35,7 @@ impl<T: Serialize + DeserializeOwned> From<Json<T>> for Value {
 
 impl<T: Serialize + DeserializeOwned> TryGetable for Json<T> {
     fn try_get_by<I: sea_orm::ColIdx>(res: &QueryResult, idx: I) -> Result<Self, TryGetError> {
-        let json_str: String = res.try_get_by(idx).map_err(TryGetError::DbErr)?;
+        let json_str = String::try_get_by(res, idx)?;
 
         serde_json::from_str(&json_str).map_err(|e| TryGetError::DbErr(DbErr::Json(e.to_string())))
     }
@@ -56,3 +61,9 @@ impl<T: Serialize + DeserializeOwned> ValueType for Json<T> {
         ArrayType::String
     }
 }
+
+impl<T: Serialize + DeserializeOwned> Nullable for Json<T> {
+    fn null() -> Value {
+        Value::String(None)
+    }
+}
@@ -0,0 +1,44 @@
+//! A cached file listing of a NAR.
+
+use sea_orm::entity::prelude::*;
+
+pub type NarListingModel = Model;
+
+/// A cached file listing of a NAR.
+///
+/// Listings are generated lazily on the first request to
+/// `{storePathHash}.ls` and stored here so subsequent requests
+/// don't need to reassemble the NAR. Since the contents of a
+/// `Valid` NAR never change, a listing never needs to be invalidated.
+#[derive(Debug, Clone, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "nar_listing")]
+pub struct Model {
+    /// ID of the NAR this listing describes.
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub nar_id: i64,
+
+    /// The listing in the `.ls` JSON format.
+    #[sea_orm(column_type = "Text")]
+    pub listing: String,
+
+    /// Timestamp when the listing is generated.
+    pub created_at: ChronoDateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::nar::Entity",
+        from = "Column::NarId",
+        to = "super::nar::Column::Id"
+    )]
+    Nar,
+}
+
+impl Related<super::nar::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Nar.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
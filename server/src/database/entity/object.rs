@@ -5,12 +5,12 @@
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use sea_orm::Insert;
 use sea_orm::entity::prelude::*;
 use sea_orm::sea_query::OnConflict;
+use sea_orm::Insert;
 
-use super::Json;
 use super::nar::NarModel;
+use super::Json;
 use crate::error::{ServerError, ServerResult};
 use crate::narinfo::{Compression, NarInfo};
 use bunker::hash::Hash;
@@ -44,6 +44,10 @@ pub struct Model {
     pub last_accessed_at: Option<ChronoDateTimeUtc>,
 
     pub created_by: Option<String>,
+
+    /// Whether a token is required to pull the object, even if
+    /// the cache is public.
+    pub is_restricted: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -78,6 +82,7 @@ impl InsertExt for Insert<ActiveModel> {
                     Column::CreatedAt,
                     Column::LastAccessedAt,
                     Column::CreatedBy,
+                    Column::IsRestricted,
                 ])
                 .to_owned(),
         )
@@ -104,7 +109,7 @@ impl Model {
             system: self.system.to_owned(),
             references: self.references.0.to_owned(),
             deriver: self.deriver.to_owned(),
-            signature: None,
+            signatures: self.sigs.0.to_owned(),
             ca: self.ca.to_owned(),
         })
     }
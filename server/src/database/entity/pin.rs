@@ -0,0 +1,65 @@
+//! A pinned store path in a local cache.
+
+use sea_orm::entity::prelude::*;
+
+pub type PinModel = Model;
+
+/// A pinned store path in a local cache.
+///
+/// Pinned objects and their closures are treated as garbage
+/// collection roots and are never deleted by time-based
+/// garbage collection until the pin is removed or expires.
+#[derive(Debug, Clone, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "pin")]
+pub struct Model {
+    /// Unique numeric ID of the pin.
+    #[sea_orm(primary_key)]
+    pub id: i64,
+
+    /// ID of the cache the pin belongs to.
+    #[sea_orm(indexed)]
+    pub cache_id: i64,
+
+    /// The hash portion of the pinned store path.
+    #[sea_orm(column_type = "String(Some(32))")]
+    pub store_path_hash: String,
+
+    /// An optional human-readable label.
+    pub label: Option<String>,
+
+    /// Timestamp when the pin expires.
+    ///
+    /// If null, the pin never expires.
+    pub expires_at: Option<ChronoDateTimeUtc>,
+
+    /// Timestamp when the pin is created.
+    pub created_at: ChronoDateTimeUtc,
+
+    /// The user that created the pin.
+    pub created_by: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::cache::Entity",
+        from = "Column::CacheId",
+        to = "super::cache::Column::Id"
+    )]
+    Cache,
+}
+
+impl Model {
+    /// Returns whether the pin has expired.
+    pub fn is_expired(&self, now: ChronoDateTimeUtc) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+impl Related<super::cache::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Cache.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,60 @@
+//! An issued access token.
+
+use sea_orm::entity::prelude::*;
+
+pub type TokenModel = Model;
+
+/// An access token known to the server.
+///
+/// Tokens issued with `bunkeradm make-token` are recorded here.
+/// Tokens can also be revoked without having been recorded, in
+/// which case only the ID is known.
+#[derive(Debug, Clone, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "token")]
+pub struct Model {
+    /// Unique numeric ID of the row.
+    #[sea_orm(primary_key)]
+    pub id: i64,
+
+    /// The ID of the token.
+    ///
+    /// This is the `jti` claim of the JWT.
+    #[sea_orm(unique)]
+    pub jti: String,
+
+    /// The subject of the token.
+    pub subject: Option<String>,
+
+    /// The ID of the token this token was issued on behalf of.
+    ///
+    /// Tokens are rejected if any of their ancestors is revoked.
+    pub parent_jti: Option<String>,
+
+    /// The claims of the token in JSON format.
+    #[sea_orm(column_type = "Text", nullable)]
+    pub claims: Option<String>,
+
+    /// Timestamp when the token expires.
+    pub expires_at: Option<ChronoDateTimeUtc>,
+
+    /// Timestamp when the token is recorded.
+    pub created_at: ChronoDateTimeUtc,
+
+    /// Timestamp when the token is revoked.
+    ///
+    /// Revoked tokens are rejected even if their signatures
+    /// are valid.
+    pub revoked_at: Option<ChronoDateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl Model {
+    /// Returns whether the token has expired.
+    pub fn is_expired(&self, now: ChronoDateTimeUtc) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+use crate::database::entity::build_log::*;
+use crate::database::entity::cache;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20261018_000002_add_build_log_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Entity)
+                    .col(
+                        ColumnDef::new(Column::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Column::CacheId).big_integer().null())
+                    .col(ColumnDef::new(Column::DrvName).string().not_null())
+                    .col(ColumnDef::new(Column::Compression).string().not_null())
+                    .col(ColumnDef::new(Column::RemoteFile).string().not_null())
+                    .col(
+                        ColumnDef::new(Column::RemoteFileId)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Column::LogSize).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(Column::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Column::CreatedBy).string().null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk_build_log_cache")
+                            .from_tbl(Entity)
+                            .from_col(Column::CacheId)
+                            .to_tbl(cache::Entity)
+                            .to_col(cache::Column::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-build-log-cache-drv")
+                    .table(Entity)
+                    .col(Column::CacheId)
+                    .col(Column::DrvName)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+}
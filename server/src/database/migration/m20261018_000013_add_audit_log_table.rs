@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+use crate::database::entity::audit_log::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20261018_000013_add_audit_log_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Entity)
+                    .col(
+                        ColumnDef::new(Column::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Column::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Column::Subject).string().null())
+                    .col(ColumnDef::new(Column::TokenId).string().null())
+                    .col(ColumnDef::new(Column::Action).string_len(32).not_null())
+                    .col(ColumnDef::new(Column::Cache).string_len(50).null())
+                    .col(ColumnDef::new(Column::Target).text().null())
+                    .col(
+                        ColumnDef::new(Column::PrevHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Column::Hash).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-audit-log-cache")
+                    .table(Entity)
+                    .col(Column::Cache)
+                    .to_owned(),
+            )
+            .await
+    }
+}
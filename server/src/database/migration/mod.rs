@@ -14,6 +14,20 @@ mod m20230112_000003_add_nar_num_chunks;
 mod m20230112_000004_migrate_nar_remote_files_to_chunks;
 mod m20230112_000005_drop_old_nar_columns;
 mod m20230112_000006_add_nar_completeness_hint;
+mod m20261018_000001_add_nar_listing_table;
+mod m20261018_000002_add_build_log_table;
+mod m20261018_000003_add_cache_upstream_proxy;
+mod m20261018_000004_add_cache_pqc_keypair;
+mod m20261018_000005_add_cache_retiring_keypairs;
+mod m20261018_000006_add_chunk_encryption_key;
+mod m20261018_000007_add_pin_table;
+mod m20261018_000008_add_cache_quota;
+mod m20261018_000009_add_chunk_deletion_failures;
+mod m20261018_000010_add_token_table;
+mod m20261018_000011_add_object_is_restricted;
+mod m20261018_000012_add_cache_restricted_paths;
+mod m20261018_000013_add_audit_log_table;
+mod m20261018_000014_add_token_parent_jti;
 
 pub struct Migrator;
 
@@ -33,6 +47,20 @@ impl MigratorTrait for Migrator {
             Box::new(m20230112_000004_migrate_nar_remote_files_to_chunks::Migration),
             Box::new(m20230112_000005_drop_old_nar_columns::Migration),
             Box::new(m20230112_000006_add_nar_completeness_hint::Migration),
+            Box::new(m20261018_000001_add_nar_listing_table::Migration),
+            Box::new(m20261018_000002_add_build_log_table::Migration),
+            Box::new(m20261018_000003_add_cache_upstream_proxy::Migration),
+            Box::new(m20261018_000004_add_cache_pqc_keypair::Migration),
+            Box::new(m20261018_000005_add_cache_retiring_keypairs::Migration),
+            Box::new(m20261018_000006_add_chunk_encryption_key::Migration),
+            Box::new(m20261018_000007_add_pin_table::Migration),
+            Box::new(m20261018_000008_add_cache_quota::Migration),
+            Box::new(m20261018_000009_add_chunk_deletion_failures::Migration),
+            Box::new(m20261018_000010_add_token_table::Migration),
+            Box::new(m20261018_000011_add_object_is_restricted::Migration),
+            Box::new(m20261018_000012_add_cache_restricted_paths::Migration),
+            Box::new(m20261018_000013_add_audit_log_table::Migration),
+            Box::new(m20261018_000014_add_token_parent_jti::Migration),
         ]
     }
 }
@@ -9,7 +9,7 @@ use chrono::Utc;
 use sea_orm::entity::prelude::*;
 use sea_orm::entity::Iterable as EnumIterable;
 use sea_orm::query::{JoinType, QueryOrder, QuerySelect, QueryTrait};
-use sea_orm::sea_query::{Expr, LockBehavior, LockType, Query, Value};
+use sea_orm::sea_query::{Alias, Expr, LockBehavior, LockType, Query, Value};
 use sea_orm::{ActiveValue::Set, ConnectionTrait, DatabaseConnection, FromQueryResult};
 use tokio::task;
 
@@ -23,6 +23,7 @@ use entity::chunk::{self, ChunkModel, ChunkState, Entity as Chunk};
 use entity::chunkref;
 use entity::nar::{self, Entity as Nar, NarModel, NarState};
 use entity::object::{self, Entity as Object, ObjectModel};
+use entity::token::{self, Entity as Token};
 
 // quintuple join time
 const SELECT_OBJECT: &str = "O_";
@@ -31,6 +32,11 @@ const SELECT_NAR: &str = "N_";
 const SELECT_CHUNK: &str = "CH_";
 const SELECT_CHUNKREF: &str = "CHR_";
 
+/// Maximum number of ancestors to check when checking whether a token is revoked.
+///
+/// Tokens with deeper chains are treated as revoked.
+const MAX_TOKEN_ANCESTORS: usize = 64;
+
 #[async_trait]
 pub trait BunkerDatabase: Send + Sync {
     /// Retrieves an object in a binary cache by its store path hash, returning all its
@@ -57,6 +63,16 @@ pub trait BunkerDatabase: Send + Sync {
 
     /// Bumps the last accessed timestamp of an object.
     async fn bump_object_last_accessed(&self, object_id: i64) -> ServerResult<()>;
+
+    /// Returns the storage usage of a cache.
+    ///
+    /// This is the sum of the NAR sizes of all objects in the cache.
+    async fn get_cache_usage(&self, cache_id: i64) -> ServerResult<i64>;
+
+    /// Returns whether a token has been revoked.
+    ///
+    /// Tokens issued on behalf of revoked tokens are revoked as well.
+    async fn is_token_revoked(&self, jti: &str) -> ServerResult<bool>;
 }
 
 pub struct NarGuard {
@@ -319,6 +335,54 @@ impl BunkerDatabase for DatabaseConnection {
 
         Ok(())
     }
+
+    async fn get_cache_usage(&self, cache_id: i64) -> ServerResult<i64> {
+        // SUM() over BIGINT is NUMERIC in PostgreSQL
+        let usage = nar::Column::NarSize
+            .into_expr()
+            .sum()
+            .cast_as(Alias::new("BIGINT"));
+
+        let usage: Option<i64> = Object::find()
+            .select_only()
+            .column_as(usage, "usage")
+            .join(JoinType::InnerJoin, object::Relation::Nar.def())
+            .filter(object::Column::CacheId.eq(cache_id))
+            .into_tuple()
+            .one(self)
+            .await
+            .map_err(ServerError::database_error)?
+            .flatten();
+
+        Ok(usage.unwrap_or(0))
+    }
+
+    async fn is_token_revoked(&self, jti: &str) -> ServerResult<bool> {
+        let mut jti = jti.to_owned();
+
+        for _ in 0..=MAX_TOKEN_ANCESTORS {
+            let Some(record) = Token::find()
+                .filter(token::Column::Jti.eq(jti.as_str()))
+                .one(self)
+                .await
+                .map_err(ServerError::database_error)?
+            else {
+                return Ok(false);
+            };
+
+            if record.revoked_at.is_some() {
+                return Ok(true);
+            }
+
+            match record.parent_jti {
+                Some(parent_jti) => jti = parent_jti,
+                None => return Ok(false),
+            }
+        }
+
+        tracing::warn!("Token has more than {} ancestors", MAX_TOKEN_ANCESTORS);
+        Ok(true)
+    }
 }
 
 impl Deref for NarGuard {
@@ -0,0 +1,250 @@
+//! Encryption at rest.
+//!
+//! Chunks are protected with envelope encryption. Each chunk is
+//! encrypted with its own random data key, which is sealed with the
+//! per-deployment master key and stored in the `chunk` table. The
+//! storage backend only ever sees ciphertext.
+//!
+//! Deduplication is unaffected since chunks are still identified by
+//! the hash of their plaintext.
+//!
+//! ## Format
+//!
+//! Encryption happens after compression, so the file hash and size
+//! recorded for a chunk describe the compressed plaintext that clients
+//! receive.
+//!
+//! The compressed chunk is split into segments of 64 KiB, each sealed
+//! with AES-256-GCM. The nonce of a segment is its big-endian index
+//! followed by a byte that is set on the final segment, which is always
+//! shorter than a full segment and may be empty. This prevents
+//! segments from being reordered or the file from being truncated.
+//!
+//! Sealed data keys are stored as the base64 encoding of a random nonce
+//! followed by the sealed key.
+
+#[cfg(test)]
+mod tests;
+
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+
+use anyhow::anyhow;
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use bytes::{Bytes, BytesMut};
+use futures::stream;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::io::AsyncRead;
+use tokio_util::io::StreamReader;
+
+use crate::database::entity::chunk::ChunkModel;
+use crate::error::{ErrorKind, ServerResult};
+use bunker::stream::read_chunk_async;
+
+/// Length of the master key and data keys, in bytes.
+pub const KEY_LEN: usize = 32;
+
+/// Size of a plaintext segment.
+const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// Length of the authentication tag of a segment.
+const TAG_LEN: usize = 16;
+
+/// Associated data for sealed data keys.
+const DATA_KEY_AAD: &[u8] = b"bunker-chunk-data-key";
+
+/// The per-deployment master key.
+#[derive(Debug)]
+pub struct MasterKey {
+    key: LessSafeKey,
+}
+
+/// A key that a single chunk is encrypted with.
+#[derive(Debug)]
+pub struct DataKey {
+    key: LessSafeKey,
+}
+
+/// State of a segmented stream.
+struct Segments<R> {
+    reader: R,
+    key: DataKey,
+    index: u64,
+    finished: bool,
+}
+
+impl MasterKey {
+    pub fn new(key: &[u8; KEY_LEN]) -> Self {
+        Self { key: new_key(key) }
+    }
+
+    /// Generates a new data key, returning it along with its sealed form.
+    pub fn generate_data_key(&self) -> ServerResult<(DataKey, String)> {
+        let rng = SystemRandom::new();
+
+        let mut key = [0u8; KEY_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut key)
+            .and_then(|_| rng.fill(&mut nonce))
+            .map_err(|_| ErrorKind::StorageError(anyhow!("Failed to generate data key")))?;
+
+        let mut sealed = key.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(DATA_KEY_AAD),
+                &mut sealed,
+            )
+            .map_err(|_| ErrorKind::StorageError(anyhow!("Failed to seal data key")))?;
+
+        let mut encoded = nonce.to_vec();
+        encoded.extend(sealed);
+
+        Ok((
+            DataKey { key: new_key(&key) },
+            BASE64_STANDARD.encode(encoded),
+        ))
+    }
+
+    /// Opens a sealed data key.
+    pub fn open_data_key(&self, sealed: &str) -> ServerResult<DataKey> {
+        let invalid = || ErrorKind::StorageError(anyhow!("Failed to open data key"));
+
+        let mut decoded = BASE64_STANDARD.decode(sealed).map_err(|_| invalid())?;
+        if decoded.len() != NONCE_LEN + KEY_LEN + TAG_LEN {
+            return Err(invalid().into());
+        }
+
+        let mut sealed = decoded.split_off(NONCE_LEN);
+        let nonce: [u8; NONCE_LEN] = decoded.try_into().unwrap();
+
+        let key = self
+            .key
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(DATA_KEY_AAD),
+                &mut sealed,
+            )
+            .map_err(|_| invalid())?;
+        let key: &[u8; KEY_LEN] = (&*key).try_into().unwrap();
+
+        Ok(DataKey { key: new_key(key) })
+    }
+}
+
+impl<R: AsyncRead + Unpin + Send> Segments<R> {
+    fn new(reader: R, key: DataKey) -> Self {
+        Self {
+            reader,
+            key,
+            index: 0,
+            finished: false,
+        }
+    }
+
+    /// Returns the nonce of the next segment.
+    fn next_nonce(&mut self, last: bool) -> Result<Nonce, IoError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[NONCE_LEN - 9..NONCE_LEN - 1].copy_from_slice(&self.index.to_be_bytes());
+        nonce[NONCE_LEN - 1] = last as u8;
+
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or_else(|| IoError::other("Too many segments"))?;
+
+        Ok(Nonce::assume_unique_for_key(nonce))
+    }
+
+    /// Reads and seals the next segment.
+    async fn seal_next(mut self) -> Result<Option<(Bytes, Self)>, IoError> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let segment =
+            read_chunk_async(&mut self.reader, BytesMut::with_capacity(SEGMENT_SIZE)).await?;
+        let last = segment.len() < SEGMENT_SIZE;
+
+        let nonce = self.next_nonce(last)?;
+        let mut segment = segment.to_vec();
+        self.key
+            .key
+            .seal_in_place_append_tag(nonce, Aad::empty(), &mut segment)
+            .map_err(|_| IoError::other("Failed to seal segment"))?;
+
+        self.finished = last;
+        Ok(Some((Bytes::from(segment), self)))
+    }
+
+    /// Reads and opens the next segment.
+    async fn open_next(mut self) -> Result<Option<(Bytes, Self)>, IoError> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let segment = read_chunk_async(
+            &mut self.reader,
+            BytesMut::with_capacity(SEGMENT_SIZE + TAG_LEN),
+        )
+        .await?;
+        let last = segment.len() < SEGMENT_SIZE + TAG_LEN;
+
+        let nonce = self.next_nonce(last)?;
+        let mut segment = segment.to_vec();
+        let len = self
+            .key
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut segment)
+            .map_err(|_| IoError::new(IoErrorKind::InvalidData, "Failed to open segment"))?
+            .len();
+        segment.truncate(len);
+
+        self.finished = last;
+        Ok(Some((Bytes::from(segment), self)))
+    }
+}
+
+/// Returns a stream that encrypts the input with a data key.
+pub fn encrypt_stream<R>(reader: R, key: DataKey) -> impl AsyncRead + Unpin + Send
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let segments = stream::try_unfold(Segments::new(reader, key), Segments::seal_next);
+    StreamReader::new(Box::pin(segments))
+}
+
+/// Returns a stream that decrypts the input with a data key.
+pub fn decrypt_stream<R>(reader: R, key: DataKey) -> impl AsyncRead + Unpin + Send
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let segments = stream::try_unfold(Segments::new(reader, key), Segments::open_next);
+    StreamReader::new(Box::pin(segments))
+}
+
+/// Returns the stored contents of a chunk, decrypting them if needed.
+pub fn open_chunk(
+    master_key: Option<&MasterKey>,
+    chunk: &ChunkModel,
+    stream: Box<dyn AsyncRead + Unpin + Send>,
+) -> ServerResult<Box<dyn AsyncRead + Unpin + Send>> {
+    let Some(sealed_key) = &chunk.encryption_key else {
+        return Ok(stream);
+    };
+
+    let master_key = master_key.ok_or_else(|| {
+        ErrorKind::StorageError(anyhow!(
+            "Chunk {} is encrypted, but no master key is configured",
+            chunk.id
+        ))
+    })?;
+
+    let key = master_key.open_data_key(sealed_key)?;
+    Ok(Box::new(decrypt_stream(stream, key)))
+}
+
+fn new_key(key: &[u8; KEY_LEN]) -> LessSafeKey {
+    // Only fails if the key length is wrong
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).unwrap())
+}
@@ -0,0 +1,91 @@
+use super::*;
+
+use tokio::io::AsyncReadExt;
+
+async fn roundtrip(master_key: &MasterKey, data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let (key, sealed_key) = master_key.generate_data_key().unwrap();
+
+    let mut ciphertext = Vec::new();
+    encrypt_stream(data, key)
+        .read_to_end(&mut ciphertext)
+        .await
+        .unwrap();
+
+    let key = master_key.open_data_key(&sealed_key).unwrap();
+    let mut plaintext = Vec::new();
+    decrypt_stream(ciphertext.as_slice(), key)
+        .read_to_end(&mut plaintext)
+        .await
+        .unwrap();
+
+    (ciphertext, plaintext)
+}
+
+#[tokio::test]
+async fn test_roundtrip() {
+    let master_key = MasterKey::new(&[1; KEY_LEN]);
+
+    for len in [
+        0,
+        1,
+        SEGMENT_SIZE - 1,
+        SEGMENT_SIZE,
+        SEGMENT_SIZE + 1,
+        3 * SEGMENT_SIZE,
+    ] {
+        let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        let (ciphertext, plaintext) = roundtrip(&master_key, &data).await;
+
+        let segments = len / SEGMENT_SIZE + 1;
+        assert_eq!(len + segments * TAG_LEN, ciphertext.len());
+        assert_eq!(data, plaintext);
+    }
+}
+
+#[tokio::test]
+async fn test_tampering() {
+    let master_key = MasterKey::new(&[1; KEY_LEN]);
+    let data = vec![42; 2 * SEGMENT_SIZE + 100];
+
+    let (key, sealed_key) = master_key.generate_data_key().unwrap();
+    let mut ciphertext = Vec::new();
+    encrypt_stream(data.as_slice(), key)
+        .read_to_end(&mut ciphertext)
+        .await
+        .unwrap();
+
+    let full_segment = SEGMENT_SIZE + TAG_LEN;
+    let flipped = {
+        let mut ciphertext = ciphertext.clone();
+        ciphertext[10] ^= 1;
+        ciphertext
+    };
+    let truncated = ciphertext[..2 * full_segment].to_vec();
+    let reordered = [
+        &ciphertext[full_segment..2 * full_segment],
+        &ciphertext[..full_segment],
+        &ciphertext[2 * full_segment..],
+    ]
+    .concat();
+
+    for tampered in [flipped, truncated, reordered] {
+        let key = master_key.open_data_key(&sealed_key).unwrap();
+        let mut plaintext = Vec::new();
+        decrypt_stream(tampered.as_slice(), key)
+            .read_to_end(&mut plaintext)
+            .await
+            .unwrap_err();
+    }
+}
+
+#[test]
+fn test_wrong_master_key() {
+    let master_key = MasterKey::new(&[1; KEY_LEN]);
+    let other_key = MasterKey::new(&[2; KEY_LEN]);
+
+    let (_, sealed_key) = master_key.generate_data_key().unwrap();
+
+    master_key.open_data_key(&sealed_key).unwrap();
+    other_key.open_data_key(&sealed_key).unwrap_err();
+    master_key.open_data_key("garbage").unwrap_err();
+}
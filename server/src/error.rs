@@ -5,10 +5,11 @@
 //! Error handling.
 use std::error::Error as StdError;
 use std::fmt;
+
 use anyhow::Error as AnyError;
-use axum::Json;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use axum::Json;
 use displaydoc::Display;
 use serde::Serialize;
 use tracing_error::SpanTrace;
@@ -44,6 +45,8 @@ pub enum ErrorKind {
     CacheAlreadyExists,
     /// The requested object does not exist.
     NoSuchObject,
+    /// The requested pin does not exist.
+    NoSuchPin,
     /// Invalid compression type "{name}".
     InvalidCompressionType { name: String },
     /// The requested NAR has missing chunks and needs to be repaired.
@@ -52,6 +55,10 @@ pub enum ErrorKind {
     DatabaseError(AnyError),
     /// Storage error: {0:#}
     StorageError(AnyError),
+    /// Upstream error: {0:#}
+    UpstreamError(AnyError),
+    /// Signer error: {0:#}
+    SignerError(AnyError),
     /// Manifest serialization error: {0}
     ManifestSerializationError(super::nix_manifest::Error),
     /// Access error: {0}
@@ -77,6 +84,15 @@ impl ServerError {
     pub fn request_error(error: impl StdError + Send + Sync + 'static) -> Self {
         ErrorKind::RequestError(AnyError::new(error)).into()
     }
+    pub fn upstream_error(error: impl StdError + Send + Sync + 'static) -> Self {
+        ErrorKind::UpstreamError(AnyError::new(error)).into()
+    }
+    pub fn signer_error(error: impl StdError + Send + Sync + 'static) -> Self {
+        ErrorKind::SignerError(AnyError::new(error)).into()
+    }
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
     pub fn set_discovery_permission(&mut self, perm: bool) {
         self.discovery_permission = perm;
     }
@@ -115,6 +131,8 @@ impl IntoResponse for ServerError {
             self.kind,
             ErrorKind::DatabaseError(_)
                 | ErrorKind::StorageError(_)
+                | ErrorKind::UpstreamError(_)
+                | ErrorKind::SignerError(_)
                 | ErrorKind::ManifestSerializationError(_)
                 | ErrorKind::BunkerError(_)
         ) {
@@ -145,6 +163,7 @@ impl ErrorKind {
             Self::Unauthorized => "Unauthorized",
             Self::InternalServerError => "InternalServerError",
             Self::NoSuchObject => "NoSuchObject",
+            Self::NoSuchPin => "NoSuchPin",
             Self::NoSuchCache => "NoSuchCache",
             Self::CacheAlreadyExists => "CacheAlreadyExists",
             Self::InvalidCompressionType { .. } => "InvalidCompressionType",
@@ -152,6 +171,8 @@ impl ErrorKind {
             Self::BunkerError(e) => e.name(),
             Self::DatabaseError(_) => "DatabaseError",
             Self::StorageError(_) => "StorageError",
+            Self::UpstreamError(_) => "UpstreamError",
+            Self::SignerError(_) => "SignerError",
             Self::ManifestSerializationError(_) => "ManifestSerializationError",
             Self::AccessError(_) => "AccessError",
             Self::RequestError(_) => "RequestError",
@@ -164,6 +185,7 @@ impl ErrorKind {
         match self {
             Self::NoSuchCache => Self::Unauthorized,
             Self::NoSuchObject => Self::Unauthorized,
+            Self::NoSuchPin => Self::Unauthorized,
             Self::AccessError(_) => Self::Unauthorized,
 
             _ => self,
@@ -176,6 +198,7 @@ impl ErrorKind {
 
             Self::DatabaseError(_) => Self::InternalServerError,
             Self::StorageError(_) => Self::InternalServerError,
+            Self::SignerError(_) => Self::InternalServerError,
             Self::ManifestSerializationError(_) => Self::InternalServerError,
 
             _ => self,
@@ -191,8 +214,10 @@ impl ErrorKind {
             Self::AccessError(_) => StatusCode::FORBIDDEN,
             Self::NoSuchCache => StatusCode::NOT_FOUND,
             Self::NoSuchObject => StatusCode::NOT_FOUND,
+            Self::NoSuchPin => StatusCode::NOT_FOUND,
             Self::CacheAlreadyExists => StatusCode::BAD_REQUEST,
             Self::IncompleteNar => StatusCode::SERVICE_UNAVAILABLE,
+            Self::UpstreamError(_) => StatusCode::BAD_GATEWAY,
             Self::ManifestSerializationError(_) => StatusCode::BAD_REQUEST,
             Self::RequestError(_) => StatusCode::BAD_REQUEST,
             Self::InvalidCompressionType { .. } => StatusCode::BAD_REQUEST,
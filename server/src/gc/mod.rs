@@ -0,0 +1,1039 @@
+// /qompassai/bunker/server/src/gc.rs
+// Qompass AI Bunker Server Garbage Collection
+// Copyright (C) 2025 Qompass AI, All rights reserved
+/////////////////////////////////////////////////////
+//! Garbage collection.
+//!
+//! Time-based garbage collection is closure-aware: an expired object
+//! is retained as long as a live object in the same cache references
+//! it, directly or transitively. Live objects are marked by following
+//! the `references` column before anything is deleted. Pinned store
+//! paths are roots as well, regardless of when they were last accessed.
+//!
+//! Caches with a quota are then brought under it by evicting the
+//! least-recently-accessed objects. Pinned closures are never evicted.
+//!
+//! Before orphans are reaped, NARs are repaired: broken chunk references
+//! are pointed at chunks that have been uploaded again, NARs with the
+//! same NAR hash left behind by racing uploads are merged, and the
+//! completeness hints are brought up to date.
+//!
+//! Build logs of destroyed caches are orphaned rather than deleted with
+//! the cache, so their files can be removed from the storage backend.
+
+#[cfg(test)]
+mod tests;
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{Duration as ChronoDuration, Utc};
+use futures::future::join_all;
+use sea_orm::entity::prelude::*;
+use sea_orm::query::{Condition, JoinType, QueryOrder, QuerySelect};
+use sea_orm::sea_query::{Expr, LockBehavior, LockType, Query, SelectStatement};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ConnectionTrait, DatabaseConnection, FromQueryResult, TransactionTrait};
+use tokio::sync::Semaphore;
+use tokio::time;
+use tracing::instrument;
+
+use super::{State, StateInner};
+use crate::config::Config;
+use crate::database::entity::build_log::{self, Entity as BuildLog};
+use crate::database::entity::cache::{self, Entity as Cache};
+use crate::database::entity::chunk::{self, ChunkState, Entity as Chunk};
+use crate::database::entity::chunkref::{self, Entity as ChunkRef};
+use crate::database::entity::nar::{self, Entity as Nar, NarState};
+use crate::database::entity::object::{self, Entity as Object};
+use crate::database::entity::pin::{self, Entity as Pin};
+use crate::database::entity::Json;
+use crate::metrics;
+
+/// Number of objects to delete in a single statement.
+///
+/// This stays within the bind parameter limits of all backends.
+const OBJECT_DELETION_BATCH_SIZE: usize = 500;
+
+/// Outcome of deleting chunks from storage.
+#[derive(Debug, Default)]
+pub struct ChunkDeletionReport {
+    /// Number of chunks deleted.
+    pub deleted: u64,
+
+    /// Number of chunks that failed to be deleted.
+    pub failed: u64,
+}
+
+/// Outcome of merging duplicate NARs.
+#[derive(Debug, Default)]
+struct NarMergeReport {
+    /// Number of duplicate NARs merged.
+    nars_merged: u64,
+
+    /// Number of objects pointed at the kept NAR.
+    objects_repointed: u64,
+
+    /// Number of missing chunks of the kept NAR filled in.
+    chunkrefs_merged: u64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CacheIdAndRetentionPeriod {
+    id: i64,
+    name: String,
+    retention_period: i32,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CacheIdAndQuota {
+    id: i64,
+    name: String,
+    quota: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct ObjectReferences {
+    id: i64,
+    store_path_hash: String,
+    references: Json<Vec<String>>,
+    created_at: ChronoDateTimeUtc,
+    last_accessed_at: Option<ChronoDateTimeUtc>,
+    nar_size: i64,
+}
+
+/// Runs garbage collection periodically.
+pub async fn run_garbage_collection(config: Config) {
+    let interval = config.garbage_collection.interval;
+
+    if interval == Duration::ZERO {
+        // disabled
+        return;
+    }
+
+    loop {
+        // We don't stop even if it errors
+        if let Err(e) = run_garbage_collection_once(config.clone()).await {
+            tracing::warn!("Garbage collection failed: {}", e);
+        }
+
+        time::sleep(interval).await;
+    }
+}
+
+/// Runs garbage collection once.
+#[instrument(skip_all)]
+pub async fn run_garbage_collection_once(config: Config) -> Result<()> {
+    tracing::info!("Running garbage collection...");
+
+    let state = StateInner::new(config).await;
+    let result = async {
+        run_reap_expired_pins(&state).await?;
+        run_time_based_garbage_collection(&state).await?;
+        run_quota_based_garbage_collection(&state).await?;
+        run_repair_nars(&state).await?;
+        run_reap_orphan_nars(&state).await?;
+        run_reap_orphan_chunks(&state).await?;
+        run_reap_orphan_build_logs(&state).await?;
+
+        Ok(())
+    }
+    .await;
+
+    metrics::record_gc_run(result.is_ok());
+
+    result
+}
+
+/// Retries deletion of chunks stuck in the Deleted state.
+///
+/// Unlike regular garbage collection, this ignores the backoff
+/// of chunks that failed to be deleted before.
+#[instrument(skip_all)]
+pub async fn run_retry_deleted_chunks(config: Config) -> Result<ChunkDeletionReport> {
+    tracing::info!("Retrying deletion of deleted chunks...");
+
+    let state = StateInner::new(config).await;
+    delete_deleted_chunks(&state, true).await
+}
+
+#[instrument(skip_all)]
+async fn run_time_based_garbage_collection(state: &State) -> Result<()> {
+    let db = state.database().await?;
+    let now = Utc::now();
+
+    let default_retention_period = state.config.garbage_collection.default_retention_period;
+    let retention_period =
+        cache::Column::RetentionPeriod.if_null(default_retention_period.as_secs() as i32);
+
+    // Find caches with retention periods set
+    let caches = Cache::find()
+        .select_only()
+        .column(cache::Column::Id)
+        .column(cache::Column::Name)
+        .column_as(retention_period.clone(), "retention_period")
+        .filter(retention_period.ne(0))
+        .into_model::<CacheIdAndRetentionPeriod>()
+        .all(db)
+        .await?;
+
+    tracing::info!(
+        "Found {} caches subject to time-based garbage collection",
+        caches.len()
+    );
+
+    let mut objects_deleted = 0;
+
+    for cache in caches {
+        let period = ChronoDuration::seconds(cache.retention_period.into());
+        let cutoff = now.checked_sub_signed(period).ok_or_else(|| {
+            anyhow!(
+                "Somehow subtracting retention period for cache {} underflowed",
+                cache.name
+            )
+        })?;
+
+        let objects = find_cache_objects(db, cache.id).await?;
+        let pinned = find_pinned_hashes(db, cache.id).await?;
+
+        let (unreachable, retained) = find_unreachable_objects(&objects, &pinned, cutoff);
+
+        let mut cache_objects_deleted = 0;
+        for batch in unreachable.chunks(OBJECT_DELETION_BATCH_SIZE) {
+            // The objects may have been accessed since they were marked
+            let deletion = Object::delete_many()
+                .filter(object::Column::Id.is_in(batch.iter().copied()))
+                .filter(object::Column::CreatedAt.lt(cutoff))
+                .filter(
+                    object::Column::LastAccessedAt
+                        .is_null()
+                        .or(object::Column::LastAccessedAt.lt(cutoff)),
+                )
+                // Paths may have been pinned since as well
+                .filter(object::Column::StorePathHash.not_in_subquery(pinned_hashes(cache.id)))
+                .exec(db)
+                .await?;
+
+            cache_objects_deleted += deletion.rows_affected;
+        }
+
+        tracing::info!(
+            "Deleted {} objects from {} (ID {}), retained {} expired objects referenced by live or pinned ones",
+            cache_objects_deleted,
+            cache.name,
+            cache.id,
+            retained
+        );
+        objects_deleted += cache_objects_deleted;
+    }
+
+    tracing::info!("Deleted {} objects in total", objects_deleted);
+    metrics::record_gc_deletions("objects", objects_deleted);
+
+    Ok(())
+}
+
+#[instrument(skip_all)]
+async fn run_quota_based_garbage_collection(state: &State) -> Result<()> {
+    let db = state.database().await?;
+
+    let caches = Cache::find()
+        .select_only()
+        .column(cache::Column::Id)
+        .column(cache::Column::Name)
+        .column(cache::Column::Quota)
+        .filter(cache::Column::Quota.is_not_null())
+        .into_model::<CacheIdAndQuota>()
+        .all(db)
+        .await?;
+
+    tracing::info!("Found {} caches with quotas", caches.len());
+
+    let mut objects_evicted = 0;
+
+    for cache in caches {
+        let objects = find_cache_objects(db, cache.id).await?;
+        let pinned = find_pinned_hashes(db, cache.id).await?;
+
+        let (evicted, usage) = find_objects_to_evict(&objects, &pinned, cache.quota);
+        if evicted.is_empty() {
+            continue;
+        }
+
+        let mut cache_objects_evicted = 0;
+        for batch in evicted.chunks(OBJECT_DELETION_BATCH_SIZE) {
+            let deletion = Object::delete_many()
+                .filter(object::Column::Id.is_in(batch.iter().copied()))
+                .filter(object::Column::StorePathHash.not_in_subquery(pinned_hashes(cache.id)))
+                .exec(db)
+                .await?;
+
+            cache_objects_evicted += deletion.rows_affected;
+        }
+
+        tracing::info!(
+            "Evicted {} objects from {} (ID {}), which used {} of {} bytes",
+            cache_objects_evicted,
+            cache.name,
+            cache.id,
+            usage,
+            cache.quota
+        );
+        objects_evicted += cache_objects_evicted;
+    }
+
+    tracing::info!("Evicted {} objects in total", objects_evicted);
+    metrics::record_gc_deletions("evicted_objects", objects_evicted);
+
+    Ok(())
+}
+
+/// Returns all objects in a cache along with their references.
+async fn find_cache_objects(
+    db: &DatabaseConnection,
+    cache_id: i64,
+) -> Result<Vec<ObjectReferences>> {
+    let objects = Object::find()
+        .select_only()
+        .column(object::Column::Id)
+        .column(object::Column::StorePathHash)
+        .column(object::Column::References)
+        .column(object::Column::CreatedAt)
+        .column(object::Column::LastAccessedAt)
+        .column(nar::Column::NarSize)
+        .join(JoinType::InnerJoin, object::Relation::Nar.def())
+        .filter(object::Column::CacheId.eq(cache_id))
+        .into_model::<ObjectReferences>()
+        .all(db)
+        .await?;
+
+    Ok(objects)
+}
+
+/// Finds the objects in a cache that must be kept when deleting store paths.
+///
+/// Returns the hashes of the objects in the closures of pinned paths, as
+/// well as the hashes of the objects in the closures of the objects that
+/// aren't being deleted.
+pub(crate) async fn find_protected_hashes(
+    db: &DatabaseConnection,
+    cache_id: i64,
+    deleting: &HashSet<String>,
+) -> Result<(HashSet<String>, HashSet<String>)> {
+    let objects = find_cache_objects(db, cache_id).await?;
+    let pinned = find_pinned_hashes(db, cache_id).await?;
+
+    let hashes = |marked: HashSet<i64>| -> HashSet<String> {
+        objects
+            .iter()
+            .filter(|object| marked.contains(&object.id))
+            .map(|object| object.store_path_hash.clone())
+            .collect()
+    };
+
+    let pinned = hashes(mark_reachable(
+        &objects,
+        objects
+            .iter()
+            .filter(|object| pinned.contains(&object.store_path_hash)),
+    ));
+    let referenced = hashes(mark_reachable(
+        &objects,
+        objects
+            .iter()
+            .filter(|object| !deleting.contains(&object.store_path_hash)),
+    ));
+
+    Ok((pinned, referenced))
+}
+
+/// Returns the hashes of the store paths pinned in a cache.
+async fn find_pinned_hashes(db: &DatabaseConnection, cache_id: i64) -> Result<HashSet<String>> {
+    let pinned = Pin::find()
+        .select_only()
+        .column(pin::Column::StorePathHash)
+        .filter(pin::Column::CacheId.eq(cache_id))
+        .into_tuple::<String>()
+        .all(db)
+        .await?;
+
+    Ok(pinned.into_iter().collect())
+}
+
+/// Returns a subquery selecting the hashes of the store paths pinned in a cache.
+fn pinned_hashes(cache_id: i64) -> SelectStatement {
+    Query::select()
+        .from(Pin)
+        .column(pin::Column::StorePathHash)
+        .and_where(pin::Column::CacheId.eq(cache_id))
+        .to_owned()
+}
+
+/// Finds expired objects that no live or pinned object depends on.
+///
+/// Returns the IDs of the unreachable expired objects, as well as the
+/// number of expired objects that are retained.
+fn find_unreachable_objects(
+    objects: &[ObjectReferences],
+    pinned: &HashSet<String>,
+    cutoff: ChronoDateTimeUtc,
+) -> (Vec<i64>, usize) {
+    let is_expired = |object: &ObjectReferences| {
+        object.created_at < cutoff
+            && object
+                .last_accessed_at
+                .is_none_or(|last_accessed_at| last_accessed_at < cutoff)
+    };
+
+    // Mark everything reachable from the live and pinned objects
+    let marked = mark_reachable(
+        objects,
+        objects
+            .iter()
+            .filter(|object| !is_expired(object) || pinned.contains(&object.store_path_hash)),
+    );
+
+    let expired = objects.iter().filter(|object| is_expired(object));
+    let (retained, unreachable): (Vec<_>, Vec<_>) =
+        expired.partition(|object| marked.contains(&object.id));
+
+    (
+        unreachable.into_iter().map(|object| object.id).collect(),
+        retained.len(),
+    )
+}
+
+/// Finds the least-recently-accessed objects to evict to bring a cache under its quota.
+///
+/// Returns the IDs of the objects to evict, as well as the current usage.
+/// An object is only evicted once no remaining object references it, so
+/// the closures of the objects that are kept stay complete. Pinned objects
+/// and their closures are never evicted, so the cache may remain over its
+/// quota.
+fn find_objects_to_evict(
+    objects: &[ObjectReferences],
+    pinned: &HashSet<String>,
+    quota: i64,
+) -> (Vec<i64>, i64) {
+    let usage: i64 = objects.iter().map(|object| object.nar_size).sum();
+    if usage <= quota {
+        return (Vec::new(), usage);
+    }
+
+    let protected = mark_reachable(
+        objects,
+        objects
+            .iter()
+            .filter(|object| pinned.contains(&object.store_path_hash)),
+    );
+
+    let by_hash = objects_by_hash(objects);
+    let by_id: HashMap<i64, &ObjectReferences> =
+        objects.iter().map(|object| (object.id, object)).collect();
+
+    // Number of other objects referencing each object
+    let mut referrers: HashMap<i64, usize> = HashMap::new();
+    for object in objects {
+        for referenced in references(object, &by_hash) {
+            if referenced.id != object.id {
+                *referrers.entry(referenced.id).or_default() += 1;
+            }
+        }
+    }
+
+    let access_key = |object: &ObjectReferences| {
+        Reverse((
+            object.last_accessed_at.unwrap_or(object.created_at),
+            object.id,
+        ))
+    };
+
+    let mut candidates: BinaryHeap<_> = objects
+        .iter()
+        .filter(|object| !protected.contains(&object.id) && !referrers.contains_key(&object.id))
+        .map(access_key)
+        .collect();
+
+    let mut remaining = usage;
+    let mut evicted = Vec::new();
+
+    while remaining > quota {
+        let Some(Reverse((_, id))) = candidates.pop() else {
+            break;
+        };
+        let object = by_id[&id];
+
+        evicted.push(object.id);
+        remaining -= object.nar_size;
+
+        for referenced in references(object, &by_hash) {
+            if referenced.id == object.id {
+                continue;
+            }
+
+            let Some(count) = referrers.get_mut(&referenced.id) else {
+                continue;
+            };
+            *count -= 1;
+
+            if *count == 0 && !protected.contains(&referenced.id) {
+                candidates.push(access_key(referenced));
+            }
+        }
+    }
+
+    (evicted, usage)
+}
+
+/// Marks the objects reachable from the roots by following their references.
+fn mark_reachable<'a>(
+    objects: &'a [ObjectReferences],
+    roots: impl Iterator<Item = &'a ObjectReferences>,
+) -> HashSet<i64> {
+    let by_hash = objects_by_hash(objects);
+
+    let mut marked: HashSet<i64> = HashSet::new();
+    let mut queue: Vec<&ObjectReferences> = roots.collect();
+
+    while let Some(object) = queue.pop() {
+        if !marked.insert(object.id) {
+            continue;
+        }
+
+        for referenced in references(object, &by_hash) {
+            if !marked.contains(&referenced.id) {
+                queue.push(referenced);
+            }
+        }
+    }
+
+    marked
+}
+
+/// Indexes objects by their store path hashes.
+fn objects_by_hash(objects: &[ObjectReferences]) -> HashMap<&str, &ObjectReferences> {
+    objects
+        .iter()
+        .map(|object| (object.store_path_hash.as_str(), object))
+        .collect()
+}
+
+/// Returns the objects in the same cache that an object references.
+fn references<'a>(
+    object: &'a ObjectReferences,
+    by_hash: &'a HashMap<&'a str, &'a ObjectReferences>,
+) -> impl Iterator<Item = &'a ObjectReferences> {
+    object.references.0.iter().filter_map(|reference| {
+        // References are store path base names
+        let hash = reference.get(..32).unwrap_or(reference);
+        by_hash.get(hash).copied()
+    })
+}
+
+#[instrument(skip_all)]
+async fn run_reap_expired_pins(state: &State) -> Result<()> {
+    let db = state.database().await?;
+
+    let deletion = Pin::delete_many()
+        .filter(pin::Column::ExpiresAt.lt(Utc::now()))
+        .exec(db)
+        .await?;
+
+    tracing::info!("Deleted {} expired pins", deletion.rows_affected);
+    metrics::record_gc_deletions("pins", deletion.rows_affected);
+
+    Ok(())
+}
+
+/// Repairs broken chunk references and merges duplicate NARs.
+///
+/// Merged NARs are left without objects and are reaped afterwards.
+#[instrument(skip_all)]
+async fn run_repair_nars(state: &State) -> Result<()> {
+    let db = state.database().await?;
+
+    // Point broken chunkrefs at chunks that have been uploaded again
+    let missing_chunks: Vec<(String, String)> = ChunkRef::find()
+        .select_only()
+        .column(chunkref::Column::ChunkHash)
+        .column(chunkref::Column::Compression)
+        .filter(chunkref::Column::ChunkId.is_null())
+        .distinct()
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut chunkrefs_repaired = 0;
+    for (chunk_hash, compression) in missing_chunks {
+        let chunk_id: Option<i64> = Chunk::find()
+            .select_only()
+            .column(chunk::Column::Id)
+            .filter(chunk::Column::State.eq(ChunkState::Valid))
+            .filter(chunk::Column::ChunkHash.eq(chunk_hash.as_str()))
+            .filter(chunk::Column::Compression.eq(compression.as_str()))
+            .into_tuple()
+            .one(db)
+            .await?;
+
+        if let Some(chunk_id) = chunk_id {
+            let repair = ChunkRef::update_many()
+                .col_expr(chunkref::Column::ChunkId, Expr::value(chunk_id))
+                .filter(chunkref::Column::ChunkId.is_null())
+                .filter(chunkref::Column::ChunkHash.eq(chunk_hash))
+                .filter(chunkref::Column::Compression.eq(compression))
+                .exec(db)
+                .await?;
+
+            chunkrefs_repaired += repair.rows_affected;
+        }
+    }
+
+    // Merge NARs with the same NAR hash from racing uploads
+    let duplicate_hashes: Vec<String> = Nar::find()
+        .select_only()
+        .column(nar::Column::NarHash)
+        .filter(nar::Column::State.eq(NarState::Valid))
+        .group_by(nar::Column::NarHash)
+        .having(Expr::expr(Expr::col(nar::Column::Id).count()).gt(1))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut merge = NarMergeReport::default();
+    for nar_hash in duplicate_hashes {
+        let report = merge_duplicate_nars(db, nar_hash).await?;
+
+        merge.nars_merged += report.nars_merged;
+        merge.objects_repointed += report.objects_repointed;
+        merge.chunkrefs_merged += report.chunkrefs_merged;
+    }
+
+    // Bring the completeness hints up to date
+    let marked_incomplete = Nar::update_many()
+        .col_expr(nar::Column::CompletenessHint, Expr::value(false))
+        .filter(nar::Column::CompletenessHint.eq(true))
+        .filter(nar::Column::Id.in_subquery(nars_with_missing_chunks()))
+        .exec(db)
+        .await?;
+
+    let marked_complete = Nar::update_many()
+        .col_expr(nar::Column::CompletenessHint, Expr::value(true))
+        .filter(nar::Column::CompletenessHint.eq(false))
+        .filter(nar::Column::State.eq(NarState::Valid))
+        .filter(nar::Column::Id.not_in_subquery(nars_with_missing_chunks()))
+        .exec(db)
+        .await?;
+
+    tracing::info!(
+        "Repaired {} chunkrefs, merged {} duplicate NARs ({} objects repointed, {} chunkrefs merged), marked {} NARs incomplete and {} NARs complete",
+        chunkrefs_repaired,
+        merge.nars_merged,
+        merge.objects_repointed,
+        merge.chunkrefs_merged,
+        marked_incomplete.rows_affected,
+        marked_complete.rows_affected
+    );
+    metrics::record_nar_repairs("chunkrefs_repaired", chunkrefs_repaired);
+    metrics::record_nar_repairs("nars_merged", merge.nars_merged);
+    metrics::record_nar_repairs("objects_repointed", merge.objects_repointed);
+    metrics::record_nar_repairs("chunkrefs_merged", merge.chunkrefs_merged);
+    metrics::record_nar_repairs("marked_incomplete", marked_incomplete.rows_affected);
+    metrics::record_nar_repairs("marked_complete", marked_complete.rows_affected);
+
+    Ok(())
+}
+
+/// Merges the valid NARs with the same NAR hash into one.
+///
+/// Missing chunks of the kept NAR are filled in from the duplicates
+/// where they were chunked identically, and objects are repointed to
+/// it. The NARs are locked while merging, and duplicates that are held
+/// by uploads are left for the next run. The merged duplicates become
+/// orphans that are reaped afterwards.
+async fn merge_duplicate_nars(db: &DatabaseConnection, nar_hash: String) -> Result<NarMergeReport> {
+    let txn = db.begin().await?;
+
+    let nars: Vec<(i64, i32)> = Nar::find()
+        .select_only()
+        .column(nar::Column::Id)
+        .column(nar::Column::HoldersCount)
+        .filter(nar::Column::State.eq(NarState::Valid))
+        .filter(nar::Column::NarHash.eq(nar_hash))
+        .order_by_asc(nar::Column::Id)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .into_tuple()
+        .all(&txn)
+        .await?;
+
+    let nar_ids: Vec<i64> = nars.iter().map(|(id, _)| *id).collect();
+
+    let incomplete: HashSet<i64> = ChunkRef::find()
+        .select_only()
+        .column(chunkref::Column::NarId)
+        .filter(chunkref::Column::NarId.is_in(nar_ids.iter().copied()))
+        .filter(chunkref::Column::ChunkId.is_null())
+        .distinct()
+        .into_tuple::<i64>()
+        .all(&txn)
+        .await?
+        .into_iter()
+        .collect();
+
+    let Some(kept) = choose_nar_to_keep(&nar_ids, &incomplete) else {
+        return Ok(NarMergeReport::default());
+    };
+
+    // An upload holding a duplicate is about to point an object at it
+    let duplicates: Vec<i64> = nars
+        .iter()
+        .filter(|(id, holders_count)| *id != kept && *holders_count == 0)
+        .map(|(id, _)| *id)
+        .collect();
+
+    if duplicates.is_empty() {
+        return Ok(NarMergeReport::default());
+    }
+
+    let mut report = NarMergeReport::default();
+
+    if incomplete.contains(&kept) {
+        let broken = ChunkRef::find()
+            .filter(chunkref::Column::NarId.eq(kept))
+            .filter(chunkref::Column::ChunkId.is_null())
+            .all(&txn)
+            .await?;
+
+        for chunkref in broken {
+            let replacement = ChunkRef::find()
+                .join(JoinType::InnerJoin, chunkref::Relation::Chunk.def())
+                .filter(chunkref::Column::NarId.is_in(duplicates.iter().copied()))
+                .filter(chunkref::Column::Seq.eq(chunkref.seq))
+                .filter(chunkref::Column::ChunkHash.eq(chunkref.chunk_hash.as_str()))
+                .filter(chunk::Column::State.eq(ChunkState::Valid))
+                .one(&txn)
+                .await?;
+
+            if let Some(replacement) = replacement {
+                ChunkRef::update(chunkref::ActiveModel {
+                    id: Set(chunkref.id),
+                    chunk_id: Set(replacement.chunk_id),
+                    compression: Set(replacement.compression),
+                    ..Default::default()
+                })
+                .exec(&txn)
+                .await?;
+
+                report.chunkrefs_merged += 1;
+            }
+        }
+    }
+
+    let repoint = Object::update_many()
+        .col_expr(object::Column::NarId, Expr::value(kept))
+        .filter(object::Column::NarId.is_in(duplicates.iter().copied()))
+        .exec(&txn)
+        .await?;
+
+    txn.commit().await?;
+
+    report.nars_merged = duplicates.len() as u64;
+    report.objects_repointed = repoint.rows_affected;
+
+    Ok(report)
+}
+
+/// Chooses the NAR to merge duplicates into.
+///
+/// The oldest NAR with all chunks available is preferred. If all of
+/// them are missing chunks, the oldest one is kept so a client upload
+/// can repair it.
+fn choose_nar_to_keep(nar_ids: &[i64], incomplete: &HashSet<i64>) -> Option<i64> {
+    nar_ids
+        .iter()
+        .copied()
+        .filter(|id| !incomplete.contains(id))
+        .min()
+        .or_else(|| nar_ids.iter().copied().min())
+}
+
+/// Returns a subquery selecting the IDs of NARs with missing chunks.
+fn nars_with_missing_chunks() -> SelectStatement {
+    Query::select()
+        .from(ChunkRef)
+        .column(chunkref::Column::NarId)
+        .and_where(chunkref::Column::ChunkId.is_null())
+        .to_owned()
+}
+
+#[instrument(skip_all)]
+async fn run_reap_orphan_nars(state: &State) -> Result<()> {
+    let db = state.database().await?;
+    // find all orphan NARs...
+    let orphan_nar_ids = Query::select()
+        .from(Nar)
+        .expr(nar::Column::Id.into_expr())
+        .left_join(
+            Object,
+            object::Column::NarId
+                .into_expr()
+                .eq(nar::Column::Id.into_expr()),
+        )
+        .and_where(object::Column::Id.is_null())
+        .and_where(nar::Column::State.eq(NarState::Valid))
+        .and_where(nar::Column::HoldersCount.eq(0))
+        .lock_with_tables_behavior(LockType::Update, [Nar], LockBehavior::SkipLocked)
+        .to_owned();
+    // ... and simply delete them
+    let deletion = Nar::delete_many()
+        .filter(nar::Column::Id.in_subquery(orphan_nar_ids))
+        .exec(db)
+        .await?;
+    tracing::info!("Deleted {} orphan NARs", deletion.rows_affected,);
+    metrics::record_gc_deletions("nars", deletion.rows_affected);
+    Ok(())
+}
+#[instrument(skip_all)]
+async fn run_reap_orphan_chunks(state: &State) -> Result<()> {
+    let db = state.database().await?;
+
+    // find all orphan chunks...
+    let orphan_chunk_ids = Query::select()
+        .from(Chunk)
+        .expr(chunk::Column::Id.into_expr())
+        .left_join(
+            ChunkRef,
+            chunkref::Column::ChunkId
+                .into_expr()
+                .eq(chunk::Column::Id.into_expr()),
+        )
+        .and_where(chunkref::Column::Id.is_null())
+        .and_where(chunk::Column::State.eq(ChunkState::Valid))
+        .and_where(chunk::Column::HoldersCount.eq(0))
+        .lock_with_tables_behavior(LockType::Update, [Chunk], LockBehavior::SkipLocked)
+        .to_owned();
+
+    // ... and transition their state to Deleted
+    //
+    // Deleted chunks are essentially invisible from our normal queries
+    let transition_statement = {
+        let change_state = Query::update()
+            .table(Chunk)
+            .value(chunk::Column::State, ChunkState::Deleted)
+            .and_where(chunk::Column::Id.in_subquery(orphan_chunk_ids))
+            .to_owned();
+        db.get_database_backend().build(&change_state)
+    };
+
+    db.execute(transition_statement).await?;
+
+    let report = delete_deleted_chunks(state, false).await?;
+
+    tracing::info!("Deleted {} orphan chunks", report.deleted);
+    if report.failed != 0 {
+        tracing::warn!(
+            "Failed to delete {} chunks, they will be retried later",
+            report.failed
+        );
+    }
+
+    Ok(())
+}
+
+/// Deletes build logs of destroyed caches from storage and the database.
+///
+/// Logs that fail to be deleted are retried in the next run.
+#[instrument(skip_all)]
+async fn run_reap_orphan_build_logs(state: &State) -> Result<()> {
+    let db = state.database().await?;
+    let storage = state.storage().await?;
+
+    let concurrency = state.config.garbage_collection.deletion_concurrency.max(1);
+    let delete_limit = Arc::new(Semaphore::new(concurrency));
+
+    let mut logs_deleted = 0;
+    let mut last_id = None;
+
+    loop {
+        let mut query = BuildLog::find()
+            .filter(build_log::Column::CacheId.is_null())
+            .order_by_asc(build_log::Column::Id)
+            .limit(OBJECT_DELETION_BATCH_SIZE as u64);
+
+        if let Some(last_id) = last_id {
+            query = query.filter(build_log::Column::Id.gt(last_id));
+        }
+
+        let orphan_logs: Vec<build_log::Model> = query.all(db).await?;
+
+        let Some(last) = orphan_logs.last() else {
+            break;
+        };
+        last_id = Some(last.id);
+
+        let futures: Vec<_> = orphan_logs
+            .into_iter()
+            .map(|log| {
+                let delete_limit = delete_limit.clone();
+                async move {
+                    let result = async {
+                        let _permit = delete_limit.acquire().await?;
+                        storage.delete_file_db(&log.remote_file.0).await?;
+                        Result::<_, anyhow::Error>::Ok(())
+                    }
+                    .await;
+
+                    (log.id, result)
+                }
+            })
+            .collect();
+
+        let mut deleted_log_ids = Vec::new();
+        for (id, result) in join_all(futures).await {
+            match result {
+                Ok(()) => deleted_log_ids.push(id),
+                Err(e) => tracing::warn!("Deletion of build log {} failed: {}", id, e),
+            }
+        }
+
+        if !deleted_log_ids.is_empty() {
+            let deletion = BuildLog::delete_many()
+                .filter(build_log::Column::Id.is_in(deleted_log_ids))
+                .exec(db)
+                .await?;
+
+            logs_deleted += deletion.rows_affected;
+        }
+    }
+
+    tracing::info!("Deleted {} orphan build logs", logs_deleted);
+    metrics::record_gc_deletions("build_logs", logs_deleted);
+
+    Ok(())
+}
+
+/// Deletes chunks in the Deleted state from storage and the database.
+///
+/// Chunks that failed to be deleted before are skipped until their
+/// backoff expires, unless `ignore_backoff` is set.
+async fn delete_deleted_chunks(state: &State, ignore_backoff: bool) -> Result<ChunkDeletionReport> {
+    let db = state.database().await?;
+    let storage = state.storage().await?;
+
+    let orphan_chunk_limit = match db.get_database_backend() {
+        // Arbitrarily chosen sensible value since there's no good default to choose from for MySQL
+        sea_orm::DatabaseBackend::MySql => 1000,
+        // Panic limit set by sqlx for postgresql: https://github.com/launchbadge/sqlx/issues/671#issuecomment-687043510
+        sea_orm::DatabaseBackend::Postgres => u64::from(u16::MAX),
+        // Default statement limit imposed by sqlite: https://www.sqlite.org/limits.html#max_variable_number
+        sea_orm::DatabaseBackend::Sqlite => 500,
+    };
+
+    let concurrency = state.config.garbage_collection.deletion_concurrency.max(1);
+    let delete_limit = Arc::new(Semaphore::new(concurrency));
+
+    let now = Utc::now();
+    let mut report = ChunkDeletionReport::default();
+    let mut last_id = None;
+
+    loop {
+        let mut query = Chunk::find()
+            .filter(chunk::Column::State.eq(ChunkState::Deleted))
+            .order_by_asc(chunk::Column::Id)
+            .limit(orphan_chunk_limit);
+
+        if !ignore_backoff {
+            query = query.filter(
+                Condition::any()
+                    .add(chunk::Column::NextDeletionAttemptAt.is_null())
+                    .add(chunk::Column::NextDeletionAttemptAt.lte(now)),
+            );
+        }
+
+        if let Some(last_id) = last_id {
+            query = query.filter(chunk::Column::Id.gt(last_id));
+        }
+
+        let orphan_chunks: Vec<chunk::Model> = query.all(db).await?;
+
+        let Some(last) = orphan_chunks.last() else {
+            break;
+        };
+        last_id = Some(last.id);
+
+        // Delete the chunks from remote storage
+        let futures: Vec<_> = orphan_chunks
+            .into_iter()
+            .map(|chunk| {
+                let delete_limit = delete_limit.clone();
+                async move {
+                    let result = async {
+                        let _permit = delete_limit.acquire().await?;
+                        storage.delete_file_db(&chunk.remote_file.0).await?;
+                        Result::<_, anyhow::Error>::Ok(())
+                    }
+                    .await;
+
+                    (chunk.id, chunk.deletion_failures, result)
+                }
+            })
+            .collect();
+
+        // Deletions can result in spurious failures, tolerate them
+        //
+        // Chunks that failed to be deleted from the remote storage
+        // stay in Deleted state and are retried with backoff.
+        let mut deleted_chunk_ids = Vec::new();
+        let mut failed_chunk_ids: HashMap<i32, Vec<i64>> = HashMap::new();
+        for (id, failures, result) in join_all(futures).await {
+            match result {
+                Ok(()) => deleted_chunk_ids.push(id),
+                Err(e) => {
+                    tracing::warn!("Deletion of chunk {} failed: {}", id, e);
+                    failed_chunk_ids
+                        .entry(failures.saturating_add(1))
+                        .or_default()
+                        .push(id);
+                }
+            }
+        }
+
+        // Schedule the next attempts for the failed chunks
+        for (failures, ids) in failed_chunk_ids {
+            report.failed += ids.len() as u64;
+
+            Chunk::update_many()
+                .col_expr(chunk::Column::DeletionFailures, Expr::value(failures))
+                .col_expr(
+                    chunk::Column::NextDeletionAttemptAt,
+                    Expr::value(now + deletion_backoff(failures)),
+                )
+                .filter(chunk::Column::Id.is_in(ids))
+                .exec(db)
+                .await?;
+        }
+
+        // Finally, delete them from the database
+        let deletion = Chunk::delete_many()
+            .filter(chunk::Column::Id.is_in(deleted_chunk_ids))
+            .exec(db)
+            .await?;
+
+        report.deleted += deletion.rows_affected;
+        metrics::record_gc_deletions("chunks", deletion.rows_affected);
+    }
+
+    Ok(report)
+}
+
+/// Returns how long to wait before retrying a failed chunk deletion.
+///
+/// The delay doubles with each failure, starting at one hour and
+/// capped at one week.
+fn deletion_backoff(failures: i32) -> ChronoDuration {
+    let exponent = (failures.max(1) - 1).min(8) as u32;
+
+    ChronoDuration::hours(1 << exponent).min(ChronoDuration::days(7))
+}
@@ -0,0 +1,300 @@
+use sea_orm::ActiveValue::NotSet;
+
+use super::*;
+use crate::testing::TestState;
+
+fn base_name(id: i64) -> String {
+    format!("{:0>32}-path-{}", id, id)
+}
+
+fn object(
+    id: i64,
+    references: &[i64],
+    last_accessed_at: Option<ChronoDateTimeUtc>,
+) -> ObjectReferences {
+    ObjectReferences {
+        id,
+        store_path_hash: format!("{:0>32}", id),
+        references: Json(references.iter().copied().map(base_name).collect()),
+        created_at: ChronoDateTimeUtc::MIN_UTC,
+        last_accessed_at,
+        nar_size: 100,
+    }
+}
+
+#[test]
+fn test_closure_retained() {
+    let now = Utc::now();
+    let cutoff = now - ChronoDuration::days(1);
+
+    let mut objects = vec![
+        object(1, &[1], None),
+        object(2, &[1], None),
+        // Recently accessed, keeps its closure alive
+        object(3, &[1, 2, 3], Some(now)),
+        // Expired and not referenced by anything live
+        object(4, &[1], None),
+        // Expired, only referenced by an expired object
+        object(5, &[], None),
+        object(6, &[5], None),
+    ];
+
+    let (mut unreachable, retained) = find_unreachable_objects(&objects, &HashSet::new(), cutoff);
+    unreachable.sort();
+
+    assert_eq!(vec![4, 5, 6], unreachable);
+    assert_eq!(2, retained);
+
+    // Once the top-level path expires, its whole closure can go
+    objects[2].last_accessed_at = None;
+
+    let (unreachable, retained) = find_unreachable_objects(&objects, &HashSet::new(), cutoff);
+    assert_eq!(6, unreachable.len());
+    assert_eq!(0, retained);
+}
+
+#[test]
+fn test_missing_references() {
+    let now = Utc::now();
+    let cutoff = now - ChronoDuration::days(1);
+
+    // References to paths that aren't in the cache are ignored
+    let mut live = object(1, &[99], Some(now));
+    live.references.0.push("bad".to_string());
+
+    let (unreachable, retained) = find_unreachable_objects(&[live], &HashSet::new(), cutoff);
+    assert!(unreachable.is_empty());
+    assert_eq!(0, retained);
+}
+
+#[test]
+fn test_pinned_closure_retained() {
+    let now = Utc::now();
+    let cutoff = now - ChronoDuration::days(1);
+
+    let objects = vec![
+        object(1, &[], None),
+        object(2, &[1], None),
+        // Expired but pinned, keeps its closure alive
+        object(3, &[2, 3], None),
+        object(4, &[1], None),
+    ];
+
+    let pinned = HashSet::from([format!("{:0>32}", 3)]);
+
+    let (unreachable, retained) = find_unreachable_objects(&objects, &pinned, cutoff);
+    assert_eq!(vec![4], unreachable);
+    assert_eq!(3, retained);
+}
+
+#[test]
+fn test_quota_eviction() {
+    let now = Utc::now();
+    let ago = |hours| Some(now - ChronoDuration::hours(hours));
+
+    let objects = vec![
+        object(1, &[], ago(5)),
+        object(2, &[], ago(1)),
+        object(3, &[], ago(4)),
+        object(4, &[], None),
+        // Least recently accessed, but pinned along with its closure
+        object(5, &[6], ago(10)),
+        object(6, &[], ago(10)),
+    ];
+
+    let (evicted, usage) = find_objects_to_evict(&objects, &HashSet::new(), 600);
+    assert!(evicted.is_empty());
+    assert_eq!(600, usage);
+
+    let pinned = HashSet::from([format!("{:0>32}", 5)]);
+
+    // Never-accessed objects count as accessed when they were created
+    let (evicted, usage) = find_objects_to_evict(&objects, &pinned, 350);
+    assert_eq!(vec![4, 1, 3], evicted);
+    assert_eq!(600, usage);
+
+    // Pinned closures are kept even if that leaves the cache over quota
+    let (evicted, _) = find_objects_to_evict(&objects, &pinned, 0);
+    assert_eq!(vec![4, 1, 3, 2], evicted);
+}
+
+#[test]
+fn test_quota_eviction_keeps_referenced_objects() {
+    let now = Utc::now();
+    let ago = |hours| Some(now - ChronoDuration::hours(hours));
+
+    let objects = vec![
+        // Least recently accessed, but referenced by 2
+        object(1, &[1], ago(10)),
+        object(2, &[1, 2], ago(1)),
+        object(3, &[], ago(5)),
+    ];
+
+    let (evicted, _) = find_objects_to_evict(&objects, &HashSet::new(), 200);
+    assert_eq!(vec![3], evicted);
+
+    // Once its referrer is gone, it can be evicted as well
+    let (evicted, _) = find_objects_to_evict(&objects, &HashSet::new(), 0);
+    assert_eq!(vec![3, 2, 1], evicted);
+}
+
+#[test]
+fn test_choose_nar_to_keep() {
+    // The oldest complete NAR is kept
+    let incomplete = HashSet::from([3]);
+    assert_eq!(Some(4), choose_nar_to_keep(&[3, 4, 5], &incomplete));
+    assert_eq!(Some(4), choose_nar_to_keep(&[5, 4], &incomplete));
+
+    // If all of them are incomplete, the oldest one is kept
+    let incomplete = HashSet::from([3, 4]);
+    assert_eq!(Some(3), choose_nar_to_keep(&[3, 4], &incomplete));
+
+    assert_eq!(None, choose_nar_to_keep(&[], &incomplete));
+}
+
+#[test]
+fn test_deletion_backoff() {
+    assert_eq!(ChronoDuration::hours(1), deletion_backoff(1));
+    assert_eq!(ChronoDuration::hours(2), deletion_backoff(2));
+    assert_eq!(ChronoDuration::hours(128), deletion_backoff(8));
+
+    // Capped at a week
+    assert_eq!(ChronoDuration::days(7), deletion_backoff(9));
+    assert_eq!(ChronoDuration::days(7), deletion_backoff(i32::MAX));
+}
+
+/// Returns a NAR large enough to be split into several chunks.
+fn large_nar() -> Vec<u8> {
+    (0..600_000u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+        .collect()
+}
+
+/// Copies a NAR along with its chunkrefs, returning the ID of the copy.
+async fn duplicate_nar(db: &DatabaseConnection, nar_id: i64) -> i64 {
+    let mut copy: nar::ActiveModel = Nar::find_by_id(nar_id)
+        .one(db)
+        .await
+        .unwrap()
+        .unwrap()
+        .into();
+    copy.id = NotSet;
+    let copy_id = Nar::insert(copy).exec(db).await.unwrap().last_insert_id;
+
+    for chunkref in chunkrefs(db, nar_id).await {
+        let mut copy: chunkref::ActiveModel = chunkref.into();
+        copy.id = NotSet;
+        copy.nar_id = Set(copy_id);
+        ChunkRef::insert(copy).exec(db).await.unwrap();
+    }
+
+    copy_id
+}
+
+async fn chunkrefs(db: &DatabaseConnection, nar_id: i64) -> Vec<chunkref::Model> {
+    ChunkRef::find()
+        .filter(chunkref::Column::NarId.eq(nar_id))
+        .order_by_asc(chunkref::Column::Seq)
+        .all(db)
+        .await
+        .unwrap()
+}
+
+/// Breaks the reference to a chunk so it can't be repaired by chunk hash.
+async fn break_chunkref(db: &DatabaseConnection, nar_id: i64, seq: i32) {
+    ChunkRef::update_many()
+        .col_expr(chunkref::Column::ChunkId, Expr::value(Option::<i64>::None))
+        .col_expr(chunkref::Column::Compression, Expr::value("bogus"))
+        .filter(chunkref::Column::NarId.eq(nar_id))
+        .filter(chunkref::Column::Seq.eq(seq))
+        .exec(db)
+        .await
+        .unwrap();
+}
+
+async fn object_nar_ids(db: &DatabaseConnection) -> Vec<i64> {
+    Object::find()
+        .select_only()
+        .column(object::Column::NarId)
+        .order_by_asc(object::Column::Id)
+        .into_tuple()
+        .all(db)
+        .await
+        .unwrap()
+}
+
+/// Returns a state with a path in two caches whose objects point at
+/// duplicate NARs that are each missing a different chunk.
+async fn setup_duplicate_nars() -> (TestState, i64, i64) {
+    let state = TestState::new().await;
+    let db = state.database().await.unwrap();
+
+    let path = "/nix/store/00000000000000000000000000000001-large";
+    let nar = large_nar();
+
+    let a = state.create_cache("a").await;
+    state.push(&a, path, &[], &nar).await;
+    let b = state.create_cache("b").await;
+    state.push(&b, path, &[], &nar).await;
+
+    let kept = Nar::find().one(db).await.unwrap().unwrap().id;
+    assert!(chunkrefs(db, kept).await.len() >= 2);
+
+    let duplicate = duplicate_nar(db, kept).await;
+    Object::update_many()
+        .col_expr(object::Column::NarId, Expr::value(duplicate))
+        .filter(object::Column::CacheId.eq(b.id))
+        .exec(db)
+        .await
+        .unwrap();
+
+    break_chunkref(db, kept, 0).await;
+    break_chunkref(db, duplicate, 1).await;
+
+    (state, kept, duplicate)
+}
+
+#[tokio::test]
+async fn test_merge_duplicate_nars() {
+    let (state, kept, duplicate) = setup_duplicate_nars().await;
+    let db = state.database().await.unwrap();
+
+    run_repair_nars(&state).await.unwrap();
+
+    // The missing chunk was filled in from the duplicate
+    let merged = chunkrefs(db, kept).await;
+    assert!(merged.iter().all(|chunkref| chunkref.chunk_id.is_some()));
+    assert_eq!(
+        chunkrefs(db, duplicate).await[0].chunk_id,
+        merged[0].chunk_id
+    );
+
+    assert_eq!(vec![kept, kept], object_nar_ids(db).await);
+
+    let kept_nar = Nar::find_by_id(kept).one(db).await.unwrap().unwrap();
+    assert!(kept_nar.completeness_hint);
+
+    // The duplicate is now an orphan
+    run_reap_orphan_nars(&state).await.unwrap();
+    assert!(Nar::find_by_id(duplicate).one(db).await.unwrap().is_none());
+    assert!(Nar::find_by_id(kept).one(db).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_merge_duplicate_nars_respects_holders() {
+    let (state, kept, duplicate) = setup_duplicate_nars().await;
+    let db = state.database().await.unwrap();
+
+    // An upload is deduplicating against the duplicate
+    Nar::update_many()
+        .col_expr(nar::Column::HoldersCount, Expr::value(1))
+        .filter(nar::Column::Id.eq(duplicate))
+        .exec(db)
+        .await
+        .unwrap();
+
+    run_repair_nars(&state).await.unwrap();
+
+    assert_eq!(vec![kept, duplicate], object_nar_ids(db).await);
+    assert!(chunkrefs(db, kept).await[0].chunk_id.is_none());
+}
@@ -19,46 +19,68 @@
 
 pub mod access;
 mod api;
+pub mod audit;
 pub mod config;
 pub mod database;
+mod encryption;
 pub mod error;
 pub mod gc;
+mod metrics;
 mod middleware;
+mod nar_listing;
 mod narinfo;
 pub mod nix_manifest;
 pub mod oobe;
+mod signer;
 mod storage;
+#[cfg(test)]
+mod testing;
+pub mod verify;
 
 use std::future::IntoFuture;
 use std::net::SocketAddr;
-use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
 use axum::{
-    Router,
     extract::Extension,
-    http::{Uri, uri::Scheme},
+    http::{uri::Scheme, Uri},
+    Router,
 };
-use sea_orm::{ConnectionTrait, Database, DatabaseConnection, query::Statement};
+use sea_orm::{query::Statement, ConnectionTrait, Database, DatabaseConnection};
 use tokio::net::TcpListener;
-use tokio::sync::OnceCell;
+use tokio::sync::{Mutex, OnceCell};
 use tokio::time;
 use tower_http::catch_panic::CatchPanicLayer;
 use tower_http::trace::TraceLayer;
 
-use access::http::{AuthState, apply_auth};
+use access::http::{apply_auth, AuthState};
+use access::oidc::OidcIssuer;
+
 use bunker::cache::CacheName;
+
 use config::{Config, StorageConfig};
 use database::migration::{Migrator, MigratorTrait};
+use encryption::MasterKey;
 use error::{ErrorKind, ServerError, ServerResult};
 use middleware::{init_request_state, restrict_host, set_visibility_header};
+use signer::UnixSocketSigner;
 use storage::{LocalBackend, S3Backend, StorageBackend};
 
 type State = Arc<StateInner>;
 type RequestState = Arc<RequestStateInner>;
 
+/// Timeout for establishing outgoing HTTP connections.
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Timeout for reads on outgoing HTTP connections.
+///
+/// This doesn't limit the total duration, so large NARs can still
+/// be streamed from upstream caches.
+const HTTP_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Global server state.
 #[derive(Debug)]
 pub struct StateInner {
@@ -70,6 +92,21 @@ pub struct StateInner {
 
     /// Handle to the storage backend.
     storage: OnceCell<Arc<Box<dyn StorageBackend>>>,
+
+    /// HTTP client for upstream substituters and OIDC issuers.
+    http_client: reqwest::Client,
+
+    /// The external signer, if configured.
+    signer: Option<Arc<UnixSocketSigner>>,
+
+    /// The master key for encryption at rest, if configured.
+    master_key: Option<Arc<MasterKey>>,
+
+    /// External OIDC issuers whose tokens are accepted.
+    oidc_issuers: Vec<OidcIssuer>,
+
+    /// Serializes appends to the audit log.
+    audit_lock: Mutex<()>,
 }
 
 /// Request state.
@@ -99,10 +136,38 @@ struct RequestStateInner {
 
 impl StateInner {
     async fn new(config: Config) -> State {
+        let signer = config
+            .signer
+            .as_ref()
+            .map(|signer| Arc::new(UnixSocketSigner::from_config(signer)));
+
+        let master_key = config
+            .encryption
+            .as_ref()
+            .map(|encryption| Arc::new(MasterKey::new(&encryption.master_key)));
+
+        let oidc_issuers = config
+            .jwt
+            .oidc
+            .iter()
+            .map(|oidc| OidcIssuer::new(oidc.clone()))
+            .collect();
+
+        let http_client = reqwest::Client::builder()
+            .connect_timeout(HTTP_CONNECT_TIMEOUT)
+            .read_timeout(HTTP_READ_TIMEOUT)
+            .build()
+            .expect("Failed to initialize the HTTP client");
+
         Arc::new(Self {
             config,
             database: OnceCell::new(),
             storage: OnceCell::new(),
+            http_client,
+            signer,
+            master_key,
+            oidc_issuers,
+            audit_lock: Mutex::new(()),
         })
     }
 
@@ -231,8 +296,13 @@ pub async fn run_api_server(cli_listen: Option<SocketAddr>, config: Config) -> R
         state.config.listen.to_owned()
     };
 
-    let rest = Router::new()
-        .merge(api::get_router())
+    let mut rest = Router::new().merge(api::get_router());
+
+    if state.config.metrics.is_some() {
+        rest = rest.layer(axum::middleware::from_fn(metrics::track_requests));
+    }
+
+    let rest = rest
         .fallback(fallback)
         // middlewares
         .layer(axum::middleware::from_fn(apply_auth))
@@ -247,16 +317,55 @@ pub async fn run_api_server(cli_listen: Option<SocketAddr>, config: Config) -> R
 
     let listener = TcpListener::bind(&listen).await?;
 
-    let (server_ret, _) = tokio::join!(axum::serve(listener, rest).into_future(), async {
-        if state.config.database.heartbeat {
-            let _ = state.run_db_heartbeat().await;
+    let metrics_listener = if let Some(metrics) = &state.config.metrics {
+        eprintln!("Serving metrics on {:?}...", metrics.listen);
+        Some(TcpListener::bind(&metrics.listen).await?)
+    } else {
+        None
+    };
+
+    let metrics_server = async {
+        if let Some(listener) = metrics_listener {
+            metrics::serve(listener, state.clone()).await?;
         }
-    },);
+
+        Ok::<(), std::io::Error>(())
+    };
+
+    let (server_ret, metrics_ret, _) = tokio::join!(
+        axum::serve(listener, rest).into_future(),
+        metrics_server,
+        async {
+            if state.config.database.heartbeat {
+                let _ = state.run_db_heartbeat().await;
+            }
+        },
+    );
 
     server_ret?;
+    metrics_ret?;
 
     Ok(())
 }
+/// Runs the metrics server on its own.
+///
+/// This serves the metrics of processes without the API server, such
+/// as the garbage collector. If metrics aren't configured, this returns
+/// immediately.
+pub async fn run_metrics_server(config: Config) -> Result<()> {
+    let Some(metrics) = config.metrics.clone() else {
+        return Ok(());
+    };
+
+    let state = StateInner::new(config).await;
+
+    eprintln!("Serving metrics on {:?}...", metrics.listen);
+    let listener = TcpListener::bind(&metrics.listen).await?;
+    metrics::serve(listener, state).await?;
+
+    Ok(())
+}
+
 /// Runs database migrations.
 pub async fn run_migrations(config: Config) -> Result<()> {
     eprintln!("Running migrations...");
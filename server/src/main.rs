@@ -5,14 +5,15 @@
 use std::env;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+
 use anyhow::Result;
+use bunker_server::config;
 use clap::{Parser, ValueEnum};
-use tokio::join;
 use tokio::task::spawn;
+use tokio::{join, try_join};
 use tracing_error::ErrorLayer;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
-use bunker_server::config;
 
 /// Nix binary cache server.
 #[derive(Debug, Parser)]
@@ -57,6 +58,9 @@ enum ServerMode {
     /// Run garbage collection then exit.
     GarbageCollectorOnce,
 
+    /// Run storage verification periodically.
+    Verifier,
+
     /// Check the configuration then exit.
     CheckConfig,
 }
@@ -72,9 +76,10 @@ async fn main() -> Result<()> {
         ServerMode::Monolithic => {
             bunker_server::run_migrations(config.clone()).await?;
 
-            let (api_server, _) = join!(
+            let (api_server, _, _) = join!(
                 bunker_server::run_api_server(opts.listen, config.clone()),
                 bunker_server::gc::run_garbage_collection(config.clone()),
+                bunker_server::verify::run_verification(config.clone()),
             );
             api_server?;
         }
@@ -82,7 +87,10 @@ async fn main() -> Result<()> {
             bunker_server::run_api_server(opts.listen, config).await?;
         }
         ServerMode::GarbageCollector => {
-            bunker_server::gc::run_garbage_collection(config.clone()).await;
+            try_join!(bunker_server::run_metrics_server(config.clone()), async {
+                bunker_server::gc::run_garbage_collection(config.clone()).await;
+                Ok::<(), anyhow::Error>(())
+            })?;
         }
         ServerMode::DbMigrations => {
             bunker_server::run_migrations(config).await?;
@@ -90,8 +98,10 @@ async fn main() -> Result<()> {
         ServerMode::GarbageCollectorOnce => {
             bunker_server::gc::run_garbage_collection_once(config).await?;
         }
-        ServerMode::CheckConfig => {
+        ServerMode::Verifier => {
+            bunker_server::verify::run_verification(config).await;
         }
+        ServerMode::CheckConfig => {}
     }
     Ok(())
 }
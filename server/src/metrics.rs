@@ -0,0 +1,187 @@
+//! Prometheus metrics.
+//!
+//! Metrics are kept in the default registry of the process and are
+//! served at `/metrics` on a separate listen address. Each process
+//! serves its own metrics: The API server serves them in the API server
+//! and monolithic modes, and the garbage collector serves them in the
+//! garbage collector mode.
+
+use std::time::Instant;
+
+use axum::{
+    extract::{Extension, MatchedPath, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge_vec,
+    Encoder, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+use sea_orm::DatabaseConnection;
+use tokio::net::TcpListener;
+
+use crate::error::{ErrorKind, ServerResult};
+use crate::State;
+use bunker::api::v1::upload_path::UploadPathResultKind;
+
+lazy_static! {
+    static ref HTTP_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "bunker_http_requests_total",
+        "Number of HTTP requests handled.",
+        &["route", "method", "status"]
+    )
+    .unwrap();
+    static ref HTTP_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "bunker_http_request_duration_seconds",
+        "Time taken to produce HTTP responses.",
+        &["route", "method"]
+    )
+    .unwrap();
+    static ref UPLOAD_BYTES: IntCounter = register_int_counter!(
+        "bunker_upload_nar_bytes_total",
+        "Uncompressed size of the NARs received in uploads."
+    )
+    .unwrap();
+    static ref UPLOADS: IntCounterVec = register_int_counter_vec!(
+        "bunker_uploads_total",
+        "Number of uploaded paths, by whether the NAR already existed.",
+        &["result"]
+    )
+    .unwrap();
+    static ref CHUNK_DOWNLOAD_ERRORS: IntCounter = register_int_counter!(
+        "bunker_chunk_download_errors_total",
+        "Number of failures while streaming chunks from the storage backend."
+    )
+    .unwrap();
+    static ref GC_DELETIONS: IntCounterVec = register_int_counter_vec!(
+        "bunker_gc_deleted_total",
+        "Number of rows deleted by garbage collection.",
+        &["kind"]
+    )
+    .unwrap();
+    static ref NAR_REPAIRS: IntCounterVec = register_int_counter_vec!(
+        "bunker_gc_nar_repairs_total",
+        "Number of repairs made to NARs during garbage collection.",
+        &["action"]
+    )
+    .unwrap();
+    static ref GC_RUNS: IntCounterVec = register_int_counter_vec!(
+        "bunker_gc_runs_total",
+        "Number of garbage collection runs.",
+        &["result"]
+    )
+    .unwrap();
+    static ref DB_POOL_CONNECTIONS: IntGaugeVec = register_int_gauge_vec!(
+        "bunker_db_pool_connections",
+        "Number of connections in the database pool.",
+        &["state"]
+    )
+    .unwrap();
+}
+
+/// Serves the metrics on a listener.
+pub(crate) async fn serve(listener: TcpListener, state: State) -> std::io::Result<()> {
+    let router = Router::new()
+        .route("/metrics", get(get_metrics))
+        .layer(Extension(state));
+
+    axum::serve(listener, router).await
+}
+
+/// Records request counts and latencies per route.
+pub(crate) async fn track_requests(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "<unmatched>".to_owned());
+    let method = req.method().to_string();
+
+    let start = Instant::now();
+    let response = next.run(req).await;
+
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[&route, &method])
+        .observe(start.elapsed().as_secs_f64());
+    HTTP_REQUESTS
+        .with_label_values(&[&route, &method, response.status().as_str()])
+        .inc();
+
+    response
+}
+
+/// Records an uploaded path.
+pub(crate) fn record_upload(kind: &UploadPathResultKind, nar_size: usize) {
+    let result = match kind {
+        UploadPathResultKind::Uploaded => "new",
+        UploadPathResultKind::Deduplicated => "deduplicated",
+        _ => "other",
+    };
+
+    UPLOADS.with_label_values(&[result]).inc();
+    UPLOAD_BYTES.inc_by(nar_size as u64);
+}
+
+/// Records a failure while streaming chunks.
+pub(crate) fn record_chunk_download_error() {
+    CHUNK_DOWNLOAD_ERRORS.inc();
+}
+
+/// Records rows deleted by garbage collection.
+pub(crate) fn record_gc_deletions(kind: &str, count: u64) {
+    GC_DELETIONS.with_label_values(&[kind]).inc_by(count);
+}
+
+/// Records repairs made to NARs during garbage collection.
+pub(crate) fn record_nar_repairs(action: &str, count: u64) {
+    NAR_REPAIRS.with_label_values(&[action]).inc_by(count);
+}
+
+/// Records the outcome of a garbage collection run.
+pub(crate) fn record_gc_run(success: bool) {
+    let result = if success { "success" } else { "failure" };
+    GC_RUNS.with_label_values(&[result]).inc();
+}
+
+/// Serves the metrics in the text exposition format.
+async fn get_metrics(Extension(state): Extension<State>) -> ServerResult<Response> {
+    if let Ok(database) = state.database().await {
+        update_db_pool_metrics(database);
+    }
+
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .map_err(|_| ErrorKind::InternalServerError)?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        buffer,
+    )
+        .into_response())
+}
+
+fn update_db_pool_metrics(database: &DatabaseConnection) {
+    let (size, idle) = match database {
+        DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+            let pool = database.get_postgres_connection_pool();
+            (pool.size(), pool.num_idle())
+        }
+        DatabaseConnection::SqlxSqlitePoolConnection(_) => {
+            let pool = database.get_sqlite_connection_pool();
+            (pool.size(), pool.num_idle())
+        }
+        _ => return,
+    };
+
+    let idle = idle as i64;
+    DB_POOL_CONNECTIONS.with_label_values(&["idle"]).set(idle);
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["active"])
+        .set(i64::from(size) - idle);
+}
@@ -0,0 +1,315 @@
+//! NAR listings.
+//!
+//! ## `.ls` format
+//!
+//! A NAR listing is a JSON document describing the file tree inside
+//! a NAR without any of the file contents. It's served at
+//! `/:cache/{storePathHash}.ls` and consumed by `nix store ls` as well
+//! as indexing tools like `nix-index`:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "root": {
+//!     "type": "directory",
+//!     "entries": {
+//!       "bin": {
+//!         "type": "directory",
+//!         "entries": {
+//!           "hello": { "type": "regular", "size": 70280, "executable": true, "narOffset": 400 }
+//!         }
+//!       },
+//!       "lib": { "type": "symlink", "target": "lib64" }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Consult `src/libstore/nar-accessor.cc` for the Nix implementation.
+//!
+//! ## NAR format
+//!
+//! A NAR is a sequence of length-prefixed strings. Each string is
+//! prefixed by its length as a 64-bit little-endian integer and
+//! padded with zeros to a multiple of 8 bytes. The grammar is
+//! described in Figure 5.2 of Eelco Dolstra's thesis.
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::BTreeMap;
+use std::io;
+
+use displaydoc::Display;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The magic string at the beginning of every NAR.
+const NAR_VERSION_MAGIC: &[u8] = b"nix-archive-1";
+
+/// The maximum length of a string token, file name, or symlink target.
+const MAX_STRING_LENGTH: u64 = 4096;
+
+/// The listing format version we generate.
+const LISTING_VERSION: u32 = 1;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A NAR listing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NarListing {
+    /// Version of the listing format.
+    pub version: u32,
+
+    /// The root node.
+    pub root: ListingNode,
+}
+
+/// A node in a NAR listing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ListingNode {
+    /// A regular file.
+    Regular {
+        /// Size of the file.
+        size: u64,
+
+        /// Whether the file is executable.
+        #[serde(default)]
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        executable: bool,
+
+        /// Offset of the file contents in the NAR.
+        #[serde(rename = "narOffset")]
+        nar_offset: u64,
+    },
+
+    /// A symbolic link.
+    Symlink {
+        /// Target of the link.
+        target: String,
+    },
+
+    /// A directory.
+    Directory {
+        /// Entries in the directory.
+        entries: BTreeMap<String, ListingNode>,
+    },
+}
+
+/// A NAR parsing error.
+#[derive(Debug, Display)]
+pub enum Error {
+    /// I/O error: {0}
+    IoError(io::Error),
+
+    /// Invalid NAR: {0}
+    InvalidNar(&'static str),
+
+    /// Invalid NAR: Expected "{expected}", got {actual:?}
+    UnexpectedTag {
+        expected: &'static str,
+        actual: String,
+    },
+}
+
+/// Streaming NAR parser that only keeps track of the file tree.
+struct NarParser<R> {
+    reader: R,
+
+    /// Number of bytes consumed so far.
+    offset: u64,
+}
+
+impl NarListing {
+    /// Generates a listing from an uncompressed NAR stream.
+    ///
+    /// The stream is consumed until the end of the NAR. File contents
+    /// are skipped without being buffered.
+    pub async fn from_nar<R>(reader: R) -> Result<Self>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let mut parser = NarParser { reader, offset: 0 };
+
+        let magic = parser.read_string().await?;
+        if magic != NAR_VERSION_MAGIC {
+            return Err(Error::InvalidNar("Bad magic"));
+        }
+
+        let root = parser.parse_node().await?;
+
+        Ok(Self {
+            version: LISTING_VERSION,
+            root,
+        })
+    }
+}
+
+impl<R: AsyncRead + Unpin + Send> NarParser<R> {
+    async fn read_u64(&mut self) -> Result<u64> {
+        let n = self.reader.read_u64_le().await?;
+        self.offset += 8;
+        Ok(n)
+    }
+
+    async fn read_padding(&mut self, len: u64) -> Result<()> {
+        let padding = ((8 - len % 8) % 8) as usize;
+
+        if padding != 0 {
+            let mut buf = [0u8; 8];
+            self.reader.read_exact(&mut buf[..padding]).await?;
+            self.offset += padding as u64;
+
+            if buf.iter().any(|b| *b != 0) {
+                return Err(Error::InvalidNar("Non-zero padding"));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn read_string(&mut self) -> Result<Vec<u8>> {
+        let len = self.read_u64().await?;
+
+        if len > MAX_STRING_LENGTH {
+            return Err(Error::InvalidNar("String is too long"));
+        }
+
+        let mut buf = vec![0u8; len as usize];
+        self.reader.read_exact(&mut buf).await?;
+        self.offset += len;
+
+        self.read_padding(len).await?;
+
+        Ok(buf)
+    }
+
+    async fn read_utf8_string(&mut self) -> Result<String> {
+        String::from_utf8(self.read_string().await?)
+            .map_err(|_| Error::InvalidNar("String contains non-UTF-8 characters"))
+    }
+
+    async fn expect_tag(&mut self, expected: &'static str) -> Result<()> {
+        let actual = self.read_string().await?;
+
+        if actual != expected.as_bytes() {
+            return Err(Error::UnexpectedTag {
+                expected,
+                actual: String::from_utf8_lossy(&actual).to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn skip_contents(&mut self, len: u64) -> Result<()> {
+        let mut contents = (&mut self.reader).take(len);
+        let skipped = tokio::io::copy(&mut contents, &mut tokio::io::sink()).await?;
+
+        if skipped != len {
+            return Err(Error::IoError(io::ErrorKind::UnexpectedEof.into()));
+        }
+
+        self.offset += len;
+        self.read_padding(len).await
+    }
+
+    fn parse_node(&mut self) -> BoxFuture<'_, Result<ListingNode>> {
+        Box::pin(async move {
+            self.expect_tag("(").await?;
+            self.expect_tag("type").await?;
+
+            let node = match self.read_string().await?.as_slice() {
+                b"regular" => {
+                    let mut executable = false;
+                    let mut tag = self.read_string().await?;
+
+                    if tag == b"executable" {
+                        executable = true;
+                        self.expect_tag("").await?;
+                        tag = self.read_string().await?;
+                    }
+
+                    if tag != b"contents" {
+                        return Err(Error::UnexpectedTag {
+                            expected: "contents",
+                            actual: String::from_utf8_lossy(&tag).to_string(),
+                        });
+                    }
+
+                    let size = self.read_u64().await?;
+                    let nar_offset = self.offset;
+                    self.skip_contents(size).await?;
+
+                    ListingNode::Regular {
+                        size,
+                        executable,
+                        nar_offset,
+                    }
+                }
+                b"symlink" => {
+                    self.expect_tag("target").await?;
+                    let target = self.read_utf8_string().await?;
+
+                    ListingNode::Symlink { target }
+                }
+                b"directory" => {
+                    let mut entries = BTreeMap::new();
+
+                    loop {
+                        match self.read_string().await?.as_slice() {
+                            b")" => {
+                                // The closing parenthesis of the directory node itself
+                                return Ok(ListingNode::Directory { entries });
+                            }
+                            b"entry" => {
+                                self.expect_tag("(").await?;
+                                self.expect_tag("name").await?;
+
+                                let name = self.read_utf8_string().await?;
+                                validate_entry_name(&name)?;
+
+                                self.expect_tag("node").await?;
+                                let node = self.parse_node().await?;
+                                self.expect_tag(")").await?;
+
+                                if entries.insert(name, node).is_some() {
+                                    return Err(Error::InvalidNar("Duplicate directory entry"));
+                                }
+                            }
+                            _ => {
+                                return Err(Error::InvalidNar("Unexpected tag in directory"));
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    return Err(Error::InvalidNar("Unknown node type"));
+                }
+            };
+
+            self.expect_tag(")").await?;
+
+            Ok(node)
+        })
+    }
+}
+
+/// Validates the name of a directory entry.
+fn validate_entry_name(name: &str) -> Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) {
+        Err(Error::InvalidNar("Invalid directory entry name"))
+    } else {
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::IoError(error)
+    }
+}
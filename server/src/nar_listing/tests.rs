@@ -0,0 +1,186 @@
+use super::*;
+
+/// Builds a NAR in memory.
+struct NarBuilder(Vec<u8>);
+
+impl NarBuilder {
+    fn new() -> Self {
+        let mut builder = Self(Vec::new());
+        builder.string(NAR_VERSION_MAGIC);
+        builder
+    }
+
+    fn string(&mut self, s: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(&(s.len() as u64).to_le_bytes());
+        self.0.extend_from_slice(s);
+        self.0.resize(self.0.len() + (8 - s.len() % 8) % 8, 0);
+        self
+    }
+
+    fn tags(&mut self, tags: &[&str]) -> &mut Self {
+        for tag in tags {
+            self.string(tag.as_bytes());
+        }
+        self
+    }
+
+    fn regular(&mut self, contents: &[u8], executable: bool) -> &mut Self {
+        self.tags(&["(", "type", "regular"]);
+        if executable {
+            self.tags(&["executable", ""]);
+        }
+        self.tags(&["contents"]).string(contents).tags(&[")"])
+    }
+
+    fn symlink(&mut self, target: &str) -> &mut Self {
+        self.tags(&["(", "type", "symlink", "target", target, ")"])
+    }
+
+    fn begin_directory(&mut self) -> &mut Self {
+        self.tags(&["(", "type", "directory"])
+    }
+
+    fn begin_entry(&mut self, name: &str) -> &mut Self {
+        self.tags(&["entry", "(", "name", name, "node"])
+    }
+
+    fn end(&mut self) -> &mut Self {
+        self.tags(&[")"])
+    }
+
+    fn build(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+#[tokio::test]
+async fn test_regular_file() {
+    let nar = NarBuilder::new().regular(b"hello", false).build();
+    let listing = NarListing::from_nar(nar.as_slice()).await.unwrap();
+
+    // magic (8 + 16) + "(" (16) + "type" (16) + "regular" (16) + "contents" (16) + size (8)
+    assert_eq!(
+        ListingNode::Regular {
+            size: 5,
+            executable: false,
+            nar_offset: 96,
+        },
+        listing.root
+    );
+}
+
+#[tokio::test]
+async fn test_directory() {
+    let nar = NarBuilder::new()
+        .begin_directory()
+        .begin_entry("bin")
+        .begin_directory()
+        .begin_entry("hello")
+        .regular(b"#!/bin/sh\necho hello\n", true)
+        .end()
+        .end()
+        .end()
+        .begin_entry("lib")
+        .symlink("lib64")
+        .end()
+        .end()
+        .build();
+
+    let listing = NarListing::from_nar(nar.as_slice()).await.unwrap();
+
+    let ListingNode::Directory { entries } = &listing.root else {
+        panic!("Root is not a directory");
+    };
+    assert_eq!(2, entries.len());
+    assert_eq!(
+        &ListingNode::Symlink {
+            target: "lib64".to_string(),
+        },
+        &entries["lib"]
+    );
+
+    let ListingNode::Directory { entries: bin } = &entries["bin"] else {
+        panic!("bin is not a directory");
+    };
+    let ListingNode::Regular {
+        size,
+        executable,
+        nar_offset,
+    } = bin["hello"]
+    else {
+        panic!("bin/hello is not a regular file");
+    };
+    assert_eq!(21, size);
+    assert!(executable);
+    assert_eq!(
+        b"#!/bin/sh\necho hello\n",
+        &nar[nar_offset as usize..(nar_offset + size) as usize]
+    );
+}
+
+#[tokio::test]
+async fn test_json() {
+    let nar = NarBuilder::new()
+        .begin_directory()
+        .begin_entry("a")
+        .regular(b"", true)
+        .end()
+        .begin_entry("b")
+        .regular(b"", false)
+        .end()
+        .end()
+        .build();
+
+    let listing = NarListing::from_nar(nar.as_slice()).await.unwrap();
+    let json = serde_json::to_value(&listing).unwrap();
+
+    assert_eq!(
+        serde_json::json!({
+            "version": 1,
+            "root": {
+                "type": "directory",
+                "entries": {
+                    "a": { "type": "regular", "size": 0, "executable": true, "narOffset": 264 },
+                    "b": { "type": "regular", "size": 0, "narOffset": 448 },
+                },
+            },
+        }),
+        json
+    );
+}
+
+#[tokio::test]
+async fn test_invalid() {
+    // Bad magic
+    let mut nar = NarBuilder::new();
+    nar.0.clear();
+    let nar = nar.string(b"nix-archive-2").symlink("x").build();
+    assert!(NarListing::from_nar(nar.as_slice()).await.is_err());
+
+    // Truncated
+    let nar = NarBuilder::new().regular(b"hello", false).build();
+    assert!(NarListing::from_nar(&nar[..nar.len() - 16]).await.is_err());
+
+    // Bad entry name
+    let nar = NarBuilder::new()
+        .begin_directory()
+        .begin_entry("..")
+        .symlink("x")
+        .end()
+        .end()
+        .build();
+    assert!(NarListing::from_nar(nar.as_slice()).await.is_err());
+
+    // Duplicate entry
+    let nar = NarBuilder::new()
+        .begin_directory()
+        .begin_entry("a")
+        .symlink("x")
+        .end()
+        .begin_entry("a")
+        .symlink("y")
+        .end()
+        .end()
+        .build();
+    assert!(NarListing::from_nar(nar.as_slice()).await.is_err());
+}
@@ -37,22 +37,25 @@
 //! 1;{storePath};{narHash};{narSize};{commaDelimitedReferences}
 //! ```
 
+use std::io;
 use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::string::ToString;
 
+use async_compression::tokio::bufread::{BrotliDecoder, XzDecoder, ZstdDecoder};
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use serde::de;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
+use tokio::io::{AsyncRead, BufReader};
 
 use crate::error::{ErrorKind, ServerError, ServerResult};
 use crate::nix_manifest::{self, SpaceDelimitedList};
+use crate::signer::Signer;
 use bunker::hash::Hash;
 use bunker::mime;
-use bunker::signing::NixKeypair;
 
 #[cfg(test)]
 mod tests;
@@ -134,13 +137,14 @@ pub struct NarInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deriver: Option<String>,
 
-    /// The signature of the object.
+    /// The signatures of the object.
     ///
-    /// The `Sig` field can be duplicated to include multiple
-    /// signatures, but we only support one for now.
+    /// Each signature is serialized as a separate `Sig` line. Signatures
+    /// from keys that a client doesn't trust are ignored by Nix.
     #[serde(rename = "Sig")]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub signature: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub signatures: Vec<String>,
 
     /// The content address of the object.
     #[serde(rename = "CA")]
@@ -174,9 +178,9 @@ impl NarInfo {
         nix_manifest::to_string(self)
     }
 
-    /// Returns the signature of this object, if it exists.
-    pub fn signature(&self) -> Option<&String> {
-        self.signature.as_ref()
+    /// Returns the signatures of this object.
+    pub fn signatures(&self) -> &[String] {
+        &self.signatures
     }
 
     /// Returns the store directory of this object.
@@ -185,10 +189,23 @@ impl NarInfo {
         self.store_path.parent().unwrap()
     }
 
-    /// Signs the narinfo and adds the signature to the narinfo.
-    pub fn sign(&mut self, keypair: &NixKeypair) {
-        let signature = self.sign_readonly(keypair);
-        self.signature = Some(signature);
+    /// Signs the narinfo and adds the signatures to the narinfo.
+    ///
+    /// Existing signatures from the signer's keys are replaced, and
+    /// other signatures are kept.
+    pub async fn sign(&mut self, signer: &dyn Signer) -> ServerResult<()> {
+        let signatures = signer.sign(&self.fingerprint()).await?;
+
+        let key_names = signer.key_names();
+        self.signatures
+            .retain(|existing| match existing.split_once(':') {
+                Some((name, _)) => !key_names.contains(&name),
+                None => true,
+            });
+
+        self.signatures.extend(signatures);
+
+        Ok(())
     }
 
     /// Returns the fingerprint of the object.
@@ -226,12 +243,6 @@ impl NarInfo {
 
         fingerprint
     }
-
-    /// Signs the narinfo with a keypair, returning the signature.
-    fn sign_readonly(&self, keypair: &NixKeypair) -> String {
-        let fingerprint = self.fingerprint();
-        keypair.sign(&fingerprint)
-    }
 }
 
 impl IntoResponse for NarInfo {
@@ -258,6 +269,25 @@ impl Compression {
             Self::Zstd => "zstd",
         }
     }
+
+    /// Wraps a stream compressed with this compression type with a decompressor.
+    pub fn decompress(
+        &self,
+        stream: Box<dyn AsyncRead + Unpin + Send>,
+    ) -> io::Result<Box<dyn AsyncRead + Unpin + Send>> {
+        let stream = BufReader::new(stream);
+
+        match self {
+            Self::None => Ok(Box::new(stream)),
+            Self::Xz => Ok(Box::new(XzDecoder::new(stream))),
+            Self::Brotli => Ok(Box::new(BrotliDecoder::new(stream))),
+            Self::Zstd => Ok(Box::new(ZstdDecoder::new(stream))),
+            Self::Bzip2 => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "bzip2 decompression is not supported",
+            )),
+        }
+    }
 }
 
 impl FromStr for Compression {
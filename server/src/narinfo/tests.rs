@@ -2,7 +2,7 @@ use super::*;
 
 use std::path::Path;
 
-use bunker::signing::NixPublicKey;
+use bunker::signing::{NixKeypair, NixPublicKey};
 
 #[test]
 fn test_basic() {
@@ -53,7 +53,7 @@ Sig: cache.nixos.org-1:lo9EfNIL4eGRuNh7DTbAAffWPpI2SlYC/8uP7JnhgmfRIUNGhSbFe8qEa
             Some("vvb4wxmnjixmrkhmj2xb75z62hrr41i7-hello-2.10.drv".to_string()),
             narinfo.deriver
         );
-        assert_eq!(Some("cache.nixos.org-1:lo9EfNIL4eGRuNh7DTbAAffWPpI2SlYC/8uP7JnhgmfRIUNGhSbFe8qEaKN0mFS02TuhPpXFPNtRkFcCp0hGAQ==".to_string()), narinfo.signature);
+        assert_eq!(vec!["cache.nixos.org-1:lo9EfNIL4eGRuNh7DTbAAffWPpI2SlYC/8uP7JnhgmfRIUNGhSbFe8qEaKN0mFS02TuhPpXFPNtRkFcCp0hGAQ==".to_string()], narinfo.signatures);
     }
 
     verify_narinfo(&narinfo);
@@ -123,6 +123,95 @@ Sig: cache.nixos.org-1:lo9EfNIL4eGRuNh7DTbAAffWPpI2SlYC/8uP7JnhgmfRIUNGhSbFe8qEa
     assert_eq!(correct_fingerprint, fingerprint.as_slice());
 
     public_key
-        .verify(&narinfo.fingerprint(), narinfo.signature().unwrap())
+        .verify(&narinfo.fingerprint(), &narinfo.signatures()[0])
         .expect("Could not verify signature");
 }
+
+#[tokio::test]
+async fn test_hybrid_signature() {
+    let s = r#"
+StorePath: /nix/store/xcp9cav49dmsjbwdjlmkjxj10gkpx553-hello-2.10
+URL: nar/0nqgf15qfiacfxrgm2wkw0gwwncjqqzzalj8rs14w9srkydkjsk9.nar.xz
+Compression: xz
+NarHash: sha256:16mvl7v0ylzcg2n3xzjn41qhzbmgcn5iyarx16nn5l2r36n2kqci
+NarSize: 206104
+References: 563528481rvhc5kxwipjmg6rqrl95mdx-glibc-2.33-56 xcp9cav49dmsjbwdjlmkjxj10gkpx553-hello-2.10
+    "#;
+
+    let keypair = NixKeypair::generate_hybrid("bunker-test").expect("Could not generate key");
+
+    let mut narinfo = NarInfo::from_str(s).expect("Could not parse narinfo");
+    narinfo
+        .sign(&keypair)
+        .await
+        .expect("Could not sign narinfo");
+
+    let signatures = narinfo.signatures();
+    assert_eq!(2, signatures.len());
+
+    let signature = &signatures[0];
+    let pqc_signature = &signatures[1];
+
+    keypair
+        .to_public_key()
+        .verify_hybrid(&narinfo.fingerprint(), signatures)
+        .expect("Could not verify signatures");
+
+    let round_trip = narinfo.to_string().expect("Could not serialize narinfo");
+    let sig_lines: Vec<&str> = round_trip
+        .lines()
+        .filter(|line| line.starts_with("Sig: "))
+        .collect();
+
+    assert_eq!(
+        vec![
+            format!("Sig: {}", signature),
+            format!("Sig: {}", pqc_signature)
+        ],
+        sig_lines
+    );
+}
+
+#[tokio::test]
+async fn test_multiple_signatures() {
+    let s = r#"
+StorePath: /nix/store/xcp9cav49dmsjbwdjlmkjxj10gkpx553-hello-2.10
+URL: nar/0nqgf15qfiacfxrgm2wkw0gwwncjqqzzalj8rs14w9srkydkjsk9.nar.xz
+Compression: xz
+NarHash: sha256:16mvl7v0ylzcg2n3xzjn41qhzbmgcn5iyarx16nn5l2r36n2kqci
+NarSize: 206104
+References: 563528481rvhc5kxwipjmg6rqrl95mdx-glibc-2.33-56 xcp9cav49dmsjbwdjlmkjxj10gkpx553-hello-2.10
+Sig: cache.nixos.org-1:lo9EfNIL4eGRuNh7DTbAAffWPpI2SlYC/8uP7JnhgmfRIUNGhSbFe8qEaKN0mFS02TuhPpXFPNtRkFcCp0hGAQ==
+Sig: hydra.example.com-1:tiQsk4P/EvjK8uyz9GwbY3L+eAxyTs2gsVC+jbnqi8qcTuX0zx7wz/pvQ9GEFR2LuLCfVh31LGYyfhQxNUi4DQ==
+Deriver: vvb4wxmnjixmrkhmj2xb75z62hrr41i7-hello-2.10.drv
+Sig: bunker-test:6NJbwyb6BbYnsrR8ZQ5bMVv8AdmN9NzAHLL8a3BZI4GGULuv+ICUsFYcGH6kRzjgjI+LKqxZSWFlnqvw5pbQCg==
+    "#;
+
+    let keypair = NixKeypair::generate("bunker-test").expect("Could not generate key");
+
+    let mut narinfo = NarInfo::from_str(s).expect("Could not parse narinfo");
+    assert_eq!(3, narinfo.signatures().len());
+    assert_eq!(
+        Some("vvb4wxmnjixmrkhmj2xb75z62hrr41i7-hello-2.10.drv".to_string()),
+        narinfo.deriver
+    );
+
+    narinfo
+        .sign(&keypair)
+        .await
+        .expect("Could not sign narinfo");
+
+    // The stale signature from our own key is replaced
+    let signatures = narinfo.signatures();
+    assert_eq!(3, signatures.len());
+    assert!(signatures[0].starts_with("cache.nixos.org-1:"));
+    assert!(signatures[1].starts_with("hydra.example.com-1:"));
+    keypair
+        .verify(&narinfo.fingerprint(), &signatures[2])
+        .expect("Could not verify signature");
+
+    let round_trip = narinfo.to_string().expect("Could not serialize narinfo");
+    let reparsed = NarInfo::from_str(&round_trip).expect("Could not reparse narinfo");
+
+    assert_eq!(narinfo.signatures(), reparsed.signatures());
+}
@@ -1,10 +1,14 @@
 //! The deserializer.
 //!
 //! This maps the manifest format into the serde data model.
+//!
+//! Keys can be repeated (e.g., `Sig`). When a sequence is requested for
+//! a value, the values of all lines with the same key are collected.
 
+use std::collections::HashSet;
 use std::ops::{AddAssign, MulAssign};
 
-use serde::de::{DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::de::{DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
 use serde::{de, forward_to_deserialize_any};
 
 use super::{Error, Result};
@@ -12,14 +16,33 @@ use super::{Error, Result};
 /// The main deserializer.
 pub struct Deserializer<'de> {
     input: &'de str,
+
+    /// Length of the full input.
+    input_len: usize,
+
+    /// The key of the value being deserialized.
+    current_key: Option<&'de str>,
+
+    /// Offsets of lines that were already consumed as repeated values.
+    consumed_lines: HashSet<usize>,
 }
 
 /// Deserializer for values.
 pub struct ValueDeserializer<'a, 'de: 'a>(&'a mut Deserializer<'de>);
 
+/// Access to the values of a repeated key.
+struct RepeatedValues<'de> {
+    values: std::vec::IntoIter<&'de str>,
+}
+
 impl<'de> Deserializer<'de> {
     pub fn from_str(input: &'de str) -> Self {
-        Deserializer { input }
+        Deserializer {
+            input,
+            input_len: input.len(),
+            current_key: None,
+            consumed_lines: HashSet::new(),
+        }
     }
 }
 
@@ -84,6 +107,46 @@ impl<'de> Deserializer<'de> {
         }
     }
 
+    /// Returns the offset of the remaining input.
+    fn offset(&self) -> usize {
+        self.input_len - self.input.len()
+    }
+
+    /// Skips lines that were already consumed as repeated values.
+    fn skip_consumed_lines(&mut self) -> Result<()> {
+        self.consume_whitespace()?;
+
+        while self.consumed_lines.contains(&self.offset()) {
+            self.parse_until_eol()?;
+            self.consume_whitespace()?;
+        }
+
+        Ok(())
+    }
+
+    /// Finds all later values of a key and marks their lines as consumed.
+    fn take_repeated_values(&mut self, key: &str) -> Vec<&'de str> {
+        let input = self.input;
+        let mut values = Vec::new();
+        let mut offset = self.offset();
+
+        for line in input.split_inclusive('\n') {
+            let line_offset = offset + (line.len() - line.trim_start().len());
+            offset += line.len();
+
+            if let Some((line_key, value)) = line.trim_start().split_once(':') {
+                if line_key == key {
+                    let value = value.split(['\r', '\n']).next().unwrap_or_default();
+
+                    values.push(value.trim_start());
+                    self.consumed_lines.insert(line_offset);
+                }
+            }
+        }
+
+        values
+    }
+
     fn parse_bool(&mut self) -> Result<bool> {
         if self.input.starts_with('1') {
             self.input = &self.input["1".len()..];
@@ -147,6 +210,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
         let identifier = &self.input[..colon];
 
+        self.current_key = Some(identifier);
         self.input = &self.input[colon..];
         visitor.visit_borrowed_str(identifier)
     }
@@ -159,7 +223,7 @@ impl<'de> MapAccess<'de> for Deserializer<'de> {
     where
         K: DeserializeSeed<'de>,
     {
-        self.consume_whitespace()?;
+        self.skip_consumed_lines()?;
 
         if self.input.is_empty() {
             return Ok(None);
@@ -335,11 +399,19 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut ValueDeserializer<'a, 'de> {
         visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(self, _visitor: V) -> Result<V::Value>
+    // only accepted in maps
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::Unsupported("Sequence"))
+        let key = self.0.current_key.ok_or(Error::Unsupported("Sequence"))?;
+
+        let mut values = vec![self.0.parse_until_eol()?.trim_start()];
+        values.extend(self.0.take_repeated_values(key));
+
+        visitor.visit_seq(RepeatedValues {
+            values: values.into_iter(),
+        })
     }
 
     fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
@@ -407,3 +479,25 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut ValueDeserializer<'a, 'de> {
         self.deserialize_any(visitor)
     }
 }
+
+impl<'de> SeqAccess<'de> for RepeatedValues<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.values.next() {
+            Some(value) => {
+                let mut deserializer = Deserializer::from_str(value);
+                seed.deserialize(&mut ValueDeserializer(&mut deserializer))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
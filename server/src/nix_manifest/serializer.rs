@@ -2,6 +2,9 @@
 //!
 //! A bulk of the serde data model is unsupported due to the restricted
 //! format.
+//!
+//! Sequences are only supported as struct fields, and each element is
+//! emitted as a separate line with the same key.
 
 use serde::{ser, Serialize};
 
@@ -10,6 +13,12 @@ use super::{Error, Result};
 pub struct Serializer {
     output: String,
     seen_map: bool,
+
+    /// The key of the field being serialized and where its line starts.
+    current_field: Option<(&'static str, usize)>,
+
+    /// Number of elements serialized in the current sequence.
+    seq_len: Option<usize>,
 }
 
 impl Serializer {
@@ -17,6 +26,8 @@ impl Serializer {
         Self {
             output: String::new(),
             seen_map: false,
+            current_field: None,
+            seq_len: None,
         }
     }
 
@@ -159,7 +170,12 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     // Compund types
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        Err(Error::Unsupported("Sequence"))
+        if self.current_field.is_none() || self.seq_len.is_some() {
+            return Err(Error::Unsupported("Sequence"));
+        }
+
+        self.seq_len = Some(0);
+        Ok(self)
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
@@ -213,16 +229,36 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
     type Error = Error;
 
     // Serialize a single element of the sequence.
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Err(Error::Unsupported("Sequence"))
+        let (key, _) = self.current_field.ok_or(Error::Unsupported("Sequence"))?;
+        let seq_len = self.seq_len.ok_or(Error::Unsupported("Sequence"))?;
+
+        // Repeat the key for subsequent elements
+        if seq_len > 0 {
+            self.output += "\n";
+            self.output += key;
+            self.output += ": ";
+        }
+        self.seq_len = Some(seq_len + 1);
+
+        value.serialize(&mut **self)
     }
 
     // Close the sequence.
     fn end(self) -> Result<()> {
-        Err(Error::Unsupported("Sequence"))
+        let seq_len = self.seq_len.take();
+
+        // An empty sequence results in no lines at all
+        if seq_len == Some(0) {
+            if let Some((_, line_start)) = self.current_field {
+                self.output.truncate(line_start);
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -307,10 +343,18 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
+        let line_start = self.output.len();
+        self.current_field = Some((key, line_start));
+
         key.serialize(&mut **self)?;
         self.output += ": ";
         value.serialize(&mut **self)?;
-        self.output += "\n";
+
+        if self.output.len() > line_start {
+            self.output += "\n";
+        }
+
+        self.current_field = None;
         Ok(())
     }
 
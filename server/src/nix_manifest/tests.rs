@@ -53,3 +53,55 @@ WantMassQuery: 1
     let parsed = super::from_str::<HypotheticalManifest>(manifest).unwrap();
     assert_eq!(parsed, expected);
 }
+
+/// A hypothetical manifest with a repeated key.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct RepeatedManifest {
+    #[serde(rename = "StoreDir")]
+    store_dir: PathBuf,
+
+    #[serde(rename = "Sig")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sigs: Vec<String>,
+
+    #[serde(rename = "WantMassQuery")]
+    want_mass_query: bool,
+}
+
+#[test]
+fn test_repeated_keys() {
+    let manifest = r#"
+StoreDir: /nix/store
+Sig: cache-1:aaaa
+WantMassQuery: 1
+Sig: cache-2:bbbb
+    "#;
+
+    let expected = RepeatedManifest {
+        store_dir: PathBuf::from("/nix/store"),
+        sigs: vec!["cache-1:aaaa".to_string(), "cache-2:bbbb".to_string()],
+        want_mass_query: true,
+    };
+
+    let parsed = super::from_str::<RepeatedManifest>(manifest).unwrap();
+    assert_eq!(parsed, expected);
+
+    let round_trip = super::to_string(&parsed).unwrap();
+    assert_eq!(
+        "StoreDir: /nix/store\nSig: cache-1:aaaa\nSig: cache-2:bbbb\nWantMassQuery: 1\n",
+        round_trip
+    );
+
+    let parsed2 = super::from_str::<RepeatedManifest>(&round_trip).unwrap();
+    assert_eq!(parsed2, expected);
+
+    // Missing repeated keys
+    let unsigned =
+        super::from_str::<RepeatedManifest>("StoreDir: /nix/store\nWantMassQuery: 1\n").unwrap();
+    assert!(unsigned.sigs.is_empty());
+    assert_eq!(
+        "StoreDir: /nix/store\nWantMassQuery: 1\n",
+        super::to_string(&unsigned).unwrap()
+    );
+}
@@ -50,10 +50,15 @@ pub async fn run_oobe() -> Result<()> {
         let pkcs1_pem = private_key.to_pkcs1_pem(rsa::pkcs1::LineEnding::LF)?;
         BASE64_STANDARD.encode(pkcs1_pem.as_bytes())
     };
+    let audit_hmac_key_base64 = {
+        let key: [u8; 32] = rand::random();
+        BASE64_STANDARD.encode(key)
+    };
     let config_content = CONFIG_TEMPLATE
         .replace("%database_url%", &database_url)
         .replace("%storage_path%", storage_path.to_str().unwrap())
-        .replace("%token_rs256_secret_base64%", &rs256_secret_base64);
+        .replace("%token_rs256_secret_base64%", &rs256_secret_base64)
+        .replace("%audit_hmac_key_base64%", &audit_hmac_key_base64);
     fs::write(&config_path, config_content.as_bytes()).await?;
     // Generate a JWT token
     let root_token = {
@@ -68,6 +73,7 @@ pub async fn run_oobe() -> Result<()> {
         perm.configure_cache = true;
         perm.configure_cache_retention = true;
         perm.destroy_cache = true;
+        perm.pin = true;
         let key = decode_token_rs256_secret_base64(&rs256_secret_base64).unwrap();
         token.encode(&SignatureType::RS256(key), &None, &None)?
     };
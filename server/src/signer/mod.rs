@@ -0,0 +1,294 @@
+//! NarInfo signers.
+//!
+//! Narinfos are either signed with keypairs stored in the database, or
+//! by an external signer that holds the private keys. In the latter case,
+//! the `cache` table only stores the public keys, which are used to
+//! reference the keys in the signer.
+//!
+//! ## Unix socket protocol
+//!
+//! For each signature, the server opens a connection to the socket and
+//! sends a single JSON request terminated by a newline. The message is
+//! the narinfo fingerprint:
+//!
+//! ```json
+//! {"keyName":"mycache-1","message":"<base64 message>"}
+//! ```
+//!
+//! The signer responds with a single line containing either the signature
+//! in the canonical format or an error:
+//!
+//! ```json
+//! {"signature":"mycache-1:<base64 signature>"}
+//! {"error":"No such key"}
+//! ```
+//!
+//! Returned signatures are verified against the public key before use.
+
+#[cfg(test)]
+mod tests;
+
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::time;
+
+use crate::config::SignerConfig;
+use crate::error::{ErrorKind, ServerError, ServerResult};
+use bunker::error::BunkerError;
+use bunker::signing::{NixKeypair, NixPublicKey, PqcKeypair, PqcPublicKey};
+
+/// A signer that produces narinfo signatures.
+#[async_trait]
+pub trait Signer: Send + Sync + Debug {
+    /// Returns the names of the keys that signatures are made with.
+    fn key_names(&self) -> Vec<&str>;
+
+    /// Signs a message, returning the signatures in the canonical format.
+    async fn sign(&self, message: &[u8]) -> ServerResult<Vec<String>>;
+}
+
+/// A signing key of a cache.
+#[derive(Debug)]
+pub enum SigningKey {
+    /// A keypair stored in the database.
+    Local(NixKeypair),
+
+    /// A key held by the external signer, referenced by its public key.
+    External(NixPublicKey),
+}
+
+/// A client of a signing daemon listening on a Unix socket.
+#[derive(Debug)]
+pub struct UnixSocketSigner {
+    /// Path to the socket.
+    path: PathBuf,
+
+    /// How long to wait for a signature.
+    timeout: Duration,
+}
+
+/// A key held by an external signer.
+#[derive(Debug)]
+struct ExternalKey {
+    /// The public key that signatures are verified against.
+    public_key: NixPublicKey,
+
+    /// The signer holding the private key.
+    signer: Arc<UnixSocketSigner>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignRequest<'a> {
+    key_name: &'a str,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignResponse {
+    #[serde(default)]
+    signature: Option<String>,
+
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl SigningKey {
+    /// Loads a signing key as stored in the `cache` table.
+    ///
+    /// A full keypair is a local key, and a public key is a reference
+    /// to a key held by the external signer.
+    pub fn from_stored(keypair: &str, pqc_keypair: Option<&str>) -> ServerResult<Self> {
+        if let Ok(keypair) = NixKeypair::from_str(keypair) {
+            let keypair = match pqc_keypair {
+                Some(pqc_keypair) => {
+                    keypair.with_pqc_keypair(PqcKeypair::from_str(pqc_keypair)?)?
+                }
+                None => keypair,
+            };
+
+            return Ok(Self::Local(keypair));
+        }
+
+        let public_key = NixPublicKey::from_str(keypair)?;
+        let public_key = match pqc_keypair {
+            Some(pqc_public_key) => {
+                public_key.with_pqc_public_key(PqcPublicKey::from_str(pqc_public_key)?)?
+            }
+            None => public_key,
+        };
+
+        Ok(Self::External(public_key))
+    }
+
+    /// Returns the name of the key.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Local(keypair) => keypair.name(),
+            Self::External(public_key) => public_key.name(),
+        }
+    }
+
+    /// Returns whether the key is held by the external signer.
+    pub fn is_external(&self) -> bool {
+        matches!(self, Self::External(_))
+    }
+
+    /// Returns the representation stored in the `cache` table.
+    ///
+    /// The post-quantum key is stored separately.
+    pub fn export(&self) -> String {
+        match self {
+            Self::Local(keypair) => keypair.export_keypair(),
+            Self::External(public_key) => public_key.export(),
+        }
+    }
+
+    /// Returns the public key.
+    pub fn public_key(&self) -> NixPublicKey {
+        match self {
+            Self::Local(keypair) => keypair.to_public_key(),
+            Self::External(public_key) => public_key.clone(),
+        }
+    }
+
+    /// Returns a signer for this key.
+    pub fn into_signer(
+        self,
+        external: Option<&Arc<UnixSocketSigner>>,
+    ) -> ServerResult<Box<dyn Signer>> {
+        match self {
+            Self::Local(keypair) => Ok(Box::new(keypair)),
+            Self::External(public_key) => {
+                let signer = external.ok_or_else(|| {
+                    ErrorKind::SignerError(anyhow!(
+                        "Key \"{}\" is held by an external signer, but none is configured",
+                        public_key.name()
+                    ))
+                })?;
+
+                Ok(Box::new(ExternalKey {
+                    public_key,
+                    signer: signer.clone(),
+                }))
+            }
+        }
+    }
+}
+
+impl UnixSocketSigner {
+    pub fn new(path: PathBuf, timeout: Duration) -> Self {
+        Self { path, timeout }
+    }
+
+    pub fn from_config(config: &SignerConfig) -> Self {
+        match config {
+            SignerConfig::UnixSocket(config) => Self::new(config.path.clone(), config.timeout),
+        }
+    }
+
+    /// Requests a signature from the signing daemon.
+    pub async fn sign(&self, key_name: &str, message: &[u8]) -> ServerResult<String> {
+        time::timeout(self.timeout, self.request(key_name, message))
+            .await
+            .map_err(|_| ErrorKind::SignerError(anyhow!("Timed out waiting for the signer")))?
+    }
+
+    async fn request(&self, key_name: &str, message: &[u8]) -> ServerResult<String> {
+        let stream = UnixStream::connect(&self.path)
+            .await
+            .map_err(ServerError::signer_error)?;
+        let (reader, mut writer) = stream.into_split();
+
+        let mut request = serde_json::to_vec(&SignRequest {
+            key_name,
+            message: BASE64_STANDARD.encode(message),
+        })
+        .map_err(ServerError::signer_error)?;
+        request.push(b'\n');
+
+        writer
+            .write_all(&request)
+            .await
+            .map_err(ServerError::signer_error)?;
+
+        let mut line = String::new();
+        BufReader::new(reader)
+            .read_line(&mut line)
+            .await
+            .map_err(ServerError::signer_error)?;
+
+        let response: SignResponse =
+            serde_json::from_str(&line).map_err(ServerError::signer_error)?;
+
+        match response {
+            SignResponse {
+                signature: Some(signature),
+                ..
+            } => Ok(signature),
+            SignResponse { error, .. } => Err(ErrorKind::SignerError(anyhow!(
+                "Signer refused to sign with \"{}\": {}",
+                key_name,
+                error.as_deref().unwrap_or("Unknown error")
+            ))
+            .into()),
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for NixKeypair {
+    fn key_names(&self) -> Vec<&str> {
+        let mut names = vec![self.name()];
+        names.extend(self.pqc_keypair().map(PqcKeypair::name));
+        names
+    }
+
+    async fn sign(&self, message: &[u8]) -> ServerResult<Vec<String>> {
+        let mut signatures = vec![NixKeypair::sign(self, message)];
+        signatures.extend(self.sign_pqc(message)?);
+        Ok(signatures)
+    }
+}
+
+#[async_trait]
+impl Signer for ExternalKey {
+    fn key_names(&self) -> Vec<&str> {
+        let mut names = vec![self.public_key.name()];
+        names.extend(self.public_key.pqc_public_key().map(PqcPublicKey::name));
+        names
+    }
+
+    async fn sign(&self, message: &[u8]) -> ServerResult<Vec<String>> {
+        let invalid = |e: BunkerError| {
+            ErrorKind::SignerError(anyhow!("Signer returned a bad signature: {}", e))
+        };
+
+        let signature = self.signer.sign(self.public_key.name(), message).await?;
+        self.public_key
+            .verify(message, &signature)
+            .map_err(invalid)?;
+
+        let mut signatures = vec![signature];
+
+        if let Some(pqc_public_key) = self.public_key.pqc_public_key() {
+            let signature = self.signer.sign(pqc_public_key.name(), message).await?;
+            pqc_public_key
+                .verify(message, &signature)
+                .map_err(invalid)?;
+
+            signatures.push(signature);
+        }
+
+        Ok(signatures)
+    }
+}
@@ -0,0 +1,112 @@
+use super::*;
+
+use std::path::Path;
+
+use tokio::net::UnixListener;
+
+/// A stand-in signing daemon holding a single keypair.
+async fn spawn_daemon(path: &Path, keypair: NixKeypair) {
+    let listener = UnixListener::bind(path).expect("Could not bind socket");
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+
+            let mut line = String::new();
+            BufReader::new(reader).read_line(&mut line).await.unwrap();
+
+            let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+            let message = BASE64_STANDARD
+                .decode(request["message"].as_str().unwrap())
+                .unwrap();
+
+            let response = if request["keyName"] == keypair.name() {
+                serde_json::json!({ "signature": NixKeypair::sign(&keypair, &message) })
+            } else {
+                serde_json::json!({ "error": "No such key" })
+            };
+
+            let mut response = serde_json::to_vec(&response).unwrap();
+            response.push(b'\n');
+            writer.write_all(&response).await.unwrap();
+        }
+    });
+}
+
+fn socket_path() -> PathBuf {
+    std::env::temp_dir().join(format!("bunker-signer-{}.sock", uuid::Uuid::new_v4()))
+}
+
+#[test]
+fn test_signing_key_from_stored() {
+    let keypair = NixKeypair::generate("cache.example.com-1").unwrap();
+
+    let local = SigningKey::from_stored(&keypair.export_keypair(), None).unwrap();
+    assert!(matches!(local, SigningKey::Local(_)));
+    assert_eq!("cache.example.com-1", local.name());
+
+    let external = SigningKey::from_stored(&keypair.export_public_key(), None).unwrap();
+    assert!(matches!(external, SigningKey::External(_)));
+    assert_eq!(keypair.export_public_key(), external.public_key().export());
+
+    SigningKey::from_stored("cache.example.com-1:garbage", None).unwrap_err();
+}
+
+#[tokio::test]
+async fn test_unix_socket_signer() {
+    let keypair = NixKeypair::generate("cache.example.com-1").unwrap();
+    let public_key = keypair.to_public_key();
+
+    let path = socket_path();
+    spawn_daemon(&path, keypair).await;
+
+    let signer = Arc::new(UnixSocketSigner::new(path.clone(), Duration::from_secs(5)));
+    let key = SigningKey::External(public_key.clone())
+        .into_signer(Some(&signer))
+        .unwrap();
+
+    assert_eq!(vec!["cache.example.com-1"], key.key_names());
+
+    let signatures = key.sign(b"hello world").await.unwrap();
+    assert_eq!(1, signatures.len());
+    public_key.verify(b"hello world", &signatures[0]).unwrap();
+
+    // The daemon doesn't hold this key
+    let other = NixKeypair::generate("cache.example.com-2").unwrap();
+    let other = SigningKey::External(other.to_public_key())
+        .into_signer(Some(&signer))
+        .unwrap();
+    other.sign(b"hello world").await.unwrap_err();
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_unix_socket_signer_bad_signature() {
+    let keypair = NixKeypair::generate("cache.example.com-1").unwrap();
+
+    // The daemon signs with a different keypair of the same name
+    let impostor = NixKeypair::generate("cache.example.com-1").unwrap();
+
+    let path = socket_path();
+    spawn_daemon(&path, impostor).await;
+
+    let signer = Arc::new(UnixSocketSigner::new(path.clone(), Duration::from_secs(5)));
+    let key = SigningKey::External(keypair.to_public_key())
+        .into_signer(Some(&signer))
+        .unwrap();
+
+    key.sign(b"hello world").await.unwrap_err();
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_external_key_without_signer() {
+    let keypair = NixKeypair::generate("cache.example.com-1").unwrap();
+
+    SigningKey::External(keypair.to_public_key())
+        .into_signer(None)
+        .unwrap_err();
+}
@@ -0,0 +1,249 @@
+//! Utilities for tests that need a database and a storage backend.
+//!
+//! Each test state is backed by a fresh SQLite database and a local
+//! storage directory, which are removed when the state is dropped.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use axum::extract::Extension;
+use axum::http::{StatusCode, Uri};
+use axum::Router;
+use chrono::Utc;
+use sea_orm::ActiveValue::Set;
+use sea_orm::EntityTrait;
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+use crate::access::http::AuthState;
+use crate::access::Token;
+use crate::api::v1::upload_path::ingest_path;
+use crate::config::Config;
+use crate::database::entity::cache::{self, CacheModel, Entity as Cache};
+use crate::database::entity::Json as DbJson;
+use crate::database::migration::{Migrator, MigratorTrait};
+use crate::{RequestState, RequestStateInner, State, StateInner};
+use bunker::api::v1::upload_path::UploadPathNarInfo;
+use bunker::cache::CacheName;
+use bunker::hash::Hash;
+use bunker::nix_store::StorePathHash;
+use bunker::signing::NixKeypair;
+
+/// "very secure secret"
+const HS256_SECRET_BASE64: &str = "dmVyeSBzZWN1cmUgc2VjcmV0";
+
+/// 32 zero bytes
+const AUDIT_HMAC_KEY_BASE64: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+
+/// A server state backed by a temporary database and storage.
+pub(crate) struct TestState {
+    state: State,
+    dir: PathBuf,
+}
+
+impl TestState {
+    /// Returns a state with the default test configuration.
+    pub(crate) async fn new() -> Self {
+        Self::with_config("").await
+    }
+
+    /// Returns a state with additional top-level configuration.
+    pub(crate) async fn with_config(extra: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("bunker-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let config = format!(
+            r#"
+{extra}
+
+[database]
+url = "sqlite://{db}?mode=rwc"
+
+[storage]
+type = "local"
+path = "{storage}"
+
+[chunking]
+nar-size-threshold = 1
+min-size = 16384
+avg-size = 65536
+max-size = 262144
+
+[compression]
+type = "none"
+
+[audit]
+hmac-key-base64 = "{AUDIT_HMAC_KEY_BASE64}"
+
+[jwt.signing]
+token-hs256-secret-base64 = "{HS256_SECRET_BASE64}"
+"#,
+            db = dir.join("server.db").display(),
+            storage = dir.join("storage").display(),
+        );
+
+        let config: Config = toml::from_str(&config).unwrap();
+        let state = StateInner::new(config).await;
+        Migrator::up(state.database().await.unwrap(), None)
+            .await
+            .unwrap();
+
+        Self { state, dir }
+    }
+
+    /// Returns the directory of the local storage backend.
+    pub(crate) fn storage_path(&self) -> PathBuf {
+        self.dir.join("storage")
+    }
+
+    /// Creates a private cache.
+    pub(crate) async fn create_cache(&self, name: &str) -> CacheModel {
+        let db = self.database().await.unwrap();
+        let keypair = NixKeypair::generate(name).unwrap();
+
+        let insertion = Cache::insert(cache::ActiveModel {
+            name: Set(name.to_string()),
+            keypair: Set(keypair.export_keypair()),
+            retiring_keypairs: Set(DbJson(Vec::new())),
+            restricted_paths: Set(DbJson(Vec::new())),
+            is_public: Set(false),
+            store_dir: Set("/nix/store".to_string()),
+            priority: Set(41),
+            upstream_cache_key_names: Set(DbJson(Vec::new())),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .unwrap();
+
+        Cache::find_by_id(insertion.last_insert_id)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    /// Pushes a path to a cache through the upload pipeline.
+    ///
+    /// The contents don't need to be a valid NAR.
+    pub(crate) async fn push(
+        &self,
+        cache: &CacheModel,
+        store_path: &str,
+        references: &[&str],
+        nar: &[u8],
+    ) {
+        let upload_info = nar_info(cache, store_path, references, nar);
+        let db = self.database().await.unwrap();
+
+        let _ = ingest_path(
+            None,
+            cache.clone(),
+            upload_info,
+            std::io::Cursor::new(nar.to_vec()),
+            db,
+            &self.state,
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Returns a request state authenticated with a token, if any.
+    pub(crate) fn request_state(&self, token: Option<Token>) -> RequestState {
+        let auth = AuthState::new();
+        if let Some(token) = token {
+            auth.token.set(token).unwrap();
+        }
+
+        Arc::new(RequestStateInner {
+            auth,
+            api_endpoint: Some("http://localhost:8080/".to_string()),
+            substituter_endpoint: None,
+            host: "localhost".to_string(),
+            client_claims_https: false,
+            public_cache: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns the `Extension` to pass to handlers.
+    pub(crate) fn extension(&self) -> Extension<State> {
+        Extension(self.state.clone())
+    }
+}
+
+impl Deref for TestState {
+    type Target = State;
+
+    fn deref(&self) -> &State {
+        &self.state
+    }
+}
+
+impl Drop for TestState {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Returns the upload info of a path.
+pub(crate) fn nar_info(
+    cache: &CacheModel,
+    store_path: &str,
+    references: &[&str],
+    nar: &[u8],
+) -> UploadPathNarInfo {
+    let base_name = Path::new(store_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap();
+    let hash = base_name.split_once('-').unwrap().0;
+
+    UploadPathNarInfo {
+        cache: CacheName::new(cache.name.clone()).unwrap(),
+        store_path_hash: StorePathHash::new(hash.to_string()).unwrap(),
+        store_path: store_path.to_string(),
+        references: references.iter().map(|r| r.to_string()).collect(),
+        system: None,
+        deriver: None,
+        sigs: Vec::new(),
+        ca: None,
+        nar_hash: Hash::sha256_from_bytes(nar),
+        nar_size: nar.len(),
+        restricted: false,
+    }
+}
+
+/// Returns a token with a subject and no permissions.
+pub(crate) fn token(sub: &str) -> Token {
+    let exp = Utc::now() + chrono::Duration::days(1);
+    Token::new(sub.to_string(), &exp)
+}
+
+/// Serves static files over HTTP.
+///
+/// Paths without a file return 404.
+pub(crate) async fn serve_files(files: HashMap<String, Vec<u8>>) -> SocketAddr {
+    let files = Arc::new(files);
+    let router = Router::new().fallback(move |uri: Uri| {
+        let files = files.clone();
+        async move {
+            match files.get(uri.path()) {
+                Some(contents) => Ok(contents.clone()),
+                None => Err(StatusCode::NOT_FOUND),
+            }
+        }
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    addr
+}
@@ -0,0 +1,467 @@
+//! Storage verification.
+//!
+//! Chunks are downloaded from the storage backend, and both their
+//! stored and uncompressed contents are rehashed and checked against
+//! the database. NARs can optionally be reassembled to check their
+//! NAR hashes as well.
+//!
+//! Corrupted chunks are transitioned into the `Deleted` state, which
+//! makes them invisible and lets garbage collection remove them from
+//! the storage backend. Their chunk references are broken and the NARs
+//! using them lose their completeness hints, so `get-missing-paths`
+//! reports the affected paths and clients pushing them will upload
+//! them again.
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use sea_orm::entity::prelude::*;
+use sea_orm::query::{QueryOrder, QuerySelect};
+use sea_orm::sea_query::{Expr, Query};
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time;
+use tracing::instrument;
+
+use super::{State, StateInner};
+use crate::config::Config;
+use crate::database::entity::chunk::{self, ChunkModel, ChunkState, Entity as Chunk};
+use crate::database::entity::chunkref::{self, Entity as ChunkRef};
+use crate::database::entity::nar::{self, Entity as Nar, NarModel, NarState};
+use crate::encryption::{open_chunk, MasterKey};
+use crate::narinfo::Compression;
+use crate::storage::{Download, StorageBackend};
+use bunker::hash::Hash;
+use bunker::stream::StreamHasher;
+
+/// Number of rows to fetch from the database at once.
+const BATCH_SIZE: u64 = 500;
+
+/// Number of chunks to verify concurrently.
+const VERIFY_CONCURRENCY: usize = 8;
+
+/// Size of the buffers chunks are streamed through.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Options for a verification run.
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Whether to reassemble NARs and verify their NAR hashes.
+    pub verify_nars: bool,
+
+    /// Whether to only report corruption without marking anything.
+    pub dry_run: bool,
+}
+
+/// The outcome of a verification run.
+#[derive(Debug, Default)]
+pub struct VerificationReport {
+    /// Number of chunks whose contents were checked.
+    pub chunks_verified: u64,
+
+    /// IDs of chunks whose contents don't match the database.
+    pub corrupted_chunks: Vec<i64>,
+
+    /// IDs of chunks that couldn't be downloaded.
+    ///
+    /// These may be transient failures, so the chunks are left as-is.
+    pub unreadable_chunks: Vec<i64>,
+
+    /// Number of NARs whose NAR hashes were checked.
+    pub nars_verified: u64,
+
+    /// IDs of NARs whose reassembled contents don't match their NAR hashes.
+    pub corrupted_nars: Vec<i64>,
+}
+
+/// The result of verifying a single chunk.
+enum ChunkStatus {
+    Valid,
+    Corrupted(String),
+    Unreadable(String),
+}
+
+/// Runs verification periodically.
+pub async fn run_verification(config: Config) {
+    let interval = config.verification.interval;
+
+    if interval == Duration::ZERO {
+        // disabled
+        return;
+    }
+
+    let options = VerifyOptions {
+        verify_nars: config.verification.verify_nars,
+        dry_run: false,
+    };
+
+    loop {
+        // We don't stop even if it errors
+        if let Err(e) = run_verification_once(config.clone(), options.clone()).await {
+            tracing::warn!("Storage verification failed: {}", e);
+        }
+
+        time::sleep(interval).await;
+    }
+}
+
+/// Runs verification once.
+#[instrument(skip_all)]
+pub async fn run_verification_once(
+    config: Config,
+    options: VerifyOptions,
+) -> Result<VerificationReport> {
+    tracing::info!("Running storage verification...");
+
+    let state = StateInner::new(config).await;
+    let mut report = VerificationReport::default();
+
+    // Chunks that have already been checked while reassembling NARs
+    let mut verified_chunks = HashSet::new();
+
+    if options.verify_nars {
+        verify_nars(&state, &mut report, &mut verified_chunks).await?;
+    }
+
+    verify_chunks(&state, &mut report, &verified_chunks).await?;
+
+    tracing::info!(
+        "Verified {} chunks and {} NARs: {} corrupted chunks, {} unreadable chunks, {} corrupted NARs",
+        report.chunks_verified,
+        report.nars_verified,
+        report.corrupted_chunks.len(),
+        report.unreadable_chunks.len(),
+        report.corrupted_nars.len()
+    );
+
+    if !report.corrupted_chunks.is_empty() && !options.dry_run {
+        let db = state.database().await?;
+        mark_corrupted_chunks(db, &report.corrupted_chunks).await?;
+
+        tracing::info!(
+            "Marked {} corrupted chunks for re-upload",
+            report.corrupted_chunks.len()
+        );
+    }
+
+    Ok(report)
+}
+
+/// Verifies all valid chunks that haven't been verified yet.
+#[instrument(skip_all)]
+async fn verify_chunks(
+    state: &State,
+    report: &mut VerificationReport,
+    verified_chunks: &HashSet<i64>,
+) -> Result<()> {
+    let db = state.database().await?;
+    let storage = &***state.storage().await?;
+    let master_key = state.master_key.as_deref();
+
+    let mut last_id = 0;
+    loop {
+        let chunks: Vec<ChunkModel> = Chunk::find()
+            .filter(chunk::Column::State.eq(ChunkState::Valid))
+            .filter(chunk::Column::Id.gt(last_id))
+            .order_by_asc(chunk::Column::Id)
+            .limit(BATCH_SIZE)
+            .all(db)
+            .await?;
+
+        let Some(last) = chunks.last() else {
+            break;
+        };
+        last_id = last.id;
+
+        let results: Vec<(i64, Result<ChunkStatus>)> = stream::iter(chunks)
+            .filter(|chunk| futures::future::ready(!verified_chunks.contains(&chunk.id)))
+            .map(|chunk| async move {
+                let status = verify_chunk(storage, master_key, &chunk, None).await;
+                (chunk.id, status)
+            })
+            .buffer_unordered(VERIFY_CONCURRENCY)
+            .collect()
+            .await;
+
+        for (chunk_id, status) in results {
+            record_chunk_status(report, chunk_id, status?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reassembles all valid NARs and verifies their NAR hashes.
+///
+/// The constituent chunks are verified along the way. NARs with
+/// missing chunks are skipped.
+#[instrument(skip_all)]
+async fn verify_nars(
+    state: &State,
+    report: &mut VerificationReport,
+    verified_chunks: &mut HashSet<i64>,
+) -> Result<()> {
+    let db = state.database().await?;
+    let storage = &***state.storage().await?;
+    let master_key = state.master_key.as_deref();
+
+    let mut last_id = 0;
+    loop {
+        let nars: Vec<NarModel> = Nar::find()
+            .filter(nar::Column::State.eq(NarState::Valid))
+            .filter(nar::Column::Id.gt(last_id))
+            .order_by_asc(nar::Column::Id)
+            .limit(BATCH_SIZE)
+            .all(db)
+            .await?;
+
+        let Some(last) = nars.last() else {
+            break;
+        };
+        last_id = last.id;
+
+        for nar in nars {
+            let Some(chunks) = find_nar_chunks(db, &nar).await? else {
+                tracing::debug!("Skipping NAR {} with missing chunks", nar.id);
+                continue;
+            };
+
+            let mut hasher = Sha256::new();
+            let mut intact = true;
+            for chunk in &chunks {
+                let status = verify_chunk(storage, master_key, chunk, Some(&mut hasher)).await?;
+
+                intact &= matches!(status, ChunkStatus::Valid);
+                if verified_chunks.insert(chunk.id) {
+                    record_chunk_status(report, chunk.id, status);
+                }
+
+                if !intact {
+                    break;
+                }
+            }
+
+            if !intact {
+                continue;
+            }
+
+            let nar_hash = Hash::Sha256(hasher.finalize().as_slice().try_into().unwrap());
+            let nar_size: i64 = chunks.iter().map(|chunk| chunk.chunk_size).sum();
+
+            report.nars_verified += 1;
+            if nar_hash.to_typed_base16() != nar.nar_hash || nar_size != nar.nar_size {
+                tracing::error!(
+                    "NAR {} is corrupted: Expected {} ({} bytes), got {} ({} bytes)",
+                    nar.id,
+                    nar.nar_hash,
+                    nar.nar_size,
+                    nar_hash.to_typed_base16(),
+                    nar_size
+                );
+                report.corrupted_nars.push(nar.id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the chunks of a NAR in order, or `None` if any of them is missing.
+async fn find_nar_chunks(
+    db: &DatabaseConnection,
+    nar: &NarModel,
+) -> Result<Option<Vec<ChunkModel>>> {
+    let chunk_ids: Vec<Option<i64>> = ChunkRef::find()
+        .select_only()
+        .column(chunkref::Column::ChunkId)
+        .filter(chunkref::Column::NarId.eq(nar.id))
+        .order_by_asc(chunkref::Column::Seq)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let Some(chunk_ids) = chunk_ids.into_iter().collect::<Option<Vec<i64>>>() else {
+        return Ok(None);
+    };
+
+    let chunks: HashMap<i64, ChunkModel> = Chunk::find()
+        .filter(chunk::Column::Id.is_in(chunk_ids.iter().copied()))
+        .filter(chunk::Column::State.eq(ChunkState::Valid))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|chunk| (chunk.id, chunk))
+        .collect();
+
+    // The same chunk can appear several times in a NAR
+    Ok(chunk_ids
+        .iter()
+        .map(|chunk_id| chunks.get(chunk_id).cloned())
+        .collect())
+}
+
+/// Checks the contents of a chunk against its hashes and sizes.
+///
+/// The uncompressed contents are also fed into `nar_hasher`.
+async fn verify_chunk(
+    storage: &dyn StorageBackend,
+    master_key: Option<&MasterKey>,
+    chunk: &ChunkModel,
+    mut nar_hasher: Option<&mut Sha256>,
+) -> Result<ChunkStatus> {
+    let stored = match storage.download_file_db(&chunk.remote_file.0, true).await {
+        Ok(Download::AsyncRead(stream)) => stream,
+        Ok(Download::Url(_)) => {
+            return Ok(ChunkStatus::Unreadable(
+                "Storage returned a URL".to_string(),
+            ));
+        }
+        Err(e) => return Ok(ChunkStatus::Unreadable(e.to_string())),
+    };
+
+    let compression = Compression::from_str(&chunk.compression)?;
+    let stored = open_chunk(master_key, chunk, stored)?;
+    let (mut stored, file_compute) = StreamHasher::new(stored, Sha256::new());
+
+    let (pipe_writer, pipe_reader) = tokio::io::duplex(BUFFER_SIZE);
+    let decompressed = compression.decompress(Box::new(pipe_reader))?;
+    let (mut decompressed, chunk_compute) = StreamHasher::new(decompressed, Sha256::new());
+
+    // The decompressor may stop reading at the end of the compressed
+    // data, but the file hash covers everything that is stored
+    let feed = async move {
+        let mut pipe_writer = Some(pipe_writer);
+        let mut buf = vec![0; BUFFER_SIZE];
+
+        loop {
+            let len = stored.read(&mut buf).await?;
+            if len == 0 {
+                return Ok::<_, IoError>(());
+            }
+
+            if let Some(writer) = &mut pipe_writer {
+                if writer.write_all(&buf[..len]).await.is_err() {
+                    pipe_writer = None;
+                }
+            }
+        }
+    };
+
+    let drain = async move {
+        let mut buf = vec![0; BUFFER_SIZE];
+
+        loop {
+            let len = decompressed.read(&mut buf).await?;
+            if len == 0 {
+                return Ok::<_, IoError>(());
+            }
+
+            if let Some(hasher) = &mut nar_hasher {
+                hasher.update(&buf[..len]);
+            }
+        }
+    };
+
+    let (feed, drain) = tokio::join!(feed, drain);
+    if let Err(e) = feed.and(drain) {
+        return Ok(match e.kind() {
+            // Failed to decrypt or decompress
+            IoErrorKind::InvalidData | IoErrorKind::UnexpectedEof => {
+                ChunkStatus::Corrupted(e.to_string())
+            }
+            _ => ChunkStatus::Unreadable(e.to_string()),
+        });
+    }
+
+    let (file_hash, file_size) = file_compute.get().unwrap();
+    let (chunk_hash, chunk_size) = chunk_compute.get().unwrap();
+
+    let file_hash = Hash::Sha256(file_hash.as_slice().try_into().unwrap());
+    let chunk_hash = Hash::Sha256(chunk_hash.as_slice().try_into().unwrap());
+
+    if let (Some(expected_hash), Some(expected_size)) = (&chunk.file_hash, chunk.file_size) {
+        if file_hash.to_typed_base16() != *expected_hash || *file_size as i64 != expected_size {
+            return Ok(ChunkStatus::Corrupted(format!(
+                "Expected file hash {} ({} bytes), got {} ({} bytes)",
+                expected_hash,
+                expected_size,
+                file_hash.to_typed_base16(),
+                file_size
+            )));
+        }
+    }
+
+    if chunk_hash.to_typed_base16() != chunk.chunk_hash || *chunk_size as i64 != chunk.chunk_size {
+        return Ok(ChunkStatus::Corrupted(format!(
+            "Expected chunk hash {} ({} bytes), got {} ({} bytes)",
+            chunk.chunk_hash,
+            chunk.chunk_size,
+            chunk_hash.to_typed_base16(),
+            chunk_size
+        )));
+    }
+
+    Ok(ChunkStatus::Valid)
+}
+
+fn record_chunk_status(report: &mut VerificationReport, chunk_id: i64, status: ChunkStatus) {
+    match status {
+        ChunkStatus::Valid => {
+            report.chunks_verified += 1;
+        }
+        ChunkStatus::Corrupted(reason) => {
+            tracing::error!("Chunk {} is corrupted: {}", chunk_id, reason);
+            report.chunks_verified += 1;
+            report.corrupted_chunks.push(chunk_id);
+        }
+        ChunkStatus::Unreadable(reason) => {
+            tracing::warn!("Chunk {} could not be read: {}", chunk_id, reason);
+            report.unreadable_chunks.push(chunk_id);
+        }
+    }
+}
+
+/// Marks chunks as corrupted so the affected paths are uploaded again.
+async fn mark_corrupted_chunks(db: &DatabaseConnection, chunk_ids: &[i64]) -> Result<()> {
+    for batch in chunk_ids.chunks(BATCH_SIZE as usize) {
+        let txn = db.begin().await?;
+
+        let affected_nars = Query::select()
+            .from(ChunkRef)
+            .column(chunkref::Column::NarId)
+            .and_where(chunkref::Column::ChunkId.is_in(batch.iter().copied()))
+            .to_owned();
+
+        Nar::update_many()
+            .col_expr(nar::Column::CompletenessHint, Expr::value(false))
+            .filter(nar::Column::Id.in_subquery(affected_nars))
+            .exec(&txn)
+            .await?;
+
+        // Uploads of the affected NARs are no longer deduplicated
+        ChunkRef::update_many()
+            .col_expr(chunkref::Column::ChunkId, Expr::value(Option::<i64>::None))
+            .filter(chunkref::Column::ChunkId.is_in(batch.iter().copied()))
+            .exec(&txn)
+            .await?;
+
+        Chunk::update_many()
+            .col_expr(chunk::Column::State, Expr::value(ChunkState::Deleted))
+            .filter(chunk::Column::Id.is_in(batch.iter().copied()))
+            .filter(chunk::Column::State.eq(ChunkState::Valid))
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+    }
+
+    Ok(())
+}
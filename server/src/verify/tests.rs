@@ -0,0 +1,149 @@
+use axum::extract::{Extension, Json};
+
+use super::*;
+use crate::api::v1::get_missing_paths::get_missing_paths;
+use crate::storage::{LocalRemoteFile, RemoteFile};
+use crate::testing::{self, TestState};
+use bunker::api::v1::get_missing_paths::GetMissingPathsRequest;
+use bunker::cache::{CacheName, CacheNamePattern};
+use bunker::nix_store::StorePathHash;
+
+const STORE_PATH: &str = "/nix/store/00000000000000000000000000000001-hello";
+
+/// Returns a state with a single path made up of one chunk.
+async fn setup() -> (TestState, ChunkModel) {
+    let state = TestState::new().await;
+    let cache = state.create_cache("test").await;
+    state.push(&cache, STORE_PATH, &[], b"hello world").await;
+
+    let chunk = Chunk::find()
+        .one(state.database().await.unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+
+    (state, chunk)
+}
+
+/// Overwrites the stored file of a chunk.
+fn corrupt_chunk(state: &TestState, chunk: &ChunkModel) {
+    let RemoteFile::Local(LocalRemoteFile { name }) = &chunk.remote_file.0 else {
+        panic!("Chunk is not stored locally");
+    };
+
+    let path = state
+        .storage_path()
+        .join(&name[0..1])
+        .join(&name[0..2])
+        .join(name);
+    assert!(path.exists());
+
+    std::fs::write(path, b"HELLO WORLD").unwrap();
+}
+
+/// Returns whether a chunk is in a state.
+async fn chunk_has_state(state: &TestState, chunk: &ChunkModel, chunk_state: ChunkState) -> bool {
+    Chunk::find_by_id(chunk.id)
+        .filter(chunk::Column::State.eq(chunk_state))
+        .one(state.database().await.unwrap())
+        .await
+        .unwrap()
+        .is_some()
+}
+
+async fn missing_paths(state: &TestState) -> Vec<StorePathHash> {
+    let mut token = testing::token("alice");
+    token
+        .get_or_insert_permission_mut(CacheNamePattern::new("test".to_string()).unwrap())
+        .push = true;
+    let req_state = state.request_state(Some(token));
+
+    let hash = StorePathHash::new("00000000000000000000000000000001".to_string()).unwrap();
+    let res = get_missing_paths(
+        state.extension(),
+        Extension(req_state),
+        Json(GetMissingPathsRequest {
+            cache: CacheName::new("test".to_string()).unwrap(),
+            store_path_hashes: vec![hash],
+        }),
+    )
+    .await
+    .unwrap();
+
+    res.0.missing_paths
+}
+
+#[tokio::test]
+async fn test_verify_intact_chunks() {
+    let (state, _) = setup().await;
+
+    for verify_nars in [false, true] {
+        let options = VerifyOptions {
+            verify_nars,
+            dry_run: false,
+        };
+        let report = run_verification_once(state.config.clone(), options)
+            .await
+            .unwrap();
+
+        assert_eq!(1, report.chunks_verified);
+        assert!(report.corrupted_chunks.is_empty());
+        assert!(report.unreadable_chunks.is_empty());
+        assert_eq!(verify_nars as u64, report.nars_verified);
+        assert!(report.corrupted_nars.is_empty());
+    }
+
+    assert!(missing_paths(&state).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_verify_marks_corrupted_chunks() {
+    let (state, chunk) = setup().await;
+    let db = state.database().await.unwrap();
+
+    corrupt_chunk(&state, &chunk);
+
+    // A dry run only reports the corruption
+    let options = VerifyOptions {
+        verify_nars: false,
+        dry_run: true,
+    };
+    let report = run_verification_once(state.config.clone(), options)
+        .await
+        .unwrap();
+    assert_eq!(vec![chunk.id], report.corrupted_chunks);
+
+    assert!(chunk_has_state(&state, &chunk, ChunkState::Valid).await);
+    assert!(missing_paths(&state).await.is_empty());
+
+    let report = run_verification_once(state.config.clone(), VerifyOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(1, report.chunks_verified);
+    assert_eq!(vec![chunk.id], report.corrupted_chunks);
+
+    assert!(chunk_has_state(&state, &chunk, ChunkState::Deleted).await);
+
+    let chunkrefs = ChunkRef::find().all(db).await.unwrap();
+    assert_eq!(1, chunkrefs.len());
+    assert_eq!(None, chunkrefs[0].chunk_id);
+
+    let nar = Nar::find().one(db).await.unwrap().unwrap();
+    assert!(!nar.completeness_hint);
+
+    assert_eq!(
+        vec!["00000000000000000000000000000001"],
+        missing_paths(&state)
+            .await
+            .iter()
+            .map(|hash| hash.as_str())
+            .collect::<Vec<_>>()
+    );
+
+    // Deleted chunks are no longer verified
+    let report = run_verification_once(state.config.clone(), VerifyOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(0, report.chunks_verified);
+    assert!(report.corrupted_chunks.is_empty());
+}
@@ -0,0 +1,184 @@
+//! JSON Web Key Sets.
+//!
+//! Identity providers like GitHub Actions and GitLab publish the
+//! public keys their OIDC tokens are signed with as a JWKS. Keys
+//! are selected by the `kid` header of the token.
+//!
+//! Only signature keys for RS256, ES256 and EdDSA (Ed25519) are
+//! supported. Other keys in the set are ignored.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL_SAFE_NO_PAD, Engine};
+use jwt_simple::algorithms::{
+    ECDSAP256PublicKeyLike, ES256PublicKey, Ed25519PublicKey, EdDSAPublicKeyLike, RSAPublicKeyLike,
+};
+use jwt_simple::prelude::VerificationOptions;
+use serde::Deserialize;
+
+use crate::{Error, RS256PublicKey, Result, Token};
+
+/// A JSON Web Key Set.
+#[derive(Debug, Default)]
+pub struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+/// A verification key in a JSON Web Key Set.
+#[derive(Debug)]
+pub struct Jwk {
+    /// The ID of the key.
+    kid: Option<String>,
+
+    /// The public key.
+    key: JwkKey,
+}
+
+#[derive(Debug)]
+enum JwkKey {
+    RS256(RS256PublicKey),
+    ES256(ES256PublicKey),
+    EdDSA(Ed25519PublicKey),
+}
+
+/// A key as it appears in the JWKS document.
+#[derive(Debug, Deserialize)]
+struct RawJwk {
+    kty: String,
+    kid: Option<String>,
+    alg: Option<String>,
+    #[serde(rename = "use")]
+    key_use: Option<String>,
+    crv: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawJwks {
+    keys: Vec<RawJwk>,
+}
+
+impl Jwks {
+    /// Parses a JWKS document.
+    ///
+    /// Unsupported keys are skipped.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let raw: RawJwks = serde_json::from_str(json).map_err(Error::JwksError)?;
+
+        let keys = raw
+            .keys
+            .into_iter()
+            .filter_map(|raw| {
+                let kid = raw.kid.clone();
+                match JwkKey::from_raw(raw) {
+                    Ok(key) => key.map(|key| Jwk { kid, key }),
+                    Err(e) => {
+                        tracing::debug!("Ignoring invalid JWK {:?}: {}", kid, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Ok(Self { keys })
+    }
+
+    /// Returns the number of usable keys.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns whether there are no usable keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the keys that may have signed a token.
+    ///
+    /// If the token has a `kid`, only the key with the same ID is
+    /// returned. Otherwise, all keys for the algorithm are.
+    pub(crate) fn find_keys<'a>(
+        &'a self,
+        kid: Option<&'a str>,
+        alg: &'a str,
+    ) -> impl Iterator<Item = &'a Jwk> {
+        self.keys
+            .iter()
+            .filter(move |jwk| jwk.key.alg() == alg && (kid.is_none() || jwk.kid.as_deref() == kid))
+    }
+}
+
+impl Jwk {
+    /// Verifies and decodes a token with this key.
+    pub(crate) fn verify_token(&self, token: &str, opts: VerificationOptions) -> Result<Token> {
+        match &self.key {
+            JwkKey::RS256(key) => key.verify_token(token, Some(opts)),
+            JwkKey::ES256(key) => key.verify_token(token, Some(opts)),
+            JwkKey::EdDSA(key) => key.verify_token(token, Some(opts)),
+        }
+        .map_err(Error::TokenError)
+        .map(Token)
+    }
+}
+
+impl JwkKey {
+    /// Converts a key from the JWKS document.
+    ///
+    /// Returns `None` if the key isn't a supported signature key.
+    fn from_raw(raw: RawJwk) -> Result<Option<Self>> {
+        if raw
+            .key_use
+            .as_deref()
+            .is_some_and(|key_use| key_use != "sig")
+        {
+            return Ok(None);
+        }
+
+        let key = match (raw.kty.as_str(), raw.crv.as_deref()) {
+            ("RSA", _) => {
+                let n = decode_param(&raw.n, "n")?;
+                let e = decode_param(&raw.e, "e")?;
+                let key = RS256PublicKey::from_components(&n, &e).map_err(Error::TokenError)?;
+                Self::RS256(key)
+            }
+            ("EC", Some("P-256")) => {
+                // Uncompressed SEC1 point
+                let mut point = vec![0x04];
+                point.extend(decode_param(&raw.x, "x")?);
+                point.extend(decode_param(&raw.y, "y")?);
+                let key = ES256PublicKey::from_bytes(&point).map_err(Error::TokenError)?;
+                Self::ES256(key)
+            }
+            ("OKP", Some("Ed25519")) => {
+                let x = decode_param(&raw.x, "x")?;
+                let key = Ed25519PublicKey::from_bytes(&x).map_err(Error::TokenError)?;
+                Self::EdDSA(key)
+            }
+            _ => return Ok(None),
+        };
+
+        // Keys may be restricted to a different algorithm (e.g., RS512)
+        if raw.alg.is_some_and(|alg| alg != key.alg()) {
+            return Ok(None);
+        }
+
+        Ok(Some(key))
+    }
+
+    /// Returns the JWT algorithm name of the key.
+    fn alg(&self) -> &'static str {
+        match self {
+            Self::RS256(_) => "RS256",
+            Self::ES256(_) => "ES256",
+            Self::EdDSA(_) => "EdDSA",
+        }
+    }
+}
+
+fn decode_param(param: &Option<String>, name: &'static str) -> Result<Vec<u8>> {
+    let param = param.as_ref().ok_or(Error::JwkMissingParameter(name))?;
+    BASE64_URL_SAFE_NO_PAD
+        .decode(param)
+        .map_err(Error::Base64Error)
+}
@@ -6,6 +6,7 @@
 //! {
 //!   "sub": "meow",
 //!   "exp": 4102324986,
+//!   "jti": "0d1c3a6e-3e5f-4b0f-8d0a-6f1f2b5c9e47",
 //!   "https://jwt.bunker.rs/v1": {
 //!     "caches": {
 //!       "cache-rw": {
@@ -40,6 +41,7 @@
     deny(unused_imports, unused_mut, unused_variables)
 )]
 
+pub mod jwks;
 pub mod util;
 
 #[cfg(test)]
@@ -47,6 +49,7 @@ mod tests;
 
 use std::collections::HashSet;
 use std::error::Error as StdError;
+use std::sync::Arc;
 
 use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
 use chrono::{DateTime, Utc};
@@ -60,8 +63,10 @@ pub use jwt_simple::{
 };
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, BoolFromInt};
+use uuid::Uuid;
 
 use bunker::cache::{CacheName, CacheNamePattern};
+use jwks::Jwks;
 
 /// Custom claim namespace for the BunkerAccess information.
 ///
@@ -98,8 +103,18 @@ pub struct Token(JWTClaims<TokenClaims>);
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct TokenClaims {
     /// Bunker namespace.
+    ///
+    /// Tokens from external issuers don't have this claim.
     #[serde(rename = "https://jwt.bunker.rs/v1")]
+    #[serde(default)]
     bunker_ns: BunkerAccess,
+
+    /// Other non-standard claims.
+    ///
+    /// These are set by external issuers, like the repository
+    /// in tokens issued by CI systems.
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Permissions granted to a client.
@@ -159,6 +174,13 @@ pub struct CachePermission {
     #[serde_as(as = "BoolFromInt")]
     pub configure_cache_retention: bool,
 
+    /// Can manage pinned store paths.
+    #[serde(default = "CachePermission::permission_default")]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[serde(rename = "pn")]
+    #[serde_as(as = "BoolFromInt")]
+    pub pin: bool,
+
     /// Can destroy the cache itself.
     #[serde(default = "CachePermission::permission_default")]
     #[serde(skip_serializing_if = "std::ops::Not::not")]
@@ -196,6 +218,24 @@ pub enum Error {
 
     /// Pubkey-only JWT authentication cannot create signed JWTs
     PubkeyOnlyCannotCreateToken,
+
+    /// JWKS parse error: {0}
+    JwksError(serde_json::Error),
+
+    /// JWK is missing the "{0}" parameter
+    JwkMissingParameter(&'static str),
+
+    /// No key in the JWKS matches the token
+    NoMatchingKey,
+
+    /// Unknown permission "{0}"
+    UnknownPermission(String),
+
+    /// Cannot delegate permissions on "{0}" that the token doesn't have
+    DelegationExceedsPermissions(String),
+
+    /// Cannot delegate a token that outlives the token it's issued with
+    DelegationExceedsExpiry,
 }
 
 /// The supported JWT signature types.
@@ -203,6 +243,11 @@ pub enum SignatureType {
     HS256(HS256Key),
     RS256(RS256KeyPair),
     RS256PubkeyOnly(RS256PublicKey),
+
+    /// Keys from a JSON Web Key Set, selected by `kid`.
+    ///
+    /// RS256, ES256 and EdDSA keys are supported.
+    Jwks(Arc<Jwks>),
 }
 
 impl Token {
@@ -248,13 +293,30 @@ impl Token {
                 .verify_token(token, Some(opts))
                 .map_err(Error::TokenError)
                 .map(Token),
+            SignatureType::Jwks(jwks) => {
+                let metadata =
+                    jwt_simple::token::Token::decode_metadata(token).map_err(Error::TokenError)?;
+
+                let mut result = Err(Error::NoMatchingKey);
+                for key in jwks.find_keys(metadata.key_id(), metadata.algorithm()) {
+                    result = key.verify_token(token, opts.clone());
+                    if result.is_ok() {
+                        break;
+                    }
+                }
+
+                result
+            }
         }
     }
 
     /// Creates a new token with an expiration timestamp.
+    ///
+    /// The token is assigned a random ID (`jti`) so it can be revoked.
     pub fn new(sub: String, exp: &DateTime<Utc>) -> Self {
         let claims = TokenClaims {
             bunker_ns: Default::default(),
+            extra: Default::default(),
         };
 
         let now_epoch = Utc::now().signed_duration_since(DateTime::UNIX_EPOCH);
@@ -271,7 +333,7 @@ impl Token {
             issuer: None,
             subject: Some(sub),
             audiences: None,
-            jwt_id: None,
+            jwt_id: Some(Uuid::new_v4().to_string()),
             nonce: None,
             custom: claims,
         })
@@ -296,7 +358,7 @@ impl Token {
         match signature_type {
             SignatureType::HS256(key) => key.authenticate(token).map_err(Error::TokenError),
             SignatureType::RS256(key) => key.sign(token).map_err(Error::TokenError),
-            SignatureType::RS256PubkeyOnly(_) => {
+            SignatureType::RS256PubkeyOnly(_) | SignatureType::Jwks(_) => {
                 return Err(Error::PubkeyOnlyCannotCreateToken);
             }
         }
@@ -307,6 +369,78 @@ impl Token {
         self.0.subject.as_deref()
     }
 
+    /// Returns the ID of the token.
+    ///
+    /// This is the `jti` claim. Tokens without one cannot be revoked.
+    pub fn jti(&self) -> Option<&str> {
+        self.0.jwt_id.as_deref()
+    }
+
+    /// Returns the expiration timestamp of the token.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.0
+            .expires_at
+            .and_then(|exp| DateTime::from_timestamp(exp.as_secs().try_into().ok()?, 0))
+    }
+
+    /// Returns all cache permissions granted by the token.
+    ///
+    /// Patterns are returned in the order they are matched.
+    pub fn permissions(&self) -> impl Iterator<Item = (&CacheNamePattern, &CachePermission)> {
+        self.bunker_access().caches.iter()
+    }
+
+    /// Returns the value of a claim as a string.
+    ///
+    /// Standard claims like `sub` and `iss` are supported as well.
+    /// Returns `None` if the claim is absent or isn't a scalar.
+    pub fn claim(&self, name: &str) -> Option<String> {
+        match name {
+            "sub" => self.0.subject.clone(),
+            "iss" => self.0.issuer.clone(),
+            "jti" => self.0.jwt_id.clone(),
+            _ => match self.0.custom.extra.get(name)? {
+                serde_json::Value::String(s) => Some(s.to_owned()),
+                serde_json::Value::Bool(b) => Some(b.to_string()),
+                serde_json::Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            },
+        }
+    }
+
+    /// Revokes all cache permissions granted by the token.
+    ///
+    /// This is used for tokens from external issuers, whose
+    /// permissions are derived from their claims instead.
+    pub fn clear_permissions(&mut self) {
+        self.bunker_access_mut().caches.clear();
+    }
+
+    /// Checks that another token can be issued on behalf of this one.
+    ///
+    /// The other token must not grant any permission on any cache
+    /// that this token doesn't, and must not expire later.
+    pub fn check_delegation(&self, child: &Token) -> Result<()> {
+        let outlives = match (self.0.expires_at, child.0.expires_at) {
+            (Some(exp), Some(child_exp)) => child_exp > exp,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if outlives {
+            return Err(Error::DelegationExceedsExpiry);
+        }
+
+        for (pattern, permission) in child.permissions() {
+            if !self.grants_everywhere(pattern, permission) {
+                return Err(Error::DelegationExceedsPermissions(
+                    pattern.as_str().to_owned(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Returns the claims as a serializable value.
     pub fn opaque_claims(&self) -> &impl Serialize {
         &self.0
@@ -344,6 +478,41 @@ impl Token {
         CachePermission::default()
     }
 
+    /// Returns whether a permission is granted on all caches matching a pattern.
+    ///
+    /// This mirrors the matching order of `get_permission_for_cache`:
+    /// Exact names take precedence over patterns, which are matched
+    /// in order.
+    fn grants_everywhere(&self, pattern: &CacheNamePattern, permission: &CachePermission) -> bool {
+        if let Ok(cache) = CacheName::new(pattern.as_str().to_owned()) {
+            return permission.is_subset_of(&self.get_permission_for_cache(&cache));
+        }
+
+        let (exact, wildcards): (Vec<_>, Vec<_>) = self
+            .bunker_access()
+            .caches
+            .iter()
+            .partition(|(ours, _)| CacheName::new(ours.as_str().to_owned()).is_ok());
+
+        for (ours, our_permission) in exact {
+            if pattern.contains(ours) && !permission.is_subset_of(our_permission) {
+                return false;
+            }
+        }
+
+        for (ours, our_permission) in wildcards {
+            let granted = permission.is_subset_of(our_permission);
+
+            if granted && ours.contains(pattern) {
+                return true;
+            } else if !granted && ours.may_overlap(pattern) {
+                return false;
+            }
+        }
+
+        !permission.can_discover()
+    }
+
     fn bunker_access(&self) -> &BunkerAccess {
         &self.0.custom.bunker_ns
     }
@@ -371,6 +540,56 @@ impl CachePermission {
             || self.configure_cache
             || self.destroy_cache
             || self.configure_cache_retention
+            || self.pin
+    }
+
+    /// Returns the names of the granted permissions.
+    pub fn granted(&self) -> Vec<&'static str> {
+        [
+            (self.pull, "pull"),
+            (self.push, "push"),
+            (self.delete, "delete"),
+            (self.create_cache, "create-cache"),
+            (self.configure_cache, "configure-cache"),
+            (self.configure_cache_retention, "configure-cache-retention"),
+            (self.destroy_cache, "destroy-cache"),
+            (self.pin, "pin"),
+        ]
+        .into_iter()
+        .filter_map(|(granted, name)| granted.then_some(name))
+        .collect()
+    }
+
+    /// Grants a permission by its name.
+    ///
+    /// The names are those returned by `granted`.
+    pub fn grant(&mut self, name: &str) -> Result<()> {
+        let permission = match name {
+            "pull" => &mut self.pull,
+            "push" => &mut self.push,
+            "delete" => &mut self.delete,
+            "create-cache" => &mut self.create_cache,
+            "configure-cache" => &mut self.configure_cache,
+            "configure-cache-retention" => &mut self.configure_cache_retention,
+            "destroy-cache" => &mut self.destroy_cache,
+            "pin" => &mut self.pin,
+            _ => return Err(Error::UnknownPermission(name.to_owned())),
+        };
+
+        *permission = true;
+        Ok(())
+    }
+
+    /// Returns whether all permissions granted are also granted by another.
+    pub const fn is_subset_of(&self, other: &CachePermission) -> bool {
+        (!self.pull || other.pull)
+            && (!self.push || other.push)
+            && (!self.delete || other.delete)
+            && (!self.create_cache || other.create_cache)
+            && (!self.configure_cache || other.configure_cache)
+            && (!self.configure_cache_retention || other.configure_cache_retention)
+            && (!self.destroy_cache || other.destroy_cache)
+            && (!self.pin || other.pin)
     }
 
     pub fn require_discover(&self) -> Result<()> {
@@ -396,6 +615,7 @@ impl CachePermission {
         configure_cache_retention
     );
     require_permission_function!(require_destroy_cache, "destroy cache", destroy_cache);
+    require_permission_function!(require_pin, "manage pins", pin);
 
     fn permission_default() -> bool {
         false
@@ -1,6 +1,6 @@
 use super::*;
 
-use bunker::cache::CacheName;
+use bunker::cache::{CacheName, CacheNamePattern};
 
 macro_rules! cache {
     ($n:expr) => {
@@ -127,3 +127,193 @@ fn test_basic() {
             .can_discover());
     }
 }
+
+#[test]
+fn test_jti() {
+    // printf '\xc3\x28 <- invalid utf8' | base64
+    let base64_secret = "wyggPC0gaW52YWxpZCB1dGY4";
+    let signature_type =
+        SignatureType::HS256(decode_token_hs256_secret_base64(base64_secret).unwrap());
+
+    let exp = Utc::now() + chrono::Duration::days(1);
+    let mut token = Token::new("meow".to_string(), &exp);
+    token
+        .get_or_insert_permission_mut(CacheNamePattern::new("cache-*".to_string()).unwrap())
+        .pull = true;
+
+    let other = Token::new("meow".to_string(), &exp);
+    assert!(token.jti().is_some());
+    assert_ne!(token.jti(), other.jti());
+
+    let encoded = token.encode(&signature_type, &None, &None).unwrap();
+    let decoded = Token::from_jwt(&encoded, &signature_type, &None, &None).unwrap();
+
+    assert_eq!(token.jti(), decoded.jti());
+    assert_eq!(
+        Some(exp.timestamp()),
+        decoded.expires_at().map(|exp| exp.timestamp())
+    );
+
+    let permissions: Vec<_> = decoded
+        .permissions()
+        .map(|(pattern, permission)| (pattern.as_str(), permission.granted()))
+        .collect();
+    assert_eq!(vec![("cache-*", vec!["pull"])], permissions);
+}
+
+#[test]
+fn test_delegation() {
+    macro_rules! token {
+        ($exp:expr, { $($pattern:literal: [$($perm:ident),*]),* $(,)? }) => {{
+            #[allow(unused_mut)]
+            let mut token = Token::new("meow".to_string(), &$exp);
+            $(
+                let _perm = token
+                    .get_or_insert_permission_mut(CacheNamePattern::new($pattern.to_string()).unwrap());
+                $(_perm.$perm = true;)*
+            )*
+            token
+        }};
+    }
+
+    let exp = Utc::now() + chrono::Duration::days(1);
+    let parent = token!(exp, {
+        "team-secret": [pull],
+        "team-a*": [pull],
+        "team-*": [pull, push],
+        "public": [pull],
+    });
+
+    for allowed in [
+        token!(exp, {}),
+        token!(exp, { "team-abc": [pull] }),
+        token!(exp, { "team-xyz": [pull, push] }),
+        token!(exp, { "team-b*": [pull, push] }),
+        token!(exp, { "team-*": [pull] }),
+        token!(exp, { "public": [pull], "team-a*": [pull] }),
+        token!(exp - chrono::Duration::hours(1), { "team-xyz": [push] }),
+    ] {
+        parent.check_delegation(&allowed).unwrap();
+    }
+
+    for denied in [
+        // Pattern not held at all
+        token!(exp, { "other": [pull] }),
+        token!(exp, { "*": [pull] }),
+        // More permissions than held
+        token!(exp, { "public": [push] }),
+        token!(exp, { "team-xyz": [delete] }),
+        // Exact names take precedence over patterns
+        token!(exp, { "team-secret": [push] }),
+        // Earlier patterns take precedence over later ones
+        token!(exp, { "team-abc": [push] }),
+        token!(exp, { "team-*": [push] }),
+        token!(exp, { "team*": [pull] }),
+    ] {
+        assert!(matches!(
+            parent.check_delegation(&denied),
+            Err(Error::DelegationExceedsPermissions(_))
+        ));
+    }
+
+    let outliving = token!(exp + chrono::Duration::hours(1), { "team-xyz": [pull] });
+    assert!(matches!(
+        parent.check_delegation(&outliving),
+        Err(Error::DelegationExceedsExpiry)
+    ));
+}
+
+#[test]
+fn test_grant_by_name() {
+    let mut permission = CachePermission::default();
+    permission.grant("pull").unwrap();
+    permission.grant("configure-cache-retention").unwrap();
+    permission.grant("nonexistent").unwrap_err();
+
+    assert_eq!(
+        vec!["pull", "configure-cache-retention"],
+        permission.granted()
+    );
+}
+
+#[test]
+fn test_jwks() {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL_SAFE_NO_PAD;
+    use jwt_simple::prelude::{
+        Claims, ECDSAP256KeyPairLike, ECDSAP256PublicKeyLike, ES256KeyPair, Ed25519KeyPair,
+        EdDSAKeyPairLike,
+    };
+
+    #[derive(Serialize, Deserialize)]
+    struct CiClaims {
+        repository: String,
+        #[serde(rename = "ref")]
+        git_ref: String,
+    }
+
+    let claims = || {
+        Claims::with_custom_claims(
+            CiClaims {
+                repository: "qompassai/bunker".to_string(),
+                git_ref: "refs/heads/main".to_string(),
+            },
+            Duration::from_hours(1),
+        )
+        .with_issuer("https://ci.example.com")
+        .with_subject("repo:qompassai/bunker")
+    };
+
+    let es256 = ES256KeyPair::generate().with_key_id("es");
+    let es256_point = es256.public_key().public_key().to_bytes_uncompressed();
+    let ed25519 = Ed25519KeyPair::generate().with_key_id("ed");
+    let ed25519_bytes = ed25519.public_key().to_bytes();
+
+    let jwks = format!(
+        r#"{{"keys": [
+            {{"kty": "EC", "crv": "P-256", "kid": "es", "x": "{}", "y": "{}"}},
+            {{"kty": "OKP", "crv": "Ed25519", "kid": "ed", "x": "{}"}},
+            {{"kty": "oct", "kid": "unsupported", "k": "c2VjcmV0"}}
+        ]}}"#,
+        BASE64_URL_SAFE_NO_PAD.encode(&es256_point[1..33]),
+        BASE64_URL_SAFE_NO_PAD.encode(&es256_point[33..]),
+        BASE64_URL_SAFE_NO_PAD.encode(&ed25519_bytes),
+    );
+    let jwks = jwks::Jwks::from_json(&jwks).unwrap();
+    assert_eq!(2, jwks.len());
+
+    let signature_type = SignatureType::Jwks(Arc::new(jwks));
+    let issuer = Some("https://ci.example.com".to_string());
+
+    for jwt in [
+        es256.sign(claims()).unwrap(),
+        ed25519.sign(claims()).unwrap(),
+    ] {
+        let token = Token::from_jwt(&jwt, &signature_type, &issuer, &None).unwrap();
+
+        assert_eq!(Some("repo:qompassai/bunker"), token.sub());
+        assert_eq!(
+            Some("qompassai/bunker".to_string()),
+            token.claim("repository")
+        );
+        assert_eq!(Some("refs/heads/main".to_string()), token.claim("ref"));
+        assert_eq!(None, token.claim("missing"));
+        assert_eq!(0, token.permissions().count());
+
+        // Bound to the issuer
+        let other_issuer = Some("https://other.example.com".to_string());
+        assert!(Token::from_jwt(&jwt, &signature_type, &other_issuer, &None).is_err());
+    }
+
+    // Keys are selected by ID
+    let unknown = ES256KeyPair::generate().with_key_id("unknown");
+    let jwt = unknown.sign(claims()).unwrap();
+    assert!(matches!(
+        Token::from_jwt(&jwt, &signature_type, &issuer, &None),
+        Err(Error::NoMatchingKey)
+    ));
+
+    // Keys with the right ID must still have signed the token
+    let impostor = ES256KeyPair::generate().with_key_id("es");
+    let jwt = impostor.sign(claims()).unwrap();
+    assert!(Token::from_jwt(&jwt, &signature_type, &issuer, &None).is_err());
+}